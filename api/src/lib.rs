@@ -0,0 +1,177 @@
+mod models;
+mod routes;
+mod search_query;
+mod utils;
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, State},
+    http::Request,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, patch, post, put, Router},
+};
+use chrono::Utc;
+use database::Repository;
+use http::StatusCode;
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use messaging::MessageBus;
+use models::api_models::AccessTokenClaims;
+use routes::{
+    clip_search::clip_search,
+    cluster_previews::cluster_previews,
+    create_face::create_face,
+    delete_face::delete_face,
+    edit_media::{edit_media, edit_media_bulk},
+    export_media::export_media,
+    face_clusters::{add_face_cluster, remove_face_cluster},
+    face_previews::face_previews,
+    face_suggestions::{accept_face_suggestion, face_suggestions, reject_face_suggestion},
+    faces::faces,
+    featured_photo::featured_photo,
+    hide_faces::{hide_cluster, hide_face},
+    image::image,
+    login::login,
+    logs::logs,
+    media::media,
+    merge_faces::merge_faces,
+    move_media_face::move_media_face,
+    people_previews::people_previews,
+    preview::preview,
+    previews::previews,
+    refresh::refresh,
+    register::register,
+    rename_face::rename_face,
+    similar_media::similar_media,
+    sync_full::sync_full,
+    sync_partial::sync_partial,
+    tag_face::tag_face,
+    upload_image::upload_image,
+    upload_sidecar::upload_sidecar,
+};
+use s3::Bucket;
+use std::{sync::Arc, time::Duration};
+pub use utils::search_cache::SearchCache;
+
+#[derive(Clone)]
+pub struct ServerConfig {
+    pub database: Arc<dyn Repository>,
+    pub secret: String,
+    pub bucket: Box<Bucket>,
+    pub bus: Arc<dyn MessageBus>,
+    pub search_cache: Arc<SearchCache>,
+    pub search_timeout: Duration,
+}
+
+pub fn router(server_config: ServerConfig) -> Router {
+    let public_routes = Router::new()
+        .route("/login", post(login))
+        .route("/register", post(register))
+        .route("/refresh", post(refresh));
+
+    let private_routes = Router::new()
+        .route(
+            "/image/upload",
+            post(upload_image).route_layer(DefaultBodyLimit::max(10737418240)),
+        )
+        .route("/sync/full", get(sync_full))
+        .route("/sync/partial", get(sync_partial))
+        .route("/previews", get(previews))
+        .route("/preview/:media_id", get(preview))
+        .route("/image/:media_id", get(image))
+        .route("/media", patch(edit_media_bulk))
+        .route("/media/:media_id", get(media).patch(edit_media))
+        .route("/media/:media_id/export", get(export_media))
+        .route("/media/:media_id/similar", get(similar_media))
+        .route("/media/:media_id/faces", post(tag_face))
+        .route("/media/:media_id/sidecar", put(upload_sidecar))
+        .route("/logs", get(logs))
+        .route("/faces", get(faces))
+        .route("/faces/previews", get(people_previews))
+        .route("/cluster/:cluster_id", get(cluster_previews))
+        .route("/cluster/:cluster_id/hidden", put(hide_cluster))
+        .route("/face/:face_id", get(face_previews).delete(delete_face))
+        .route("/face/:face_id/name", put(rename_face))
+        .route("/face/:face_id/merge", post(merge_faces))
+        .route("/face/:face_id/featured_photo", put(featured_photo))
+        .route("/face/:face_id/hidden", put(hide_face))
+        .route(
+            "/face/:face_id/cluster/:cluster_id",
+            put(add_face_cluster).delete(remove_face_cluster),
+        )
+        .route("/media_face/:media_face_id", put(move_media_face))
+        .route("/suggestions", get(face_suggestions))
+        .route(
+            "/suggestions/:suggestion_id/accept",
+            post(accept_face_suggestion),
+        )
+        .route(
+            "/suggestions/:suggestion_id/reject",
+            post(reject_face_suggestion),
+        )
+        .route("/search", get(clip_search))
+        .route("/create_face", post(create_face))
+        .layer(middleware::from_fn_with_state(
+            server_config.secret.clone(),
+            auth_middleware,
+        ));
+
+    public_routes
+        .merge(private_routes)
+        .with_state(server_config)
+}
+
+async fn auth_middleware(
+    State(secret): State<String>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let authorization_header = match req.headers_mut().get(http::header::AUTHORIZATION) {
+        Some(header) => header,
+        None => return (StatusCode::UNAUTHORIZED, "No authorization header found").into_response(),
+    };
+    let mut authorization_header_str = match authorization_header.to_str() {
+        Ok(token) => token.split_whitespace(),
+        Err(..) => {
+            return (StatusCode::UNAUTHORIZED, "Authorization header is empty").into_response()
+        }
+    };
+
+    let (bearer_keyword, jwt_header) = (
+        authorization_header_str.next(),
+        authorization_header_str.next(),
+    );
+
+    if bearer_keyword != Some("Bearer") {
+        return (
+            StatusCode::UNAUTHORIZED,
+            "Authorization header must contain a Bearer token",
+        )
+            .into_response();
+    }
+
+    let secret = &DecodingKey::from_secret(secret.as_ref());
+
+    let result = match decode::<AccessTokenClaims>(
+        jwt_header.unwrap(),
+        secret,
+        &Validation::new(jsonwebtoken::Algorithm::HS256),
+    ) {
+        Ok(token) => token,
+        Err(err) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                format!("Could not decode JWT token {}", err),
+            )
+                .into_response()
+        }
+    };
+
+    let now = Utc::now().timestamp_millis();
+    if now < result.claims.iat || now > result.claims.exp {
+        return (StatusCode::UNAUTHORIZED, "Authorization header is invalid").into_response();
+    }
+
+    req.extensions_mut().insert(result.claims.user_id);
+
+    next.run(req).await
+}
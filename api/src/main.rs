@@ -1,34 +1,9 @@
-mod models;
-mod routes;
-mod utils;
-use axum::{
-    body::Body,
-    extract::{DefaultBodyLimit, State},
-    http::Request,
-    middleware::{self, Next},
-    response::{IntoResponse, Response},
-    routing::{get, post, Router},
-};
-use chrono::Utc;
+use api::{router, SearchCache, ServerConfig};
 use database::DbManager;
-use http::StatusCode;
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use models::api_models::AccessTokenClaims;
-use routes::{
-    clip_search::clip_search, cluster_previews::cluster_previews, create_face::create_face, face_previews::face_previews, faces::faces, login::login, logs::logs, media::media, preview::preview, previews::previews, refresh::refresh, register::register, sync_full::sync_full, sync_partial::sync_partial, upload_image::upload_image
-
-};
+use messaging::NatsBus;
 use s3::{creds::Credentials, error::S3Error, Bucket, BucketConfiguration, Region};
 use serde::Deserialize;
-
-#[derive(Clone)]
-pub struct ServerConfig {
-    pub database: DbManager,
-    pub secret: String,
-    pub bucket: Box<Bucket>,
-    pub nats_jetstream: async_nats::jetstream::Context,
-    pub nats_client: async_nats::Client,
-}
+use std::{sync::Arc, time::Duration};
 
 #[derive(Deserialize, Debug)]
 pub struct EnvVars {
@@ -51,6 +26,12 @@ pub struct EnvVars {
     pub object_storage_secret_key: String,
     #[serde(alias = "OBJECT_STORAGE_REGION")]
     pub object_storage_region: String,
+    #[serde(alias = "SEARCH_TIMEOUT_SECONDS")]
+    #[serde(default = "search_timeout_seconds_default")]
+    pub search_timeout_seconds: u64,
+    #[serde(alias = "SEARCH_CACHE_TTL_SECONDS")]
+    #[serde(default = "search_cache_ttl_seconds_default")]
+    pub search_cache_ttl_seconds: u64,
 }
 
 fn listen_on_default() -> String {
@@ -65,6 +46,14 @@ fn object_storage_endpoint_default() -> String {
     "http://localhost".to_string()
 }
 
+fn search_timeout_seconds_default() -> u64 {
+    10
+}
+
+fn search_cache_ttl_seconds_default() -> u64 {
+    300
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
@@ -85,56 +74,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(err) => panic!("{}", err),
     };
 
-    let nats_client = match async_nats::connect(environment_variables.nats_endpoint.clone()).await {
-        Ok(c) => c,
+    let bus = match NatsBus::connect(environment_variables.nats_endpoint.clone()).await {
+        Ok(bus) => bus,
         Err(err) => {
             panic!("Couldn't connect to NATS client: {err}");
         }
     };
 
-    let nats_jetstream = async_nats::jetstream::new(nats_client.clone());
-
     let server_config = ServerConfig {
-        database,
+        database: Arc::new(database),
         secret,
         bucket,
-        nats_jetstream,
-        nats_client,
+        bus: Arc::new(bus),
+        search_cache: Arc::new(SearchCache::new(Duration::from_secs(
+            environment_variables.search_cache_ttl_seconds,
+        ))),
+        search_timeout: Duration::from_secs(environment_variables.search_timeout_seconds),
     };
 
-    let public_routes = Router::new()
-        .route("/login", post(login))
-        .route("/register", post(register))
-        .route("/refresh", post(refresh));
-
-        let private_routes = Router::new()
-        .route(
-            "/image/upload",
-            post(upload_image).route_layer(DefaultBodyLimit::max(10737418240)),
-        )
-        .route("/sync/full", get(sync_full))
-        .route("/sync/partial", get(sync_partial))
-        .route("/previews", get(previews))
-        .route("/preview/:media_id", get(preview))
-        .route("/media/:media_id", get(media))
-        .route("/logs", get(logs))
-        .route("/faces", get(faces))
-        .route("/cluster/:cluster_id", get(cluster_previews))
-        .route("/face/:face_id", get(face_previews))
-        .route("/search", get(clip_search))
-        .route("/create_face", post(create_face)) 
-        .layer(middleware::from_fn_with_state(
-            server_config.secret.clone(),
-            auth_middleware,
-        ));
-
     let listener = tokio::net::TcpListener::bind(&environment_variables.listen_on)
         .await
         .unwrap();
 
-    let app = public_routes
-        .merge(private_routes)
-        .with_state(server_config);
+    let app = router(server_config);
     axum::serve(listener, app).await.unwrap();
 
     Ok(())
@@ -173,59 +135,3 @@ async fn setup_bucket(envs: &EnvVars) -> Result<Box<Bucket>, S3Error> {
     }
     Ok(bucket)
 }
-
-async fn auth_middleware(
-    State(secret): State<String>,
-    mut req: Request<Body>,
-    next: Next,
-) -> Response {
-    let authorization_header = match req.headers_mut().get(http::header::AUTHORIZATION) {
-        Some(header) => header,
-        None => return (StatusCode::UNAUTHORIZED, "No authorization header found").into_response(),
-    };
-    let mut authorization_header_str = match authorization_header.to_str() {
-        Ok(token) => token.split_whitespace(),
-        Err(..) => {
-            return (StatusCode::UNAUTHORIZED, "Authorization header is empty").into_response()
-        }
-    };
-
-    let (bearer_keyword, jwt_header) = (
-        authorization_header_str.next(),
-        authorization_header_str.next(),
-    );
-
-    if bearer_keyword != Some("Bearer") {
-        return (
-            StatusCode::UNAUTHORIZED,
-            "Authorization header must contain a Bearer token",
-        )
-            .into_response();
-    }
-
-    let secret = &DecodingKey::from_secret(secret.as_ref());
-
-    let result = match decode::<AccessTokenClaims>(
-        jwt_header.unwrap(),
-        secret,
-        &Validation::new(jsonwebtoken::Algorithm::HS256),
-    ) {
-        Ok(token) => token,
-        Err(err) => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                format!("Could not decode JWT token {}", err),
-            )
-                .into_response()
-        }
-    };
-
-    let now = Utc::now().timestamp_millis();
-    if now < result.claims.iat || now > result.claims.exp {
-        return (StatusCode::UNAUTHORIZED, "Authorization header is invalid").into_response();
-    }
-
-    req.extensions_mut().insert(result.claims.user_id);
-
-    next.run(req).await
-}
@@ -1,6 +1,4 @@
-use std::collections::HashMap;
-
-use database::{RemoteMediaAdded, RemoteMediaDeleted};
+use database::{MediaPreview, RemoteMediaAdded, RemoteMediaDeleted};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -48,18 +46,6 @@ pub struct PartialSyncResponse {
     pub deleted: Vec<RemoteMediaDeleted>,
 }
 
-#[derive(Serialize)]
-#[serde(transparent)]
-pub struct PreviewResponse {
-    pub previews: HashMap<String, String>,
-}
-
-#[derive(Serialize)]
-#[serde(transparent)]
-pub struct ClusterPreviewResponse {
-    pub previews: Vec<HashMap<String, String>>,
-}
-
 #[derive(Serialize)]
 pub struct GetFacesResponse {
     pub faces: Vec<FaceResponse>,
@@ -72,6 +58,7 @@ pub struct FaceResponse {
     pub name: String,
     pub photo_url: String,
     pub bbox: Vec<i32>,
+    pub crop_url: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -79,6 +66,18 @@ pub struct ClusterResponse {
     pub cluster_id: i32,
     pub photo_url: String,
     pub bbox: Vec<i32>,
+    pub crop_url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct FaceSuggestionResponse {
+    pub suggestion_id: i32,
+    pub media_face_id: i32,
+    pub face_id: i32,
+    pub name: String,
+    pub photo_url: String,
+    pub bbox: Vec<i32>,
+    pub distance: f32,
 }
 
 #[derive(Deserialize)]
@@ -87,17 +86,82 @@ pub struct Pagination {
     pub page_size: Option<u64>,
 }
 
+// The size is the name of a rendition, e.g. grid or display, or a height in pixels.
+// Without one the default preview is returned
+#[derive(Deserialize)]
+pub struct RenditionQuery {
+    pub size: Option<String>,
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PreviewsQuery {
+    pub page: Option<u64>,
+    pub page_size: Option<u64>,
+    pub size: Option<String>,
+    pub format: Option<String>,
+}
+
+// Comma separated face ids, see `database::PeopleQuery`
+#[derive(Deserialize)]
+pub struct PeoplePreviewsQuery {
+    pub all: Option<String>,
+    pub any: Option<String>,
+    pub none: Option<String>,
+    pub page: Option<u64>,
+    pub page_size: Option<u64>,
+}
+
+// Width and height are the ones of the upright image, enough to lay out a justified grid.
+// They are missing until the preview worker has decoded the media
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PreviewItem {
     pub id: String,
     pub preview_url: String,
+    pub created_at: i64,
+    pub utc_offset: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub media_type: Option<String>,
+    // Painted until the preview is loaded
+    pub blurhash: Option<String>,
+    pub dominant_colors: Vec<String>,
+}
+
+impl PreviewItem {
+    pub fn new(preview: MediaPreview, preview_url: String) -> Self {
+        PreviewItem {
+            id: preview.id,
+            preview_url,
+            created_at: preview.created_at,
+            utc_offset: preview.utc_offset,
+            width: preview.width,
+            height: preview.height,
+            media_type: preview.media_type,
+            blurhash: preview.blurhash,
+            dominant_colors: preview.dominant_colors,
+        }
+    }
+}
+
+// A photo of a person, with the crop of their face once the preview worker made it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FacePreviewItem {
+    #[serde(flatten)]
+    pub media: PreviewItem,
+    pub crop_url: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct MediaMetadataResponse {
     pub id: String,
     pub media_url: String,
+    // What browsers can show, the original or its full resolution copy. Missing until the
+    // copy of a media browsers can't show is made
+    pub display_url: Option<String>,
     pub created_at: i64,
+    pub uploaded_at: i64,
+    pub utc_offset: Option<i32>,
     pub file_size: i64,
     pub file_name: String,
     pub longitude: Option<f64>,
@@ -110,18 +174,130 @@ pub struct MediaMetadataResponse {
     pub exposure_time: Option<String>,
     pub photographic_sensitivity: Option<String>,
     pub orientation: Option<i32>,
+    pub lens_make: Option<String>,
+    pub lens_model: Option<String>,
+    pub focal_length: Option<f64>,
+    pub focal_length_35mm: Option<i32>,
+    pub altitude: Option<f64>,
+    pub image_direction: Option<f64>,
+    pub flash: Option<String>,
+    pub white_balance: Option<String>,
+    pub metering_mode: Option<String>,
+    pub exposure_program: Option<String>,
+    pub exposure_bias: Option<f64>,
+    pub software: Option<String>,
+    pub artist: Option<String>,
+    pub exif: Option<serde_json::Value>,
+    pub rating: Option<i32>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub people: Vec<String>,
 }
 
 #[derive(Deserialize)]
 pub struct SearchQuery {
-    pub query: String,
+    // Missing or empty when the filters alone select the media
+    pub query: Option<String>,
     pub page: Option<u32>,
     pub page_size: Option<u32>,
+    pub after: Option<i64>,
+    pub before: Option<i64>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub has_gps: Option<bool>,
+    pub face_id: Option<i32>,
+    pub cluster_id: Option<i32>,
+    // Minimum score of the results
+    pub threshold: Option<f32>,
 }
 
+// A media ranked by the search service, the score is higher for more relevant media
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchResult {
+    pub id: String,
+    #[serde(default)]
+    pub score: Option<f32>,
+}
+
+// Media listed by a search are missing the score when the query has no text to rank them by
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchItem {
+    #[serde(flatten)]
+    pub media: PreviewItem,
+    pub score: Option<f32>,
+}
+
+#[derive(Deserialize)]
+pub struct RenameFacePayload {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct MergeFacesPayload {
+    pub face_id: i32,
+}
+
+// Exactly one of the targets has to be given
+#[derive(Deserialize)]
+pub struct MoveMediaFacePayload {
+    pub cluster_id: Option<i32>,
+    pub face_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct FeaturedPhotoPayload {
+    pub media_id: String,
+}
+
+// Exactly one of face_id and name has to be given, a name creates a new face
+#[derive(Deserialize)]
+pub struct TagFacePayload {
+    pub bbox: Vec<i32>,
+    pub face_id: Option<i32>,
+    pub name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TagFaceResponse {
+    pub media_face_id: i32,
+    pub face_id: i32,
+}
+
+#[derive(Deserialize)]
+pub struct HiddenPayload {
+    pub hidden: bool,
+}
+
+#[derive(Deserialize)]
+pub struct FacesQuery {
+    #[serde(default)]
+    pub hidden: bool,
+}
 
 #[derive(Deserialize)]
 pub struct CreateFacePayload {
     pub ids: Vec<i32>,
     pub name: String,
-}
\ No newline at end of file
+}
+
+// Latitude and longitude go together, the capture time is either set or shifted.
+// The utc offset is in minutes east of UTC and the rating goes from -1 for rejected media to 5
+#[derive(Deserialize, Default)]
+pub struct EditMediaPayload {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub created_at: Option<i64>,
+    pub shift_hours: Option<f64>,
+    pub utc_offset: Option<i32>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub rating: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct BulkEditMediaPayload {
+    pub media_ids: Vec<String>,
+    #[serde(flatten)]
+    pub edit: EditMediaPayload,
+}
@@ -1,32 +1,187 @@
 use crate::{
-    models::api_models::{PreviewItem, SearchQuery},
+    models::api_models::{SearchItem, SearchQuery, SearchResult},
+    search_query::{self, ParsedQuery},
+    utils::renditions::preview_item,
     ServerConfig,
 };
-use async_nats::Client;
 use axum::{
     extract::{Extension, Query, State},
     response::{IntoResponse, Response},
     Json,
 };
+use database::{MediaFilter, MediaPreview};
 use http::StatusCode;
 use serde_json::json;
+use std::collections::{HashMap, HashSet};
+
+// How many clip results are fetched, filtered and cached for a query
+const SEARCH_CANDIDATES: u32 = 500;
 
 pub async fn clip_search(
     State(server_config): State<ServerConfig>,
     Extension(user_id): Extension<String>,
     Query(params): Query<SearchQuery>,
 ) -> Response {
-    let query = params.query;
     let page = params.page.unwrap_or(1).max(1);
     let page_size = params.page_size.unwrap_or(10).clamp(1, 30);
+    let filter = MediaFilter {
+        after: params.after,
+        before: params.before,
+        make: params.make,
+        model: params.model,
+        has_gps: params.has_gps,
+        face_id: params.face_id,
+        cluster_id: params.cluster_id,
+        ..Default::default()
+    };
 
     // println!("Received request for clip_search with query: {}, page: {}, pagesize: {}", query, page, page_size);
 
+    let ParsedQuery {
+        text: query,
+        filter,
+    } = match search_query::parse(params.query.as_deref().unwrap_or_default(), filter) {
+        Ok(parsed) => parsed,
+        Err(err) => return (StatusCode::BAD_REQUEST, Json(err)).into_response(),
+    };
+
+    if let (Some(after), Some(before)) = (filter.after, filter.before) {
+        if after >= before {
+            return (StatusCode::BAD_REQUEST, "After must be earlier than before").into_response();
+        }
+    }
+
+    // Without text there is nothing to rank, the media matching the filters are listed newest first
     if query.is_empty() {
-        // println!("Query is empty");
-        return (StatusCode::BAD_REQUEST, "Query is required").into_response();
+        if filter.is_empty() {
+            return (StatusCode::BAD_REQUEST, "Query is required").into_response();
+        }
+        return match filtered_results(&server_config, user_id, filter, page, page_size).await {
+            Ok(items) => Json(json!(items)).into_response(),
+            Err(response) => response,
+        };
+    }
+
+    // Every page of a query is cut from the same cached ranking
+    let cache_key = format!("{user_id}\n{query}\n{filter:?}\n{:?}", params.threshold);
+    let results = match server_config.search_cache.get(&cache_key) {
+        Some(results) => results,
+        None => {
+            let results =
+                match ranked_results(&server_config, &user_id, &query, filter, params.threshold)
+                    .await
+                {
+                    Ok(results) => results,
+                    Err(response) => return response,
+                };
+            server_config.search_cache.insert(cache_key, results)
+        }
+    };
+
+    let page_results: Vec<&SearchResult> = results
+        .iter()
+        .skip(((page - 1) * page_size) as usize)
+        .take(page_size as usize)
+        .collect();
+    let media_ids = page_results
+        .iter()
+        .map(|result| result.id.clone())
+        .collect();
+    let mut previews: HashMap<String, MediaPreview> = match server_config
+        .database
+        .get_media_previews(user_id, media_ids)
+        .await
+    {
+        Ok(previews) => previews
+            .into_iter()
+            .map(|preview| (preview.id.clone(), preview))
+            .collect(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    // Media deleted since the search was cached are left out
+    let ranked = page_results
+        .into_iter()
+        .filter_map(|result| Some((previews.remove(&result.id)?, result.score)));
+    Json(json!(search_items(&server_config, ranked).await)).into_response()
+}
+
+// The search service only ranks by similarity, so its best candidates are filtered
+// in the database and paginated here
+async fn ranked_results(
+    server_config: &ServerConfig,
+    user_id: &str,
+    query: &str,
+    filter: MediaFilter,
+    threshold: Option<f32>,
+) -> Result<Vec<SearchResult>, Response> {
+    let mut candidates = search(server_config, user_id, query, 1, SEARCH_CANDIDATES).await?;
+
+    // Results without a score can't be compared and are kept
+    if let Some(threshold) = threshold {
+        candidates.retain(|item| item.score.is_none_or(|score| score >= threshold));
     }
 
+    if filter.is_empty() {
+        return Ok(candidates);
+    }
+
+    let candidate_ids = candidates.iter().map(|item| item.id.clone()).collect();
+    let allowed_ids: HashSet<String> = match server_config
+        .database
+        .filter_media(user_id.to_string(), candidate_ids, filter)
+        .await
+    {
+        Ok(media_ids) => media_ids.into_iter().collect(),
+        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR).into_response()),
+    };
+    candidates.retain(|item| allowed_ids.contains(&item.id));
+    Ok(candidates)
+}
+
+async fn filtered_results(
+    server_config: &ServerConfig,
+    user_id: String,
+    filter: MediaFilter,
+    page: u32,
+    page_size: u32,
+) -> Result<Vec<SearchItem>, Response> {
+    let media_previews = match server_config
+        .database
+        .search_media(user_id, filter, page as u64, page_size as u64)
+        .await
+    {
+        Ok(media_previews) => media_previews,
+        Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR).into_response()),
+    };
+
+    let unranked = media_previews.into_iter().map(|preview| (preview, None));
+    Ok(search_items(server_config, unranked).await)
+}
+
+async fn search_items(
+    server_config: &ServerConfig,
+    results: impl Iterator<Item = (MediaPreview, Option<f32>)>,
+) -> Vec<SearchItem> {
+    futures_util::future::join_all(results.map(|(preview, score)| async move {
+        let object_id = preview.preview_id.clone();
+        Some(SearchItem {
+            media: preview_item(&server_config.bucket, preview, object_id).await?,
+            score,
+        })
+    }))
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+async fn search(
+    server_config: &ServerConfig,
+    user_id: &str,
+    query: &str,
+    page: u32,
+    page_size: u32,
+) -> Result<Vec<SearchResult>, Response> {
     let request_message = json!({
         "user_id": user_id,
         "query": query,
@@ -34,39 +189,50 @@ pub async fn clip_search(
         "page_size": page_size,
     });
 
-    let nats_client: &Client = &server_config.nats_client;
-
     let subject = "clip-process-search";
-    let response = match nats_client
-        .request(subject, request_message.to_string().into())
-        .await
+    let response = match tokio::time::timeout(
+        server_config.search_timeout,
+        server_config
+            .bus
+            .request(subject, request_message.to_string().into()),
+    )
+    .await
     {
-        Ok(response) => response,
+        Ok(Ok(response)) => response,
         Err(_) => {
+            return Err((
+                StatusCode::GATEWAY_TIMEOUT,
+                "The search service did not answer in time",
+            )
+                .into_response())
+        }
+        Ok(Err(_)) => {
             // eprintln!("Failed to send request to NATS: {}", e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, "Error sending request").into_response();
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, "Error sending request").into_response()
+            );
         }
     };
 
-    let response_data = match String::from_utf8(response.payload.to_vec()) {
+    let response_data = match String::from_utf8(response.to_vec()) {
         Ok(data) => data,
         Err(_) => {
             // eprintln!("Failed to parse NATS response: {}", e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, "Error parsing response").into_response();
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, "Error parsing response").into_response(),
+            );
         }
     };
 
-    let preview_items: Result<Vec<PreviewItem>, _> = serde_json::from_str(&response_data);
-
-    match preview_items {
-        Ok(items) => Json(json!(items)).into_response(),
+    match serde_json::from_str(&response_data) {
+        Ok(items) => Ok(items),
         Err(_) => {
             // eprintln!("Failed to deserialize response data: {}", e);
-            (
+            Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Error deserializing response",
             )
-                .into_response()
+                .into_response())
         }
     }
 }
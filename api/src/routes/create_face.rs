@@ -1,9 +1,9 @@
+use crate::models::api_models::CreateFacePayload;
+use crate::ServerConfig;
 use axum::{
-    extract::{Json, State, Extension},
+    extract::{Extension, Json, State},
     http::StatusCode,
 };
-use crate::models::api_models::CreateFacePayload;
-use crate::ServerConfig;
 
 pub async fn create_face(
     State(server_config): State<ServerConfig>,
@@ -18,7 +18,7 @@ pub async fn create_face(
         .insert_face(user_id.clone(), payload.ids.clone(), payload.name.clone())
         .await
     {
-        Ok(_) => StatusCode::OK,                
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, 
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
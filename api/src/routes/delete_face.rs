@@ -0,0 +1,25 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use database::UpdateFaceError;
+use http::StatusCode;
+
+use crate::ServerConfig;
+
+pub async fn delete_face(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(face_id): Path<i32>,
+) -> Response {
+    match server_config.database.delete_face(user_id, face_id).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
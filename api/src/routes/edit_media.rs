@@ -0,0 +1,125 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::{EditMediaError, MediaEdit};
+use http::StatusCode;
+
+use crate::{
+    models::api_models::{BulkEditMediaPayload, EditMediaPayload},
+    ServerConfig,
+};
+
+const MILLIS_PER_HOUR: f64 = 3600.0 * 1000.0;
+// Time zones are at most 14 hours away from UTC
+const MAX_UTC_OFFSET: i32 = 14 * 60;
+// A century, far more than any wrong camera clock and far from overflowing the timestamps
+const MAX_SHIFT_HOURS: f64 = 24.0 * 366.0 * 100.0;
+// The ids are bound as parameters of a single query, Postgres takes at most 65535 of them
+const MAX_BULK_EDIT_MEDIA: usize = 10000;
+
+pub async fn edit_media(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(media_id): Path<String>,
+    Json(payload): Json<EditMediaPayload>,
+) -> Response {
+    apply_edit(&server_config, user_id, vec![media_id], payload).await
+}
+
+// Edits many media at once, e.g. to shift every photo of a camera with the wrong clock
+pub async fn edit_media_bulk(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<BulkEditMediaPayload>,
+) -> Response {
+    if payload.media_ids.is_empty() {
+        return (StatusCode::BAD_REQUEST, "No media to edit").into_response();
+    }
+    if payload.media_ids.len() > MAX_BULK_EDIT_MEDIA {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_BULK_EDIT_MEDIA} media are edited at once"),
+        )
+            .into_response();
+    }
+    apply_edit(&server_config, user_id, payload.media_ids, payload.edit).await
+}
+
+async fn apply_edit(
+    server_config: &ServerConfig,
+    user_id: String,
+    media_ids: Vec<String>,
+    payload: EditMediaPayload,
+) -> Response {
+    let edit = match parse_edit(payload) {
+        Ok(edit) => edit,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+
+    match server_config
+        .database
+        .edit_media(user_id, media_ids, edit)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(EditMediaError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Media does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(EditMediaError::InternalError) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+fn parse_edit(payload: EditMediaPayload) -> Result<MediaEdit, &'static str> {
+    if payload.latitude.is_some() != payload.longitude.is_some() {
+        return Err("Latitude and longitude have to be edited together");
+    }
+    if payload
+        .latitude
+        .is_some_and(|latitude| !(-90.0..=90.0).contains(&latitude))
+        || payload
+            .longitude
+            .is_some_and(|longitude| !(-180.0..=180.0).contains(&longitude))
+    {
+        return Err("Location is out of range");
+    }
+    if payload.created_at.is_some() && payload.shift_hours.is_some() {
+        return Err("The capture time is either set or shifted");
+    }
+    if payload
+        .utc_offset
+        .is_some_and(|utc_offset| utc_offset.abs() > MAX_UTC_OFFSET)
+    {
+        return Err("The utc offset is out of range");
+    }
+    if payload
+        .rating
+        .is_some_and(|rating| !(-1..=5).contains(&rating))
+    {
+        return Err("The rating goes from -1 to 5");
+    }
+    let shift = match payload.shift_hours {
+        Some(hours) if !hours.is_finite() => return Err("The shift has to be a number of hours"),
+        Some(hours) if hours.abs() > MAX_SHIFT_HOURS => return Err("The shift is out of range"),
+        Some(hours) => Some((hours * MILLIS_PER_HOUR).round() as i64),
+        None => None,
+    };
+
+    let edit = MediaEdit {
+        latitude: payload.latitude,
+        longitude: payload.longitude,
+        created_at: payload.created_at,
+        shift,
+        utc_offset: payload.utc_offset,
+        title: payload.title,
+        description: payload.description,
+        rating: payload.rating,
+    };
+    if !edit.edits_location() && !edit.edits_capture_time() && !edit.edits_annotations() {
+        return Err("Nothing to edit");
+    }
+    Ok(edit)
+}
@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use http::{header, StatusCode};
+use metadata::{export_media as write_metadata, ExportMetadata, MediaFormat};
+
+use crate::ServerConfig;
+
+// Downloads a copy of the media with its current metadata, manual edits included, written
+// into the file so other apps see the same dates, places and titles
+pub async fn export_media(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(media_id): Path<String>,
+) -> Response {
+    match server_config
+        .database
+        .user_has_media(user_id, &media_id)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Media does not exist or user does not have permissions to access it",
+            )
+                .into_response()
+        }
+        Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    let media = match server_config.database.get_media(media_id.clone()).await {
+        Ok(Some(media)) => media,
+        Ok(None) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Media does not exist or user does not have permissions to access it",
+            )
+                .into_response()
+        }
+        Err(..) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error fetching media metadata",
+            )
+                .into_response()
+        }
+    };
+
+    let original = match server_config.bucket.get_object(&media_id).await {
+        Ok(response) if response.status_code() == 200 => response,
+        _ => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error fetching media from object storage",
+            )
+                .into_response()
+        }
+    };
+
+    // The whole file is buffered rather than streamed: the EXIF is read from the original, the
+    // metadata of a HEIF can sit at its end and its item locations are patched after the fact
+    let metadata = ExportMetadata::from(&media);
+    let exported =
+        match tokio::task::spawn_blocking(move || write_metadata(original.as_slice(), &metadata))
+            .await
+        {
+            Ok(Ok(exported)) => exported,
+            _ => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error writing metadata into media",
+                )
+                    .into_response()
+            }
+        };
+
+    let file_name = media.file_name.replace(['"', '\\'], "_");
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                MediaFormat::of(&exported).content_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        exported,
+    )
+        .into_response()
+}
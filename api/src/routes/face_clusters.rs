@@ -0,0 +1,50 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use database::UpdateFaceError;
+use http::StatusCode;
+
+use crate::ServerConfig;
+
+pub async fn add_face_cluster(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path((face_id, cluster_id)): Path<(i32, i32)>,
+) -> Response {
+    match server_config
+        .database
+        .add_cluster_to_face(user_id, face_id, cluster_id)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face or cluster does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// The cluster is kept as an unnamed cluster
+pub async fn remove_face_cluster(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path((face_id, cluster_id)): Path<(i32, i32)>,
+) -> Response {
+    match server_config
+        .database
+        .remove_cluster_from_face(user_id, face_id, cluster_id)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face or cluster does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -6,7 +6,11 @@ use axum::{
 use database::GetPreviewError;
 use http::StatusCode;
 
-use crate::{models::api_models::Pagination, models::api_models::PreviewItem, ServerConfig};
+use crate::{
+    models::api_models::{FacePreviewItem, Pagination},
+    utils::renditions::preview_item,
+    ServerConfig,
+};
 
 pub async fn face_previews(
     State(server_config): State<ServerConfig>,
@@ -22,38 +26,32 @@ pub async fn face_previews(
         .get_face_previews(user_id.clone(), face_id, page, page_size)
         .await
     {
-        Ok(preview_ids) => {
-            let previews: Vec<PreviewItem> = futures_util::future::join_all(
-                preview_ids.into_iter().map(|(media_id, preview_id)| {
-                    let bucket = server_config.bucket.clone();
+        Ok(detection_previews) => {
+            let previews: Vec<FacePreviewItem> =
+                futures_util::future::join_all(detection_previews.into_iter().map(|detection| {
+                    let bucket = &server_config.bucket;
                     async move {
-                        if let Some(p_id) = preview_id {
-                            match bucket.presign_get(p_id, 86400, None).await {
-                                Ok(url) => Some(PreviewItem {
-                                    id: media_id,
-                                    preview_url: url,
-                                }),
-                                Err(_) => None,
-                            }
-                        } else {
-                            Some(PreviewItem {
-                                id: media_id,
-                                preview_url: "".to_string(),
-                            })
-                        }
+                        let crop_url = match detection.crop_id {
+                            Some(crop_id) => bucket.presign_get(crop_id, 86400, None).await.ok(),
+                            None => None,
+                        };
+                        let object_id = detection.media.preview_id.clone();
+                        Some(FacePreviewItem {
+                            media: preview_item(bucket, detection.media, object_id).await?,
+                            crop_url,
+                        })
                     }
-                }),
-            )
-            .await
-            .into_iter()
-            .flatten()
-            .collect();
+                }))
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
             (StatusCode::OK, Json(previews)).into_response()
         }
         Err(GetPreviewError::InternalError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
         Err(GetPreviewError::NotFound) => (
             StatusCode::FORBIDDEN,
-            "Cluster does not exist or user does not have permissions to access it",
+            "Face does not exist or user does not have permissions to access it",
         )
             .into_response(),
     }
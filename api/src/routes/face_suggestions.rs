@@ -0,0 +1,85 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::UpdateFaceError;
+use futures_util::future::join_all;
+use http::StatusCode;
+
+use crate::{models::api_models::FaceSuggestionResponse, ServerConfig};
+
+// Detections that look like a named face, closest first
+pub async fn face_suggestions(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+) -> Response {
+    match server_config.database.get_face_suggestions(user_id).await {
+        Ok(suggestions) => {
+            let server_config = &server_config;
+            let suggestion_futures: Vec<_> = suggestions
+                .into_iter()
+                .map(|suggestion| async move {
+                    let photo_url = server_config
+                        .bucket
+                        .presign_get(&suggestion.media_id, 86400, None)
+                        .await
+                        .unwrap();
+                    FaceSuggestionResponse {
+                        suggestion_id: suggestion.suggestion_id,
+                        media_face_id: suggestion.media_face_id,
+                        face_id: suggestion.face_id,
+                        name: suggestion.name,
+                        photo_url: photo_url.to_string(),
+                        bbox: suggestion.bbox,
+                        distance: suggestion.distance,
+                    }
+                })
+                .collect();
+            (StatusCode::OK, Json(join_all(suggestion_futures).await)).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, Json(err.to_string())).into_response(),
+    }
+}
+
+// Moves the detection into the suggested face
+pub async fn accept_face_suggestion(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(suggestion_id): Path<i32>,
+) -> Response {
+    match server_config
+        .database
+        .accept_face_suggestion(user_id, suggestion_id)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Suggestion does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// The detection is never suggested for, nor attached to, the face again
+pub async fn reject_face_suggestion(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(suggestion_id): Path<i32>,
+) -> Response {
+    match server_config
+        .database
+        .reject_face_suggestion(user_id, suggestion_id)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Suggestion does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    extract::State,
+    extract::{Query, State},
     response::{IntoResponse, Response},
     Extension, Json,
 };
@@ -7,31 +7,43 @@ use futures_util::future::join_all;
 use http::StatusCode;
 
 use crate::{
-    models::api_models::{ClusterResponse, FaceResponse, GetFacesResponse},
+    models::api_models::{ClusterResponse, FaceResponse, FacesQuery, GetFacesResponse},
     ServerConfig,
 };
 
+// Either the visible or, with `?hidden=true`, the hidden faces and clusters
 pub async fn faces(
     State(server_config): State<ServerConfig>,
     Extension(user_id): Extension<String>,
+    Query(params): Query<FacesQuery>,
 ) -> Response {
-    match server_config.database.get_faces(user_id).await {
+    match server_config
+        .database
+        .get_faces(user_id, params.hidden)
+        .await
+    {
         Ok((faces, clusters)) => {
             let sc1 = &server_config.clone();
             let sc2 = &server_config.clone();
             let face_futures: Vec<_> = faces
                 .into_iter()
                 .map(|face| async move {
-                    let photo_url = sc1
-                        .bucket
-                        .presign_get(&face.photo_id, 86400, None)
-                        .await
-                        .unwrap();
+                    let photo_url = match face.photo_id {
+                        Some(photo_id) => {
+                            sc1.bucket.presign_get(photo_id, 86400, None).await.unwrap()
+                        }
+                        None => "".to_string(),
+                    };
+                    let crop_url = match face.crop_id {
+                        Some(crop_id) => sc1.bucket.presign_get(crop_id, 86400, None).await.ok(),
+                        None => None,
+                    };
                     FaceResponse {
                         face_id: face.face_id,
                         name: face.name,
                         photo_url: photo_url.to_string(),
                         bbox: face.bbox,
+                        crop_url,
                     }
                 })
                 .collect();
@@ -44,10 +56,15 @@ pub async fn faces(
                         .presign_get(&cluster.photo_id, 86400, None)
                         .await
                         .unwrap();
+                    let crop_url = match cluster.crop_id {
+                        Some(crop_id) => sc2.bucket.presign_get(crop_id, 86400, None).await.ok(),
+                        None => None,
+                    };
                     ClusterResponse {
                         cluster_id: cluster.cluster_id,
                         photo_url: photo_url.to_string(),
                         bbox: cluster.bbox,
+                        crop_url,
                     }
                 })
                 .collect();
@@ -0,0 +1,35 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::UpdateFaceError;
+use http::StatusCode;
+
+use crate::{models::api_models::FeaturedPhotoPayload, ServerConfig};
+
+pub async fn featured_photo(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(face_id): Path<i32>,
+    Json(payload): Json<FeaturedPhotoPayload>,
+) -> Response {
+    match server_config
+        .database
+        .set_featured_photo(user_id, face_id, payload.media_id)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::InvalidRequest) => (
+            StatusCode::BAD_REQUEST,
+            "The face does not appear in this media",
+        )
+            .into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(UpdateFaceError::InternalError) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
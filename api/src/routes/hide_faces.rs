@@ -0,0 +1,53 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::UpdateFaceError;
+use http::StatusCode;
+
+use crate::{models::api_models::HiddenPayload, ServerConfig};
+
+// Hidden faces are only listed by `GET /faces?hidden=true`
+pub async fn hide_face(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(face_id): Path<i32>,
+    Json(payload): Json<HiddenPayload>,
+) -> Response {
+    match server_config
+        .database
+        .set_face_hidden(user_id, face_id, payload.hidden)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// Detections in a hidden cluster are left there by the clustering, so they don't come back in a new one
+pub async fn hide_cluster(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(cluster_id): Path<i32>,
+    Json(payload): Json<HiddenPayload>,
+) -> Response {
+    match server_config
+        .database
+        .set_cluster_hidden(user_id, cluster_id, payload.hidden)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Cluster does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -0,0 +1,169 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::Utc;
+use database::{needs_display_copy, FULL_RENDITION};
+use http::{header, StatusCode};
+
+use crate::{
+    utils::resize::{Fit, Resize, ResizeQuery},
+    ServerConfig,
+};
+
+// Private since the url needs a token, revalidated with the etag once it expires
+const CACHE_CONTROL: &str = "private, max-age=86400";
+
+// Serves the media resized and cropped as asked, e.g. for a new client layout, resized images
+// are kept in the bucket so every size is only made once
+pub async fn image(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(media_id): Path<String>,
+    Query(query): Query<ResizeQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let resize = match Resize::parse(&query) {
+        Ok(resize) => resize,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+
+    match server_config
+        .database
+        .user_has_media(user_id.clone(), &media_id)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Media does not exist or user does not have permissions to access it",
+            )
+                .into_response()
+        }
+        Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    let media = match server_config.database.get_media(media_id.clone()).await {
+        Ok(Some(media)) => media,
+        Ok(None) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Media does not exist or user does not have permissions to access it",
+            )
+                .into_response()
+        }
+        Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let faces = if resize.fit == Fit::Face {
+        match server_config
+            .database
+            .get_face_boxes(media_id.clone())
+            .await
+        {
+            Ok(faces) => faces,
+            Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    } else {
+        Vec::new()
+    };
+
+    // Browsers can't show HEIC, nor can the image crate decode it, the display copy is used instead
+    let source_id = if media.media_type.as_deref().is_some_and(needs_display_copy) {
+        let renditions = match server_config
+            .database
+            .get_renditions(vec![media_id.clone()])
+            .await
+        {
+            Ok(renditions) => renditions,
+            Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        match renditions
+            .into_iter()
+            .find(|rendition| rendition.name == FULL_RENDITION)
+        {
+            Some(rendition) => rendition.object_id,
+            None => {
+                return (
+                    StatusCode::CONFLICT,
+                    "The display copy of the media has not been made yet",
+                )
+                    .into_response()
+            }
+        }
+    } else {
+        media_id.clone()
+    };
+
+    let object_id = resize.object_id(&media_id, &faces);
+    let etag = resize.etag(&media.hash, &object_id);
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+    ];
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+
+    let content_type = resize.format.content_type();
+    if let Ok(cached) = server_config.bucket.get_object(&object_id).await {
+        if cached.status_code() == 200 {
+            return (
+                StatusCode::OK,
+                cache_headers,
+                [(header::CONTENT_TYPE, content_type)],
+                cached.to_vec(),
+            )
+                .into_response();
+        }
+    }
+
+    let source = match server_config.bucket.get_object(&source_id).await {
+        Ok(source) if source.status_code() == 200 => source,
+        _ => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error fetching media from object storage",
+            )
+                .into_response()
+        }
+    };
+    let resized =
+        match tokio::task::spawn_blocking(move || resize.apply(source.as_slice(), &faces)).await {
+            Ok(Ok(resized)) => resized,
+            Ok(Err(err)) => {
+                let _ = server_config
+                    .database
+                    .add_log(
+                        user_id,
+                        database::LogLevel::Error,
+                        Utc::now().timestamp_millis(),
+                        format!("Image: {err}"),
+                    )
+                    .await;
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error resizing media").into_response();
+            }
+            Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+
+    // A failed write only means the next request resizes again
+    let _ = server_config
+        .bucket
+        .put_object_with_content_type(&object_id, &resized, content_type)
+        .await;
+
+    (
+        StatusCode::OK,
+        cache_headers,
+        [(header::CONTENT_TYPE, content_type)],
+        resized,
+    )
+        .into_response()
+}
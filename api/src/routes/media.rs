@@ -3,6 +3,7 @@ use axum::{
     response::{IntoResponse, Response},
     Extension, Json,
 };
+use database::{needs_display_copy, FULL_RENDITION};
 use http::StatusCode;
 
 use crate::{models::api_models::MediaMetadataResponse, ServerConfig};
@@ -47,7 +48,7 @@ pub async fn media(
         };
         let url = match server_config
             .bucket
-            .presign_get(media_id, 86400, None)
+            .presign_get(media_id.clone(), 86400, None)
             .await
         {
             Ok(url) => url,
@@ -59,10 +60,47 @@ pub async fn media(
                     .into_response();
             }
         };
+        let full_rendition = match server_config
+            .database
+            .get_renditions(vec![media_id.clone()])
+            .await
+        {
+            Ok(renditions) => renditions
+                .into_iter()
+                .find(|rendition| rendition.name == FULL_RENDITION),
+            Err(..) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error fetching media renditions",
+                )
+                    .into_response();
+            }
+        };
+        let display_url = match full_rendition {
+            Some(rendition) => match server_config
+                .bucket
+                .presign_get(rendition.object_id, 86400, None)
+                .await
+            {
+                Ok(url) => Some(url),
+                Err(..) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Error creating media presigned url",
+                    )
+                        .into_response();
+                }
+            },
+            None if media.media_type.as_deref().is_some_and(needs_display_copy) => None,
+            None => Some(url.clone()),
+        };
         let media_metadata = MediaMetadataResponse {
             id: media.id,
             created_at: media.created_at,
+            uploaded_at: media.uploaded_at,
+            utc_offset: media.utc_offset,
             media_url: url,
+            display_url,
             file_size: media.file_size,
             file_name: media.file_name,
             longitude: media.longitude,
@@ -75,6 +113,25 @@ pub async fn media(
             exposure_time: media.exposure_time,
             photographic_sensitivity: media.photographic_sensitivity,
             orientation: media.orientation,
+            lens_make: media.lens_make,
+            lens_model: media.lens_model,
+            focal_length: media.focal_length,
+            focal_length_35mm: media.focal_length_35mm,
+            altitude: media.altitude,
+            image_direction: media.image_direction,
+            flash: media.flash,
+            white_balance: media.white_balance,
+            metering_mode: media.metering_mode,
+            exposure_program: media.exposure_program,
+            exposure_bias: media.exposure_bias,
+            software: media.software,
+            artist: media.artist,
+            exif: media.exif,
+            rating: media.rating,
+            title: media.title,
+            description: media.description,
+            keywords: media.keywords,
+            people: media.people,
         };
         (StatusCode::OK, Json(media_metadata)).into_response()
     } else {
@@ -0,0 +1,36 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::UpdateFaceError;
+use http::StatusCode;
+
+use crate::{models::api_models::MergeFacesPayload, ServerConfig};
+
+// Moves everything of the face in the payload into the face in the path
+pub async fn merge_faces(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(face_id): Path<i32>,
+    Json(payload): Json<MergeFacesPayload>,
+) -> Response {
+    match server_config
+        .database
+        .merge_faces(user_id, face_id, payload.face_id)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::InvalidRequest) => (
+            StatusCode::BAD_REQUEST,
+            "A face can't be merged into itself",
+        )
+            .into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(UpdateFaceError::InternalError) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -1,15 +1,30 @@
 pub mod clip_search;
 pub mod cluster_previews;
+pub mod create_face;
+pub mod delete_face;
+pub mod edit_media;
+pub mod export_media;
+pub mod face_clusters;
 pub mod face_previews;
+pub mod face_suggestions;
 pub mod faces;
+pub mod featured_photo;
+pub mod hide_faces;
+pub mod image;
 pub mod login;
 pub mod logs;
 pub mod media;
+pub mod merge_faces;
+pub mod move_media_face;
+pub mod people_previews;
 pub mod preview;
 pub mod previews;
 pub mod refresh;
 pub mod register;
+pub mod rename_face;
+pub mod similar_media;
 pub mod sync_full;
 pub mod sync_partial;
+pub mod tag_face;
 pub mod upload_image;
-pub mod create_face;
\ No newline at end of file
+pub mod upload_sidecar;
@@ -0,0 +1,43 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::{MediaFaceTarget, UpdateFaceError};
+use http::StatusCode;
+
+use crate::{models::api_models::MoveMediaFacePayload, ServerConfig};
+
+// Moves a single face detection to another cluster or face
+pub async fn move_media_face(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(media_face_id): Path<i32>,
+    Json(payload): Json<MoveMediaFacePayload>,
+) -> Response {
+    let target = match (payload.cluster_id, payload.face_id) {
+        (Some(cluster_id), None) => MediaFaceTarget::Cluster(cluster_id),
+        (None, Some(face_id)) => MediaFaceTarget::Face(face_id),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Either a cluster_id or a face_id is required",
+            )
+                .into_response()
+        }
+    };
+
+    match server_config
+        .database
+        .move_media_face(user_id, media_face_id, target)
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face detection or target does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
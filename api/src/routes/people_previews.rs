@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::{GetPreviewError, PeopleQuery};
+use http::StatusCode;
+
+use crate::{
+    models::api_models::{PeoplePreviewsQuery, PreviewItem},
+    utils::renditions::preview_item,
+    ServerConfig,
+};
+
+// Photos by the people in them, e.g. `?all=1,2&none=3` for photos with both 1 and 2 but without 3
+pub async fn people_previews(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Query(params): Query<PeoplePreviewsQuery>,
+) -> Response {
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(10).clamp(1, 30);
+
+    let people = match (
+        parse_face_ids(params.all.as_deref()),
+        parse_face_ids(params.any.as_deref()),
+        parse_face_ids(params.none.as_deref()),
+    ) {
+        (Some(all), Some(any), Some(none)) => PeopleQuery { all, any, none },
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Face ids have to be a comma separated list of numbers",
+            )
+                .into_response()
+        }
+    };
+    if people.all.is_empty() && people.any.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "At least one face id is required in all or any",
+        )
+            .into_response();
+    }
+
+    match server_config
+        .database
+        .get_people_previews(user_id, people, page, page_size)
+        .await
+    {
+        Ok(media_previews) => {
+            let previews: Vec<PreviewItem> =
+                futures_util::future::join_all(media_previews.into_iter().map(|preview| {
+                    let object_id = preview.preview_id.clone();
+                    preview_item(&server_config.bucket, preview, object_id)
+                }))
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+            (StatusCode::OK, Json(previews)).into_response()
+        }
+        Err(GetPreviewError::InternalError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(GetPreviewError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+    }
+}
+
+fn parse_face_ids(ids: Option<&str>) -> Option<Vec<i32>> {
+    match ids.map(str::trim) {
+        None | Some("") => Some(vec![]),
+        Some(ids) => ids.split(',').map(|id| id.trim().parse().ok()).collect(),
+    }
+}
@@ -1,17 +1,18 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::{IntoResponse, Response},
     Extension,
 };
 use database::GetPreviewError;
 use http::StatusCode;
 
-use crate::ServerConfig;
+use crate::{models::api_models::RenditionQuery, utils::renditions::pick_rendition, ServerConfig};
 
 pub async fn preview(
     State(server_config): State<ServerConfig>,
     Extension(user_id): Extension<String>,
     Path(media_id): Path<String>,
+    Query(query): Query<RenditionQuery>,
 ) -> Response {
     match server_config
         .database
@@ -19,9 +20,21 @@ pub async fn preview(
         .await
     {
         Ok(preview_id) => {
+            let object_id = match query.size {
+                Some(size) => match server_config
+                    .database
+                    .get_renditions(vec![media_id.clone()])
+                    .await
+                {
+                    Ok(renditions) => pick_rendition(&renditions, &size, query.format.as_deref())
+                        .map_or(preview_id, |rendition| rendition.object_id.clone()),
+                    Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                },
+                None => preview_id,
+            };
             let url = server_config
                 .bucket
-                .presign_get(preview_id, 86400, None)
+                .presign_get(object_id, 86400, None)
                 .await
                 .unwrap();
             (StatusCode::OK, url).into_response()
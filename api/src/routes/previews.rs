@@ -1,55 +1,72 @@
+use std::collections::HashMap;
+
 use axum::{
     extract::{Query, State},
     response::{IntoResponse, Response},
     Extension, Json,
 };
-use database::GetPreviewError;
+use database::{schema::rendition, GetPreviewError};
 use http::StatusCode;
 
 use crate::{
-    models::api_models::{Pagination, PreviewItem},
+    models::api_models::{PreviewItem, PreviewsQuery},
+    utils::renditions::{pick_rendition, preview_item},
     ServerConfig,
 };
 
 pub async fn previews(
     State(server_config): State<ServerConfig>,
     Extension(user_id): Extension<String>,
-    Query(pagination): Query<Pagination>,
+    Query(query): Query<PreviewsQuery>,
 ) -> Response {
-    let page = pagination.page.unwrap_or(1).max(1);
-    let page_size = pagination.page_size.unwrap_or(10).clamp(1, 30);
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(10).clamp(1, 30);
 
     match server_config
         .database
         .get_previews(user_id, page, page_size)
         .await
     {
-        Ok(preview_ids) => {
-            let previews: Vec<PreviewItem> = futures_util::future::join_all(
-                preview_ids.into_iter().map(|(media_id, preview_id)| {
-                    let bucket = server_config.bucket.clone();
-                    async move {
-                        if let Some(p_id) = preview_id {
-                            match bucket.presign_get(p_id, 86400, None).await {
-                                Ok(url) => Some(PreviewItem {
-                                    id: media_id,
-                                    preview_url: url,
-                                }),
-                                Err(_) => None,
-                            }
-                        } else {
-                            Some(PreviewItem {
-                                id: media_id,
-                                preview_url: "".to_string(),
-                            })
+        Ok(media_previews) => {
+            let mut renditions: HashMap<String, Vec<rendition::Model>> = HashMap::new();
+            if query.size.is_some() {
+                let media_ids = media_previews
+                    .iter()
+                    .map(|preview| preview.id.clone())
+                    .collect();
+                match server_config.database.get_renditions(media_ids).await {
+                    Ok(found) => {
+                        for rendition in found {
+                            renditions
+                                .entry(rendition.media_id.clone())
+                                .or_default()
+                                .push(rendition);
                         }
                     }
-                }),
-            )
-            .await
-            .into_iter()
-            .flatten()
-            .collect();
+                    Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                }
+            }
+
+            let previews: Vec<PreviewItem> =
+                futures_util::future::join_all(media_previews.into_iter().map(|preview| {
+                    let object_id = query
+                        .size
+                        .as_deref()
+                        .and_then(|size| {
+                            pick_rendition(
+                                renditions.get(&preview.id)?,
+                                size,
+                                query.format.as_deref(),
+                            )
+                        })
+                        .map(|rendition| rendition.object_id.clone())
+                        .or(preview.preview_id.clone());
+                    preview_item(&server_config.bucket, preview, object_id)
+                }))
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
             (StatusCode::OK, Json(previews)).into_response()
         }
         Err(GetPreviewError::InternalError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
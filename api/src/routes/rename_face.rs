@@ -0,0 +1,35 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::UpdateFaceError;
+use http::StatusCode;
+
+use crate::{models::api_models::RenameFacePayload, ServerConfig};
+
+pub async fn rename_face(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(face_id): Path<i32>,
+    Json(payload): Json<RenameFacePayload>,
+) -> Response {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Name is required").into_response();
+    }
+
+    match server_config
+        .database
+        .rename_face(user_id, face_id, name.to_string())
+        .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(UpdateFaceError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Face does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
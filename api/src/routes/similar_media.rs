@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use database::GetPreviewError;
+use http::StatusCode;
+
+use crate::{
+    models::api_models::{Pagination, PreviewItem},
+    utils::renditions::preview_item,
+    ServerConfig,
+};
+
+pub async fn similar_media(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(media_id): Path<String>,
+    Query(pagination): Query<Pagination>,
+) -> Response {
+    let page = pagination.page.unwrap_or(1).max(1);
+    let page_size = pagination.page_size.unwrap_or(10).clamp(1, 30);
+
+    match server_config
+        .database
+        .get_similar_media(user_id, &media_id, page, page_size)
+        .await
+    {
+        Ok(media_previews) => {
+            let previews: Vec<PreviewItem> =
+                futures_util::future::join_all(media_previews.into_iter().map(|preview| {
+                    let object_id = preview.preview_id.clone();
+                    preview_item(&server_config.bucket, preview, object_id)
+                }))
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+            (StatusCode::OK, Json(previews)).into_response()
+        }
+        Err(GetPreviewError::InternalError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(GetPreviewError::NotFound) => (
+            StatusCode::FORBIDDEN,
+            "Media does not exist or user does not have permissions to access it",
+        )
+            .into_response(),
+    }
+}
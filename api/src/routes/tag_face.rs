@@ -0,0 +1,85 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::Utc;
+use database::{TagTarget, UpdateFaceError};
+use http::StatusCode;
+
+use crate::{
+    models::api_models::{TagFacePayload, TagFaceResponse},
+    ServerConfig,
+};
+
+// Tags a face the detector missed, drawn by the user on the media, as an existing or a new person
+pub async fn tag_face(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(media_id): Path<String>,
+    Json(payload): Json<TagFacePayload>,
+) -> Response {
+    if payload.bbox.len() != 4 || payload.bbox.iter().any(|value| *value < 0) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "A bounding box is four non negative numbers",
+        )
+            .into_response();
+    }
+    let target = match (payload.face_id, payload.name.as_deref().map(str::trim)) {
+        (Some(face_id), None) => TagTarget::Face(face_id),
+        (None, Some(name)) if !name.is_empty() => TagTarget::NewFace(name.to_string()),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Either a face_id or the name of a new face is required",
+            )
+                .into_response()
+        }
+    };
+
+    let (media_face_id, face_id) = match server_config
+        .database
+        .tag_face(user_id.clone(), media_id.clone(), payload.bbox, target)
+        .await
+    {
+        Ok(ids) => ids,
+        Err(UpdateFaceError::NotFound) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Media or face does not exist or user does not have permissions to access it",
+            )
+                .into_response()
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    // The detection never gets an embedding, the clustering leaves it in the face it was tagged as.
+    // The crop is made by the next catch-up of the preview worker when it can't be reached.
+    if server_config
+        .bus
+        .publish("face-crops", Bytes::from(media_id))
+        .await
+        .is_err()
+    {
+        let _ = server_config
+            .database
+            .add_log(
+                user_id,
+                database::LogLevel::Error,
+                Utc::now().timestamp_millis(),
+                format!("Face Tagging: Error publishing face {media_face_id} on face-crops"),
+            )
+            .await;
+    }
+
+    (
+        StatusCode::OK,
+        Json(TagFaceResponse {
+            media_face_id,
+            face_id,
+        }),
+    )
+        .into_response()
+}
@@ -60,28 +60,28 @@ pub async fn upload_image(
                 .into_response();
         };
 
-        let timestamp = match headers
-            .get("Timestamp")
-            .and_then(|ts| ts.to_str().ok())
-            .and_then(|ts| ts.parse::<i64>().ok())
-        {
-            Some(ts) => ts,
-            None => {
-                let _ = server_config
-                    .database
-                    .add_log(
-                        user_id,
-                        database::LogLevel::Error,
-                        Utc::now().timestamp_millis(),
-                        "Media Upload: Timestamp header missing or invalid format".to_string(),
+        // Only a fallback, the metadata worker replaces it with the capture time of the file
+        let timestamp = match headers.get("Timestamp") {
+            None => Utc::now().timestamp_millis(),
+            Some(ts) => match ts.to_str().ok().and_then(|ts| ts.parse::<i64>().ok()) {
+                Some(ts) => ts,
+                None => {
+                    let _ = server_config
+                        .database
+                        .add_log(
+                            user_id,
+                            database::LogLevel::Error,
+                            Utc::now().timestamp_millis(),
+                            "Media Upload: Timestamp header has an invalid format".to_string(),
+                        )
+                        .await;
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        "Timestamp header has an invalid format",
                     )
-                    .await;
-                return (
-                    StatusCode::BAD_REQUEST,
-                    "Timestamp header missing or invalid format",
-                )
-                    .into_response();
-            }
+                        .into_response();
+                }
+            },
         };
 
         match server_config
@@ -281,67 +281,64 @@ pub async fn upload_image(
 
                     // Step 4: publish preview generation request
                     let file_uuid_bytes = Bytes::from(String::from(file_uuid));
-                    let _ = match server_config
-                        .nats_jetstream
+                    if server_config
+                        .bus
                         .publish("previews", file_uuid_bytes.clone())
                         .await
+                        .is_err()
                     {
-                        Ok(publish_ack) => publish_ack,
-                        Err(..) => {
-                            let _ = server_config
-                                .database
-                                .add_log(
-                                    user_id,
-                                    database::LogLevel::Error,
-                                    Utc::now().timestamp_millis(),
-                                    "Media Upload: Error publishing picture to NATS".to_string(),
-                                )
-                                .await;
-                            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-                        }
-                    };
+                        let _ = server_config
+                            .database
+                            .add_log(
+                                user_id,
+                                database::LogLevel::Error,
+                                Utc::now().timestamp_millis(),
+                                "Media Upload: Error publishing picture to the message bus"
+                                    .to_string(),
+                            )
+                            .await;
+                        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                    }
 
                     // Step 5: publish ml embeddings generation request
-                    let _ = match server_config
-                        .nats_jetstream
+                    if server_config
+                        .bus
                         .publish("image-process", file_uuid_bytes.clone())
                         .await
+                        .is_err()
                     {
-                        Ok(publish_ack) => publish_ack,
-                        Err(..) => {
-                            let _ = server_config
-                                .database
-                                .add_log(
-                                    user_id,
-                                    database::LogLevel::Error,
-                                    Utc::now().timestamp_millis(),
-                                    "Media Upload: Error publishing picture to NATS".to_string(),
-                                )
-                                .await;
-                            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-                        }
-                    };
+                        let _ = server_config
+                            .database
+                            .add_log(
+                                user_id,
+                                database::LogLevel::Error,
+                                Utc::now().timestamp_millis(),
+                                "Media Upload: Error publishing picture to the message bus"
+                                    .to_string(),
+                            )
+                            .await;
+                        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                    }
 
                     // Step 6: publish metadata request
-                    let _ = match server_config
-                        .nats_jetstream
+                    if server_config
+                        .bus
                         .publish("metadata", file_uuid_bytes)
                         .await
+                        .is_err()
                     {
-                        Ok(publish_ack) => publish_ack,
-                        Err(..) => {
-                            let _ = server_config
-                                .database
-                                .add_log(
-                                    user_id,
-                                    database::LogLevel::Error,
-                                    Utc::now().timestamp_millis(),
-                                    "Media Upload: Error publishing picture to NATS".to_string(),
-                                )
-                                .await;
-                            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-                        }
-                    };
+                        let _ = server_config
+                            .database
+                            .add_log(
+                                user_id,
+                                database::LogLevel::Error,
+                                Utc::now().timestamp_millis(),
+                                "Media Upload: Error publishing picture to the message bus"
+                                    .to_string(),
+                            )
+                            .await;
+                        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                    }
 
                     let _ = server_config
                         .database
@@ -0,0 +1,85 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::Utc;
+use http::StatusCode;
+
+use crate::ServerConfig;
+
+// Read by the metadata worker next to the media itself
+const SIDECAR_PREFIX: &str = "xmp/";
+
+// Stores the .xmp sidecar a photo manager exported next to a media and has the
+// metadata read again, the sidecar wins over the metadata embedded in the file
+pub async fn upload_sidecar(
+    State(server_config): State<ServerConfig>,
+    Extension(user_id): Extension<String>,
+    Path(media_id): Path<String>,
+    body: Bytes,
+) -> Response {
+    match server_config
+        .database
+        .user_has_media(user_id.clone(), &media_id)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Media does not exist or user does not have permissions to access it",
+            )
+                .into_response()
+        }
+        Err(..) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    let is_xmp = std::str::from_utf8(&body)
+        .is_ok_and(|xml| xml.contains("<x:xmpmeta") || xml.contains("<rdf:RDF"));
+    if !is_xmp {
+        return (StatusCode::BAD_REQUEST, "The sidecar is not an XMP packet").into_response();
+    }
+
+    if server_config
+        .bucket
+        .put_object_with_content_type(
+            format!("{SIDECAR_PREFIX}{media_id}"),
+            &body,
+            "application/rdf+xml",
+        )
+        .await
+        .is_err()
+    {
+        let _ = server_config
+            .database
+            .add_log(
+                user_id,
+                database::LogLevel::Error,
+                Utc::now().timestamp_millis(),
+                "Sidecar Upload: Error uploading sidecar to object storage".to_string(),
+            )
+            .await;
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if server_config
+        .bus
+        .publish("metadata", Bytes::from(media_id))
+        .await
+        .is_err()
+    {
+        let _ = server_config
+            .database
+            .add_log(
+                user_id,
+                database::LogLevel::Error,
+                Utc::now().timestamp_millis(),
+                "Sidecar Upload: Error publishing media to the message bus".to_string(),
+            )
+            .await;
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,200 @@
+use chrono::NaiveDate;
+use database::{Location, MediaFilter};
+use serde::Serialize;
+
+const DEFAULT_NEAR_RADIUS_KM: f64 = 10.0;
+
+// A search query split into the structured terms, applied as filters in the database,
+// and the free text that is ranked by clip. For example:
+// camera:"iPhone 15" after:2023-06 before:2023-09 person:Alice near:38.72,-9.14 iso>800 beach sunset
+pub struct ParsedQuery {
+    pub text: String,
+    pub filter: MediaFilter,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QueryError {
+    pub error: String,
+    pub term: String,
+}
+
+impl QueryError {
+    fn new(term: &str, error: &str) -> Self {
+        QueryError {
+            error: error.to_string(),
+            term: term.to_string(),
+        }
+    }
+}
+
+struct Token {
+    value: String,
+    // Position of the first operator, only set when it is not inside quotes
+    operator_at: Option<usize>,
+}
+
+// Terms of the query override the same fields of the given filter
+pub fn parse(query: &str, mut filter: MediaFilter) -> Result<ParsedQuery, QueryError> {
+    let mut text = Vec::new();
+
+    for token in tokenize(query)? {
+        let Some(operator_at) = token.operator_at else {
+            text.push(token.value);
+            continue;
+        };
+        let field = token.value[..operator_at].to_lowercase();
+        // Things like 10:30 or <3 are not fields
+        if field.is_empty() || !field.chars().all(|c| c.is_ascii_alphabetic()) {
+            text.push(token.value);
+            continue;
+        }
+
+        let rest = &token.value[operator_at..];
+        let (operator, value) = [">=", "<=", ":", ">", "<"]
+            .into_iter()
+            .find_map(|operator| rest.strip_prefix(operator).map(|value| (operator, value)))
+            .unwrap();
+        let term = token.value.as_str();
+        if value.is_empty() {
+            return Err(QueryError::new(term, "Missing value"));
+        }
+        if operator != ":" && field != "iso" {
+            return Err(QueryError::new(
+                term,
+                "Comparisons are only supported for iso",
+            ));
+        }
+
+        match field.as_str() {
+            "camera" => filter.camera = Some(value.to_string()),
+            "make" => filter.make = Some(value.to_string()),
+            "model" => filter.model = Some(value.to_string()),
+            "person" => filter.person = Some(value.to_string()),
+            "after" => filter.after = Some(parse_date(term, value)?),
+            "before" => filter.before = Some(parse_date(term, value)?),
+            "near" => filter.near = Some(parse_location(term, value)?),
+            "iso" => {
+                let iso: i32 = value
+                    .parse()
+                    .map_err(|_| QueryError::new(term, "Iso must be a number"))?;
+                match operator {
+                    ":" => {
+                        filter.min_iso = Some(iso);
+                        filter.max_iso = Some(iso);
+                    }
+                    ">" => filter.min_iso = Some(iso.saturating_add(1)),
+                    ">=" => filter.min_iso = Some(iso),
+                    "<" => filter.max_iso = Some(iso.saturating_sub(1)),
+                    _ => filter.max_iso = Some(iso),
+                }
+            }
+            _ => return Err(QueryError::new(term, "Unknown field")),
+        }
+    }
+
+    Ok(ParsedQuery {
+        text: text.join(" "),
+        filter,
+    })
+}
+
+// Splits on whitespace outside of double quotes, the quotes themselves are dropped
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut value = String::new();
+    let mut operator_at = None;
+    let mut quoted = false;
+    let mut seen_quote = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                seen_quote = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !value.is_empty() {
+                    tokens.push(Token {
+                        value: std::mem::take(&mut value),
+                        operator_at: operator_at.take(),
+                    });
+                }
+                seen_quote = false;
+            }
+            ':' | '>' | '<' if !seen_quote && operator_at.is_none() => {
+                operator_at = Some(value.len());
+                value.push(c);
+            }
+            c => value.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(QueryError::new(&value, "Unterminated quote"));
+    }
+    if !value.is_empty() {
+        tokens.push(Token { value, operator_at });
+    }
+    Ok(tokens)
+}
+
+// Accepts 2023, 2023-06 and 2023-06-15, returning the start of that period in milliseconds
+fn parse_date(term: &str, value: &str) -> Result<i64, QueryError> {
+    let mut parts = value.splitn(3, '-');
+    let year = parts.next().and_then(|year| year.parse::<i32>().ok());
+    let month = parts.next().map(|month| month.parse::<u32>().ok());
+    let day = parts.next().map(|day| day.parse::<u32>().ok());
+
+    let date = match (year, month, day) {
+        (Some(year), None, None) => NaiveDate::from_ymd_opt(year, 1, 1),
+        (Some(year), Some(Some(month)), None) => NaiveDate::from_ymd_opt(year, month, 1),
+        (Some(year), Some(Some(month)), Some(Some(day))) => {
+            NaiveDate::from_ymd_opt(year, month, day)
+        }
+        _ => None,
+    };
+
+    match date.and_then(|date| date.and_hms_opt(0, 0, 0)) {
+        Some(date) => Ok(date.and_utc().timestamp_millis()),
+        None => Err(QueryError::new(
+            term,
+            "Dates must be formatted as YYYY, YYYY-MM or YYYY-MM-DD",
+        )),
+    }
+}
+
+// Accepts latitude,longitude with an optional radius in kilometers. Media only store
+// coordinates and there is no geocoder, so place names such as near:Lisbon are rejected
+fn parse_location(term: &str, value: &str) -> Result<Location, QueryError> {
+    let numbers: Option<Vec<f64>> = value
+        .split(',')
+        .map(|number| number.trim().parse::<f64>().ok())
+        .collect();
+
+    let location = match numbers.as_deref() {
+        Some([latitude, longitude]) => Location {
+            latitude: *latitude,
+            longitude: *longitude,
+            radius_km: DEFAULT_NEAR_RADIUS_KM,
+        },
+        Some([latitude, longitude, radius_km]) => Location {
+            latitude: *latitude,
+            longitude: *longitude,
+            radius_km: *radius_km,
+        },
+        _ => {
+            return Err(QueryError::new(
+                term,
+                "Place names are not supported, locations must be given as latitude,longitude[,radius in km]",
+            ))
+        }
+    };
+
+    if !(-90.0..=90.0).contains(&location.latitude)
+        || !(-180.0..=180.0).contains(&location.longitude)
+        || location.radius_km <= 0.0
+    {
+        return Err(QueryError::new(term, "Location is out of range"));
+    }
+    Ok(location)
+}
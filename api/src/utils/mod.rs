@@ -1 +1,4 @@
 pub mod jwt;
+pub mod renditions;
+pub mod resize;
+pub mod search_cache;
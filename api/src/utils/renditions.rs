@@ -0,0 +1,56 @@
+use database::{schema::rendition, MediaPreview, FULL_RENDITION};
+use s3::Bucket;
+
+use crate::models::api_models::PreviewItem;
+
+// The rendition to show for a size, either the name of a rendition or a height in pixels,
+// which picks the smallest rendition at least that high or else the largest one, leaving out
+// the full resolution copy. Media without a rendition in the format get the JPEG or PNG made for every size
+pub fn pick_rendition<'a>(
+    renditions: &'a [rendition::Model],
+    size: &str,
+    format: Option<&str>,
+) -> Option<&'a rendition::Model> {
+    let name = match size.parse::<i32>() {
+        Ok(height) => {
+            let mut sized: Vec<&rendition::Model> = renditions
+                .iter()
+                .filter(|rendition| rendition.name != FULL_RENDITION)
+                .collect();
+            sized.sort_by_key(|rendition| rendition.height);
+            sized
+                .iter()
+                .find(|rendition| rendition.height >= height)
+                .or(sized.last())
+                .map(|rendition| rendition.name.as_str())?
+        }
+        Err(_) => size,
+    };
+    let of_size = || renditions.iter().filter(|rendition| rendition.name == name);
+
+    let content_type = format.map(|format| match format.to_lowercase().as_str() {
+        "jpg" => "image/jpeg".to_string(),
+        format => format!("image/{format}"),
+    });
+    of_size()
+        .find(|rendition| Some(&rendition.content_type) == content_type.as_ref())
+        .or_else(|| {
+            of_size().find(|rendition| {
+                rendition.content_type == "image/jpeg" || rendition.content_type == "image/png"
+            })
+        })
+}
+
+// A listed media with the url of its preview, or of the rendition picked for it. Media
+// without one get an empty url, those whose url can't be presigned are left out
+pub async fn preview_item(
+    bucket: &Bucket,
+    preview: MediaPreview,
+    object_id: Option<String>,
+) -> Option<PreviewItem> {
+    let preview_url = match object_id {
+        Some(object_id) => bucket.presign_get(object_id, 86400, None).await.ok()?,
+        None => "".to_string(),
+    };
+    Some(PreviewItem::new(preview, preview_url))
+}
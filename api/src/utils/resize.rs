@@ -0,0 +1,339 @@
+use std::io::Cursor;
+
+use image::{imageops::FilterType::Lanczos3, DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const RESIZED_ID_PREFIX: &str = "img/";
+const MAX_SIZE: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fit {
+    // The whole image inside the box
+    Contain,
+    // The box filled, cropping what sticks out around the center
+    Cover,
+    // Like cover, cropping around the faces of the media
+    Face,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Jpeg,
+    Webp,
+    Png,
+    Avif,
+}
+
+impl OutputFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Png => "image/png",
+            OutputFormat::Avif => "image/avif",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Png => "png",
+            OutputFormat::Avif => "avif",
+        }
+    }
+
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Webp => ImageFormat::WebP,
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Avif => ImageFormat::Avif,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ResizeQuery {
+    pub w: Option<u32>,
+    pub h: Option<u32>,
+    pub fit: Option<String>,
+    pub format: Option<String>,
+}
+
+// A validated resize query, a missing width or height leaves that side unbounded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resize {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: Fit,
+    pub format: OutputFormat,
+}
+
+impl Resize {
+    pub fn parse(query: &ResizeQuery) -> Result<Resize, String> {
+        if query.w.is_none() && query.h.is_none() {
+            return Err("A width or a height is needed".to_string());
+        }
+        for size in [query.w, query.h].into_iter().flatten() {
+            if size == 0 || size > MAX_SIZE {
+                return Err(format!("Sizes go from 1 to {MAX_SIZE}"));
+            }
+        }
+        let fit = match query.fit.as_deref().unwrap_or("contain") {
+            "contain" => Fit::Contain,
+            "cover" => Fit::Cover,
+            "face" => Fit::Face,
+            fit => return Err(format!("Unknown fit {fit}")),
+        };
+        if fit != Fit::Contain && (query.w.is_none() || query.h.is_none()) {
+            return Err("Cropping needs a width and a height".to_string());
+        }
+        let format = match query.format.as_deref().unwrap_or("jpeg") {
+            "jpeg" | "jpg" => OutputFormat::Jpeg,
+            "webp" => OutputFormat::Webp,
+            "png" => OutputFormat::Png,
+            "avif" => OutputFormat::Avif,
+            format => return Err(format!("Unknown format {format}")),
+        };
+        Ok(Resize {
+            width: query.w,
+            height: query.h,
+            fit,
+            format,
+        })
+    }
+
+    // The same request for the same faces always maps to the same object, faces are part of
+    // the key so that moving them makes a new crop
+    pub fn object_id(&self, media_id: &str, faces: &[Vec<i32>]) -> String {
+        let fit = match self.fit {
+            Fit::Contain => "contain".to_string(),
+            Fit::Cover => "cover".to_string(),
+            Fit::Face => format!("face-{}", &hash(&format!("{faces:?}"))[..16]),
+        };
+        format!(
+            "{RESIZED_ID_PREFIX}{media_id}/{}x{}-{fit}.{}",
+            self.width.unwrap_or(0),
+            self.height.unwrap_or(0),
+            self.format.extension()
+        )
+    }
+
+    // Strong, the output only depends on the original, whose checksum never changes, and the key
+    pub fn etag(&self, checksum: &str, object_id: &str) -> String {
+        format!("\"{}\"", hash(&format!("{checksum}:{object_id}")))
+    }
+
+    // Decodes the source upright, resizes it and encodes it, images are never scaled up.
+    // Face boxes are in the coordinates of the upright image, as the face crops are
+    pub fn apply(&self, source: &[u8], faces: &[Vec<i32>]) -> Result<Vec<u8>, String> {
+        let image = decode(source)?;
+        let resized = match self.fit {
+            Fit::Contain => contain(&image, self.width, self.height),
+            Fit::Cover => cover(&image, self.width, self.height, None),
+            Fit::Face => cover(&image, self.width, self.height, faces_center(faces)),
+        };
+        let resized = match self.format {
+            OutputFormat::Jpeg => DynamicImage::ImageRgb8(resized.to_rgb8()),
+            _ if resized.color().has_alpha() => DynamicImage::ImageRgba8(resized.to_rgba8()),
+            _ => DynamicImage::ImageRgb8(resized.to_rgb8()),
+        };
+        let mut bytes: Vec<u8> = Vec::new();
+        resized
+            .write_to(&mut Cursor::new(&mut bytes), self.format.image_format())
+            .map_err(|err| format!("Couldn't encode resized image: {err}"))?;
+        Ok(bytes)
+    }
+}
+
+fn hash(value: &str) -> String {
+    hex::encode(Sha256::digest(value.as_bytes()))
+}
+
+fn decode(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|err| format!("Couldn't read image: {err}"))?
+        .into_decoder()
+        .map_err(|err| format!("Couldn't decode image: {err}"))?;
+    let orientation = decoder
+        .orientation()
+        .map_err(|err| format!("Couldn't get image orientation: {err}"))?;
+    let mut image = DynamicImage::from_decoder(decoder)
+        .map_err(|err| format!("Couldn't decode image: {err}"))?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn contain(image: &DynamicImage, width: Option<u32>, height: Option<u32>) -> DynamicImage {
+    let width = width.unwrap_or(u32::MAX).min(image.width());
+    let height = height.unwrap_or(u32::MAX).min(image.height());
+    if width == image.width() && height == image.height() {
+        return image.clone();
+    }
+    image.resize(width, height, Lanczos3)
+}
+
+// Crops the window of `cover_window`, then scales it down to the requested size
+fn cover(
+    image: &DynamicImage,
+    width: Option<u32>,
+    height: Option<u32>,
+    focus: Option<(f32, f32)>,
+) -> DynamicImage {
+    let (Some(width), Some(height)) = (width, height) else {
+        return contain(image, width, height);
+    };
+    let (x, y, crop_width, crop_height) =
+        cover_window(image.width(), image.height(), width, height, focus);
+    let crop = image.crop_imm(x, y, crop_width, crop_height);
+    if crop.width() <= width {
+        return crop;
+    }
+    crop.resize_exact(width, height, Lanczos3)
+}
+
+// The largest window of the requested aspect ratio centered on the focus, or the middle of
+// the image, moved to stay inside it. Returns its top left corner, width and height
+fn cover_window(
+    image_width: u32,
+    image_height: u32,
+    width: u32,
+    height: u32,
+    focus: Option<(f32, f32)>,
+) -> (u32, u32, u32, u32) {
+    let (image_width, image_height) = (image_width as f32, image_height as f32);
+    let aspect_ratio = width as f32 / height as f32;
+    let crop_width = image_width.min(image_height * aspect_ratio);
+    let crop_height = crop_width / aspect_ratio;
+    let (center_x, center_y) = focus.unwrap_or((image_width / 2.0, image_height / 2.0));
+    let x = (center_x - crop_width / 2.0).clamp(0.0, image_width - crop_width);
+    let y = (center_y - crop_height / 2.0).clamp(0.0, image_height - crop_height);
+    (
+        x as u32,
+        y as u32,
+        (crop_width as u32).max(1),
+        (crop_height as u32).max(1),
+    )
+}
+
+// Center of the box around every [left, top, right, bottom] face box. The boxes are taken as
+// they are, in the coordinates of the upright image that `decode` returns
+fn faces_center(faces: &[Vec<i32>]) -> Option<(f32, f32)> {
+    let boxes: Vec<[i32; 4]> = faces
+        .iter()
+        .filter_map(|face| face.as_slice().try_into().ok())
+        .collect();
+    let left = boxes.iter().map(|face| face[0]).min()?;
+    let top = boxes.iter().map(|face| face[1]).min()?;
+    let right = boxes.iter().map(|face| face[2]).max()?;
+    let bottom = boxes.iter().map(|face| face[3]).max()?;
+    Some(((left + right) as f32 / 2.0, (top + bottom) as f32 / 2.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resize(width: u32, height: u32, fit: Fit) -> Resize {
+        Resize {
+            width: Some(width),
+            height: Some(height),
+            fit,
+            format: OutputFormat::Jpeg,
+        }
+    }
+
+    #[test]
+    fn cover_crops_the_middle_at_the_requested_ratio() {
+        assert_eq!(
+            cover_window(4000, 3000, 100, 100, None),
+            (500, 0, 3000, 3000)
+        );
+        assert_eq!(
+            cover_window(4000, 3000, 400, 100, None),
+            (0, 1000, 4000, 1000)
+        );
+        assert_eq!(
+            cover_window(3000, 4000, 300, 200, None),
+            (0, 1000, 3000, 2000)
+        );
+
+        let image = DynamicImage::new_rgb8(400, 300);
+        let covered = cover(&image, Some(100), Some(50), None);
+        assert_eq!((covered.width(), covered.height()), (100, 50));
+    }
+
+    #[test]
+    fn images_are_never_scaled_up() {
+        let image = DynamicImage::new_rgb8(200, 100);
+        let contained = contain(&image, Some(800), Some(800));
+        assert_eq!((contained.width(), contained.height()), (200, 100));
+        let contained = contain(&image, Some(100), None);
+        assert_eq!((contained.width(), contained.height()), (100, 50));
+
+        // The crop keeps the requested ratio at the size of the image
+        let covered = cover(&image, Some(400), Some(400), None);
+        assert_eq!((covered.width(), covered.height()), (100, 100));
+    }
+
+    #[test]
+    fn face_crops_follow_the_faces_and_stay_inside_the_image() {
+        // A face near the right edge of a landscape photo
+        let focus = faces_center(&[vec![3800, 1400, 3950, 1600]]);
+        assert_eq!(focus, Some((3875.0, 1500.0)));
+        assert_eq!(
+            cover_window(4000, 3000, 100, 100, focus),
+            (1000, 0, 3000, 3000)
+        );
+        // Faces on both sides are kept together
+        let focus = faces_center(&[vec![200, 1700, 300, 1800], vec![1700, 2000, 1800, 2100]]);
+        assert_eq!(focus, Some((1000.0, 1900.0)));
+        assert_eq!(
+            cover_window(4000, 3000, 200, 100, focus),
+            (0, 900, 4000, 2000)
+        );
+        // Malformed boxes are left out, without faces the crop is centered
+        assert_eq!(faces_center(&[vec![1, 2, 3]]), None);
+        assert_eq!(
+            cover_window(4000, 3000, 100, 100, None),
+            (500, 0, 3000, 3000)
+        );
+    }
+
+    #[test]
+    fn keys_only_change_with_the_request_and_the_faces() {
+        let faces = vec![vec![10, 10, 50, 50]];
+        let face = resize(100, 100, Fit::Face);
+        assert_eq!(
+            face.object_id("photo", &faces),
+            face.object_id("photo", &faces.clone())
+        );
+        assert_ne!(
+            face.object_id("photo", &faces),
+            face.object_id("photo", &[vec![20, 10, 60, 50]])
+        );
+        assert!(face
+            .object_id("photo", &faces)
+            .starts_with("img/photo/100x100-face-"));
+
+        let cover = resize(100, 100, Fit::Cover);
+        assert_eq!(
+            cover.object_id("photo", &faces),
+            cover.object_id("photo", &[])
+        );
+        assert_eq!(cover.object_id("photo", &[]), "img/photo/100x100-cover.jpg");
+
+        let object_id = cover.object_id("photo", &[]);
+        assert_eq!(
+            cover.etag("checksum", &object_id),
+            cover.etag("checksum", &object_id)
+        );
+        assert_ne!(
+            cover.etag("checksum", &object_id),
+            cover.etag("other checksum", &object_id)
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::models::api_models::SearchResult;
+
+const MAX_ENTRIES: usize = 1000;
+
+struct Entry {
+    stored_at: Instant,
+    items: Arc<Vec<SearchResult>>,
+}
+
+// Keeps the full ranking of recent searches so that every page of a query is cut from the same
+// results and the search service is only asked once
+pub struct SearchCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl SearchCache {
+    pub fn new(ttl: Duration) -> Self {
+        SearchCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<Vec<SearchResult>>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|entry| entry.stored_at.elapsed() < self.ttl)
+            .map(|entry| entry.items.clone())
+    }
+
+    pub fn insert(&self, key: String, items: Vec<SearchResult>) -> Arc<Vec<SearchResult>> {
+        let items = Arc::new(items);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.stored_at.elapsed() < self.ttl);
+        if entries.len() >= MAX_ENTRIES {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                stored_at: Instant::now(),
+                items: items.clone(),
+            },
+        );
+        items
+    }
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use chrono::Utc;
+use common::{access_token, TestApp, ALICE, SECRET};
+use database::UserRepository;
+use serde_json::{json, Value};
+
+fn previews_with_authorization(authorization: &str) -> Request<Body> {
+    Request::get("/previews")
+        .header(header::AUTHORIZATION, authorization)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn private_routes_require_an_authorization_header() {
+    let app = TestApp::new();
+    let (status, _) = app
+        .send(Request::get("/previews").body(Body::empty()).unwrap())
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn private_routes_require_a_bearer_token() {
+    let app = TestApp::new();
+    let now = Utc::now().timestamp_millis();
+    let token = access_token(ALICE, SECRET, now, now + 60_000);
+    let (status, _) = app
+        .send(previews_with_authorization(&format!("Basic {token}")))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn tokens_signed_with_another_secret_are_rejected() {
+    let app = TestApp::new();
+    let now = Utc::now().timestamp_millis();
+    let token = access_token(ALICE, "another-secret", now, now + 60_000);
+    let (status, _) = app
+        .send(previews_with_authorization(&format!("Bearer {token}")))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn expired_tokens_are_rejected() {
+    let app = TestApp::new();
+    let now = Utc::now().timestamp_millis();
+    let token = access_token(ALICE, SECRET, now - 120_000, now - 60_000);
+    let (status, _) = app
+        .send(previews_with_authorization(&format!("Bearer {token}")))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn login_issues_tokens_accepted_by_private_routes() {
+    let app = TestApp::new();
+    let password = bcrypt::hash("hunter2", 4).unwrap();
+    app.repository
+        .add_user(ALICE.to_string(), "alice".to_string(), password)
+        .await
+        .unwrap();
+
+    let (status, body) = app
+        .send(
+            Request::post("/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "username": "alice", "password": "hunter2" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let tokens: Value = serde_json::from_slice(&body).unwrap();
+    let access_token = tokens["access_token"].as_str().unwrap();
+    let (status, _) = app
+        .send(previews_with_authorization(&format!(
+            "Bearer {access_token}"
+        )))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn login_rejects_a_wrong_password() {
+    let app = TestApp::new();
+    let password = bcrypt::hash("hunter2", 4).unwrap();
+    app.repository
+        .add_user(ALICE.to_string(), "alice".to_string(), password)
+        .await
+        .unwrap();
+
+    let (status, _) = app
+        .send(
+            Request::post("/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "username": "alice", "password": "hunter3" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn login_rejects_unknown_users() {
+    let app = TestApp::new();
+    let (status, _) = app
+        .send(
+            Request::post("/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "username": "mallory", "password": "hunter2" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
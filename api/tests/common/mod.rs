@@ -0,0 +1,156 @@
+#![allow(dead_code)]
+
+use std::{sync::Arc, time::Duration};
+
+use api::{router, SearchCache, ServerConfig};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use chrono::Utc;
+use database::{InMemoryRepository, MediaRepository};
+use jsonwebtoken::{EncodingKey, Header};
+use messaging::InMemoryBus;
+use s3::{creds::Credentials, Bucket, Region};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+pub const SECRET: &str = "test-secret";
+pub const ALICE: &str = "alice";
+pub const BOB: &str = "bob";
+
+pub struct TestApp {
+    pub repository: InMemoryRepository,
+    pub bus: InMemoryBus,
+    router: Router,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let repository = InMemoryRepository::new();
+        let bus = InMemoryBus::new();
+        let server_config = ServerConfig {
+            database: Arc::new(repository.clone()),
+            secret: SECRET.to_string(),
+            bucket: bucket(),
+            bus: Arc::new(bus.clone()),
+            search_cache: Arc::new(SearchCache::new(Duration::from_secs(60))),
+            search_timeout: Duration::from_millis(200),
+        };
+        TestApp {
+            repository,
+            bus,
+            router: router(server_config),
+        }
+    }
+
+    pub async fn add_media(&self, user_id: &str, media_id: &str, created_at: i64) {
+        self.repository
+            .add_media(
+                user_id.to_string(),
+                media_id.to_string(),
+                format!("hash-{media_id}"),
+                created_at,
+                1024,
+                format!("{media_id}.jpg"),
+            )
+            .await
+            .unwrap();
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    pub async fn get(&self, uri: &str, user_id: &str) -> (StatusCode, Vec<u8>) {
+        self.send(
+            Request::get(uri)
+                .header(header::AUTHORIZATION, bearer(user_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+    }
+
+    pub async fn get_json(&self, uri: &str, user_id: &str) -> (StatusCode, Value) {
+        let (status, body) = self.get(uri, user_id).await;
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    pub async fn send_json(
+        &self,
+        method: &str,
+        uri: &str,
+        user_id: &str,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let (status, body) = self
+            .send(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header(header::AUTHORIZATION, bearer(user_id))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await;
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+}
+
+// Signs an access token the same way the login route does
+pub fn access_token(user_id: &str, secret: &str, issued_at: i64, expires_at: i64) -> String {
+    jsonwebtoken::encode(
+        &Header::default(),
+        &json!({ "iat": issued_at, "exp": expires_at, "user_id": user_id }),
+        &EncodingKey::from_secret(secret.as_ref()),
+    )
+    .unwrap()
+}
+
+pub fn bearer(user_id: &str) -> String {
+    let now = Utc::now().timestamp_millis();
+    format!(
+        "Bearer {}",
+        access_token(user_id, SECRET, now - 1000, now + 3_600_000)
+    )
+}
+
+// Presigning is done locally, the endpoint is never contacted
+fn bucket() -> Box<Bucket> {
+    let region = Region::Custom {
+        region: "test".to_string(),
+        endpoint: "http://127.0.0.1:9".to_string(),
+    };
+    let credentials = Credentials::new(Some("access"), Some("secret"), None, None, None).unwrap();
+    Bucket::new("chronolens", region, credentials)
+        .unwrap()
+        .with_path_style()
+}
+
+pub fn multipart_upload(
+    user_id: &str,
+    checksum: &str,
+    content_type: &str,
+    timestamp: Option<i64>,
+) -> Request<Body> {
+    let boundary = "chronolens-boundary";
+    let body = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"{checksum}\"; filename=\"photo\"\r\nContent-Type: {content_type}\r\n\r\ncontents\r\n--{boundary}--\r\n"
+    );
+    let mut request = Request::post("/image/upload")
+        .header(header::AUTHORIZATION, bearer(user_id))
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={boundary}"),
+        );
+    if let Some(timestamp) = timestamp {
+        request = request.header("Timestamp", timestamp);
+    }
+    request.body(Body::from(body)).unwrap()
+}
@@ -0,0 +1,771 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{TestApp, ALICE, BOB};
+use database::{FaceRepository, NewFaceSuggestion};
+use futures_util::StreamExt;
+use messaging::MessageBus;
+use serde_json::{json, Value};
+
+struct Person {
+    face_id: i32,
+    cluster_id: i32,
+}
+
+// A named face with one cluster detected in each of the given photos
+async fn add_person(app: &TestApp, user_id: &str, name: &str, media_ids: &[&str]) -> Person {
+    let cluster_id = app.repository.add_cluster(user_id, None);
+    for (i, media_id) in media_ids.iter().enumerate() {
+        app.add_media(user_id, media_id, i as i64).await;
+        app.repository
+            .add_media_face(media_id, vec![0, 0, 10, 10], Some(cluster_id));
+    }
+    app.repository
+        .insert_face(user_id.to_string(), vec![cluster_id], name.to_string())
+        .await
+        .unwrap();
+    let (_, faces) = app.get_json("/faces", user_id).await;
+    let face_id = faces["faces"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|face| face["name"] == name)
+        .unwrap()["face_id"]
+        .as_i64()
+        .unwrap() as i32;
+    Person {
+        face_id,
+        cluster_id,
+    }
+}
+
+fn names(faces: &Value) -> Vec<String> {
+    let mut names: Vec<String> = faces["faces"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|face| face["name"].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+fn cluster_ids(faces: &Value) -> Vec<i64> {
+    faces["clusters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|cluster| cluster["cluster_id"].as_i64().unwrap())
+        .collect()
+}
+
+fn ids(previews: &Value) -> Vec<String> {
+    let mut ids: Vec<String> = previews
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|preview| preview["id"].as_str().unwrap().to_string())
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn faces_can_be_renamed() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["photo"]).await;
+
+    let uri = format!("/face/{}/name", carol.face_id);
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "name": " Caroline " }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(names(&faces), vec!["Caroline".to_string()]);
+
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "name": "  " }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn merging_moves_the_clusters_into_the_face() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["photo-1"]).await;
+    let duplicate = add_person(&app, ALICE, "Carol 2", &["photo-2"]).await;
+
+    let (status, _) = app
+        .send_json(
+            "POST",
+            &format!("/face/{}/merge", carol.face_id),
+            ALICE,
+            json!({ "face_id": duplicate.face_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(names(&faces), vec!["Carol".to_string()]);
+    let (_, previews) = app
+        .get_json(&format!("/face/{}", carol.face_id), ALICE)
+        .await;
+    assert_eq!(
+        ids(&previews),
+        vec!["photo-1".to_string(), "photo-2".to_string()]
+    );
+
+    let (status, _) = app
+        .send_json(
+            "POST",
+            &format!("/face/{}/merge", carol.face_id),
+            ALICE,
+            json!({ "face_id": carol.face_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn deleting_a_face_keeps_its_clusters_unnamed() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["photo"]).await;
+
+    let (status, _) = app
+        .send_json(
+            "DELETE",
+            &format!("/face/{}", carol.face_id),
+            ALICE,
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert!(names(&faces).is_empty());
+    assert_eq!(cluster_ids(&faces), vec![carol.cluster_id as i64]);
+}
+
+#[tokio::test]
+async fn clusters_can_be_added_to_and_removed_from_a_face() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["photo-1"]).await;
+    app.add_media(ALICE, "photo-2", 2).await;
+    let cluster_id = app.repository.add_cluster(ALICE, None);
+    app.repository
+        .add_media_face("photo-2", vec![0, 0, 10, 10], Some(cluster_id));
+
+    let uri = format!("/face/{}/cluster/{cluster_id}", carol.face_id);
+    let (status, _) = app.send_json("PUT", &uri, ALICE, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, previews) = app
+        .get_json(&format!("/face/{}", carol.face_id), ALICE)
+        .await;
+    assert_eq!(
+        ids(&previews),
+        vec!["photo-1".to_string(), "photo-2".to_string()]
+    );
+
+    let (status, _) = app.send_json("DELETE", &uri, ALICE, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(cluster_ids(&faces), vec![cluster_id as i64]);
+
+    // Only clusters of the face can be removed from it
+    let (status, _) = app.send_json("DELETE", &uri, ALICE, Value::Null).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn moving_the_last_detection_of_a_person_keeps_the_person() {
+    let app = TestApp::new();
+    let dave = add_person(&app, ALICE, "Dave", &["photo-1"]).await;
+    app.add_media(ALICE, "photo-2", 2).await;
+    let cluster_id = app.repository.add_cluster(ALICE, None);
+    let media_face_id =
+        app.repository
+            .add_media_face("photo-2", vec![0, 0, 10, 10], Some(cluster_id));
+    app.repository
+        .insert_face(ALICE.to_string(), vec![cluster_id], "Carol".to_string())
+        .await
+        .unwrap();
+    let face = |faces: &Value, name: &str| {
+        faces["faces"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|face| face["name"] == name)
+            .cloned()
+    };
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    let carol_face_id = face(&faces, "Carol").unwrap()["face_id"].as_i64().unwrap();
+
+    let uri = format!("/media_face/{media_face_id}");
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "face_id": dave.face_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(names(&faces), vec!["Carol".to_string(), "Dave".to_string()]);
+    let carol = face(&faces, "Carol").unwrap();
+    assert_eq!(carol["photo_url"], "");
+    assert_eq!(carol["bbox"], json!([]));
+    let (_, previews) = app.get_json(&format!("/face/{carol_face_id}"), ALICE).await;
+    assert!(ids(&previews).is_empty());
+
+    // The kept cluster takes the detection back
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "face_id": carol_face_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, previews) = app.get_json(&format!("/face/{carol_face_id}"), ALICE).await;
+    assert_eq!(ids(&previews), vec!["photo-2".to_string()]);
+}
+
+#[tokio::test]
+async fn unnaming_an_emptied_cluster_removes_it() {
+    let app = TestApp::new();
+    let dave = add_person(&app, ALICE, "Dave", &["photo-1"]).await;
+    app.add_media(ALICE, "photo-2", 2).await;
+    let cluster_id = app.repository.add_cluster(ALICE, None);
+    let media_face_id =
+        app.repository
+            .add_media_face("photo-2", vec![0, 0, 10, 10], Some(cluster_id));
+    app.repository
+        .insert_face(ALICE.to_string(), vec![cluster_id], "Carol".to_string())
+        .await
+        .unwrap();
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    let carol_face_id = faces["faces"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|face| face["name"] == "Carol")
+        .unwrap()["face_id"]
+        .as_i64()
+        .unwrap();
+    let uri = format!("/media_face/{media_face_id}");
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "face_id": dave.face_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app
+        .send_json(
+            "DELETE",
+            &format!("/face/{carol_face_id}"),
+            ALICE,
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(names(&faces), vec!["Dave".to_string()]);
+    assert!(cluster_ids(&faces).is_empty());
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "cluster_id": cluster_id }))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn a_detection_can_be_moved_to_a_face_without_clusters() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["photo-1"]).await;
+    let uri = format!("/face/{}/cluster/{}", carol.face_id, carol.cluster_id);
+    let (status, _) = app.send_json("DELETE", &uri, ALICE, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    app.add_media(ALICE, "photo-2", 2).await;
+    let media_face_id = app
+        .repository
+        .add_media_face("photo-2", vec![0, 0, 10, 10], None);
+
+    let (status, _) = app
+        .send_json(
+            "PUT",
+            &format!("/media_face/{media_face_id}"),
+            ALICE,
+            json!({ "face_id": carol.face_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, previews) = app
+        .get_json(&format!("/face/{}", carol.face_id), ALICE)
+        .await;
+    assert_eq!(ids(&previews), vec!["photo-2".to_string()]);
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(cluster_ids(&faces), vec![carol.cluster_id as i64]);
+}
+
+#[tokio::test]
+async fn a_detection_can_be_moved_to_another_face() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["photo-1"]).await;
+    app.add_media(ALICE, "photo-2", 2).await;
+    let stray_cluster_id = app.repository.add_cluster(ALICE, None);
+    let media_face_id =
+        app.repository
+            .add_media_face("photo-2", vec![0, 0, 10, 10], Some(stray_cluster_id));
+
+    let uri = format!("/media_face/{media_face_id}");
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "face_id": carol.face_id }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, previews) = app
+        .get_json(&format!("/face/{}", carol.face_id), ALICE)
+        .await;
+    assert_eq!(
+        ids(&previews),
+        vec!["photo-1".to_string(), "photo-2".to_string()]
+    );
+    // The emptied cluster is gone
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert!(cluster_ids(&faces).is_empty());
+
+    let (status, _) = app
+        .send_json(
+            "PUT",
+            &uri,
+            ALICE,
+            json!({ "face_id": carol.face_id, "cluster_id": carol.cluster_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn the_featured_photo_has_to_show_the_face() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["photo-1", "photo-2"]).await;
+    app.add_media(ALICE, "landscape", 3).await;
+
+    let uri = format!("/face/{}/featured_photo", carol.face_id);
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "media_id": "landscape" }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .send_json("PUT", &uri, ALICE, json!({ "media_id": "photo-2" }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert!(faces["faces"][0]["photo_url"]
+        .as_str()
+        .unwrap()
+        .contains("photo-2"));
+}
+
+#[tokio::test]
+async fn people_of_another_user_can_not_be_changed() {
+    let app = TestApp::new();
+    let dave = add_person(&app, BOB, "Dave", &["bob-photo"]).await;
+    let carol = add_person(&app, ALICE, "Carol", &["alice-photo"]).await;
+    app.add_media(BOB, "bob-group-photo", 2).await;
+    let bob_media_face_id =
+        app.repository
+            .add_media_face("bob-group-photo", vec![5, 5, 10, 10], Some(dave.cluster_id));
+
+    let requests = [
+        (
+            "PUT",
+            format!("/face/{}/name", dave.face_id),
+            json!({ "name": "Mallory" }),
+        ),
+        (
+            "POST",
+            format!("/face/{}/merge", carol.face_id),
+            json!({ "face_id": dave.face_id }),
+        ),
+        (
+            "POST",
+            format!("/face/{}/merge", dave.face_id),
+            json!({ "face_id": carol.face_id }),
+        ),
+        ("DELETE", format!("/face/{}", dave.face_id), Value::Null),
+        (
+            "PUT",
+            format!("/face/{}/cluster/{}", carol.face_id, dave.cluster_id),
+            Value::Null,
+        ),
+        (
+            "DELETE",
+            format!("/face/{}/cluster/{}", dave.face_id, dave.cluster_id),
+            Value::Null,
+        ),
+        (
+            "PUT",
+            format!("/media_face/{bob_media_face_id}"),
+            json!({ "face_id": carol.face_id }),
+        ),
+        (
+            "PUT",
+            format!("/face/{}/featured_photo", dave.face_id),
+            json!({ "media_id": "bob-photo" }),
+        ),
+    ];
+    for (method, uri, body) in requests {
+        let (status, _) = app.send_json(method, &uri, ALICE, body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{method} {uri}");
+    }
+
+    let (_, faces) = app.get_json("/faces", BOB).await;
+    assert_eq!(names(&faces), vec!["Dave".to_string()]);
+    let (_, previews) = app.get_json(&format!("/face/{}", dave.face_id), BOB).await;
+    assert_eq!(
+        ids(&previews),
+        vec!["bob-group-photo".to_string(), "bob-photo".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn people_of_another_user_can_not_be_read() {
+    let app = TestApp::new();
+    let dave = add_person(&app, BOB, "Dave", &["bob-photo"]).await;
+
+    let (status, _) = app.get(&format!("/face/{}", dave.face_id), ALICE).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(names(&faces).is_empty());
+    assert!(cluster_ids(&faces).is_empty());
+}
+
+#[tokio::test]
+async fn creating_a_face_only_claims_clusters_of_the_user() {
+    let app = TestApp::new();
+    app.add_media(BOB, "bob-photo", 1).await;
+    let cluster_id = app.repository.add_cluster(BOB, None);
+    app.repository
+        .add_media_face("bob-photo", vec![0, 0, 10, 10], Some(cluster_id));
+
+    let (status, _) = app
+        .send_json(
+            "POST",
+            "/create_face",
+            ALICE,
+            json!({ "ids": [cluster_id], "name": "Mallory" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, faces) = app.get_json("/faces", BOB).await;
+    assert!(names(&faces).is_empty());
+    assert_eq!(cluster_ids(&faces), vec![cluster_id as i64]);
+}
+
+#[tokio::test]
+async fn suggestions_can_be_accepted_or_rejected() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["photo-1"]).await;
+    app.add_media(ALICE, "photo-2", 2).await;
+    app.add_media(ALICE, "photo-3", 3).await;
+    let stray_cluster_id = app.repository.add_cluster(ALICE, None);
+    let maybe_carol =
+        app.repository
+            .add_media_face("photo-2", vec![0, 0, 10, 10], Some(stray_cluster_id));
+    let not_carol = app
+        .repository
+        .add_media_face("photo-3", vec![0, 0, 10, 10], None);
+    app.repository
+        .save_face_suggestions(
+            ALICE.to_string(),
+            vec![
+                NewFaceSuggestion {
+                    media_face_id: not_carol,
+                    face_id: carol.face_id,
+                    distance: 0.4,
+                },
+                NewFaceSuggestion {
+                    media_face_id: maybe_carol,
+                    face_id: carol.face_id,
+                    distance: 0.35,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+    let (status, suggestions) = app.get_json("/suggestions", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    let suggestions = suggestions.as_array().unwrap().clone();
+    assert_eq!(suggestions.len(), 2);
+    assert_eq!(suggestions[0]["media_face_id"], maybe_carol);
+    assert_eq!(suggestions[0]["name"], "Carol");
+
+    // Suggestions are only visible to the owner of the face
+    let (_, bob_suggestions) = app.get_json("/suggestions", BOB).await;
+    assert!(bob_suggestions.as_array().unwrap().is_empty());
+    let uri = format!("/suggestions/{}/accept", suggestions[0]["suggestion_id"]);
+    let (status, _) = app.send_json("POST", &uri, BOB, Value::Null).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app.send_json("POST", &uri, ALICE, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, previews) = app
+        .get_json(&format!("/face/{}", carol.face_id), ALICE)
+        .await;
+    assert_eq!(
+        ids(&previews),
+        vec!["photo-1".to_string(), "photo-2".to_string()]
+    );
+
+    let uri = format!("/suggestions/{}/reject", suggestions[1]["suggestion_id"]);
+    let (status, _) = app.send_json("POST", &uri, ALICE, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, suggestions) = app.get_json("/suggestions", ALICE).await;
+    assert!(suggestions.as_array().unwrap().is_empty());
+    assert_eq!(
+        app.repository
+            .get_rejected_suggestions(ALICE.to_string())
+            .await
+            .unwrap(),
+        vec![(not_carol, carol.face_id)]
+    );
+}
+
+#[tokio::test]
+async fn previews_can_combine_people() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["carol", "group"]).await;
+    let dave = add_person(&app, ALICE, "Dave", &["dave"]).await;
+    app.repository
+        .add_media_face("group", vec![20, 20, 10, 10], Some(dave.cluster_id));
+    let erin = add_person(&app, ALICE, "Erin", &["erin"]).await;
+
+    let previews = |query: String| {
+        let app = &app;
+        async move {
+            let (status, previews) = app
+                .get_json(&format!("/faces/previews?{query}"), ALICE)
+                .await;
+            assert_eq!(status, StatusCode::OK, "{query}");
+            ids(&previews)
+        }
+    };
+    let (c, d, e) = (carol.face_id, dave.face_id, erin.face_id);
+    assert_eq!(
+        previews(format!("all={c},{d}")).await,
+        vec!["group".to_string()]
+    );
+    assert_eq!(
+        previews(format!("any={d},{e}")).await,
+        vec!["dave".to_string(), "erin".to_string(), "group".to_string()]
+    );
+    assert_eq!(
+        previews(format!("all={c}&none={d}")).await,
+        vec!["carol".to_string()]
+    );
+
+    let (status, _) = app.get("/faces/previews?none=1", ALICE).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.get("/faces/previews?all=carol", ALICE).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.get(&format!("/faces/previews?all={c}"), BOB).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn hidden_faces_and_clusters_are_listed_separately() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["carol"]).await;
+    app.add_media(ALICE, "crowd", 2).await;
+    let stranger_cluster_id = app.repository.add_cluster(ALICE, None);
+    app.repository
+        .add_media_face("crowd", vec![0, 0, 10, 10], Some(stranger_cluster_id));
+
+    let hide = json!({ "hidden": true });
+    let (status, _) = app
+        .send_json(
+            "PUT",
+            &format!("/face/{}/hidden", carol.face_id),
+            ALICE,
+            hide.clone(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app
+        .send_json(
+            "PUT",
+            &format!("/cluster/{stranger_cluster_id}/hidden"),
+            ALICE,
+            hide.clone(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert!(names(&faces).is_empty());
+    assert!(cluster_ids(&faces).is_empty());
+    let (_, hidden) = app.get_json("/faces?hidden=true", ALICE).await;
+    assert_eq!(names(&hidden), vec!["Carol".to_string()]);
+    assert_eq!(cluster_ids(&hidden), vec![stranger_cluster_id as i64]);
+
+    let (status, _) = app
+        .send_json(
+            "PUT",
+            &format!("/face/{}/hidden", carol.face_id),
+            ALICE,
+            json!({ "hidden": false }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(names(&faces), vec!["Carol".to_string()]);
+
+    let (status, _) = app
+        .send_json(
+            "PUT",
+            &format!("/cluster/{stranger_cluster_id}/hidden"),
+            BOB,
+            hide,
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn faces_can_be_tagged_by_hand() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["portrait"]).await;
+    app.add_media(ALICE, "profile", 2).await;
+    app.add_media(ALICE, "party", 3).await;
+    let mut crop_requests = app
+        .bus
+        .subscribe("face-crops", "test_consumer")
+        .await
+        .unwrap();
+
+    let (status, tagged) = app
+        .send_json(
+            "POST",
+            "/media/profile/faces",
+            ALICE,
+            json!({ "bbox": [10, 10, 40, 40], "face_id": carol.face_id }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tagged["face_id"], carol.face_id);
+    let request = crop_requests.next().await.unwrap().unwrap();
+    assert_eq!(String::from_utf8_lossy(&request.payload), "profile");
+    let (_, previews) = app
+        .get_json(&format!("/face/{}", carol.face_id), ALICE)
+        .await;
+    assert_eq!(
+        ids(&previews),
+        vec!["portrait".to_string(), "profile".to_string()]
+    );
+
+    let (status, tagged) = app
+        .send_json(
+            "POST",
+            "/media/party/faces",
+            ALICE,
+            json!({ "bbox": [0, 0, 20, 20], "name": "Dave" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert_eq!(names(&faces), vec!["Carol".to_string(), "Dave".to_string()]);
+    let (_, previews) = app
+        .get_json(&format!("/face/{}", tagged["face_id"]), ALICE)
+        .await;
+    assert_eq!(ids(&previews), vec!["party".to_string()]);
+
+    let invalid = [
+        json!({ "bbox": [0, 0, 20], "name": "Erin" }),
+        json!({ "bbox": [0, -1, 20, 20], "name": "Erin" }),
+        json!({ "bbox": [0, 0, 20, 20] }),
+        json!({ "bbox": [0, 0, 20, 20], "name": "Erin", "face_id": carol.face_id }),
+    ];
+    for body in invalid {
+        let (status, _) = app
+            .send_json("POST", "/media/party/faces", ALICE, body.clone())
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+
+    let (status, _) = app
+        .send_json(
+            "POST",
+            "/media/party/faces",
+            BOB,
+            json!({ "bbox": [0, 0, 20, 20], "name": "Mallory" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn face_crops_are_returned_once_made() {
+    let app = TestApp::new();
+    let carol = add_person(&app, ALICE, "Carol", &["portrait"]).await;
+    app.add_media(ALICE, "crowd", 2).await;
+    let stranger_cluster_id = app.repository.add_cluster(ALICE, None);
+    let stranger_media_face_id =
+        app.repository
+            .add_media_face("crowd", vec![0, 0, 10, 10], Some(stranger_cluster_id));
+
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert!(faces["faces"][0]["crop_url"].is_null());
+
+    let missing = app
+        .repository
+        .get_missing_face_crops("portrait".to_string())
+        .await
+        .unwrap();
+    assert_eq!(missing.len(), 1);
+    let (media_face_id, _) = missing[0];
+    app.repository
+        .set_face_crop(media_face_id, format!("face/{media_face_id}"))
+        .await
+        .unwrap();
+    assert_eq!(
+        app.repository
+            .get_media_with_missing_face_crops(10)
+            .await
+            .unwrap(),
+        vec!["crowd".to_string()]
+    );
+    // A detection that can't be cropped is given up on after a few attempts
+    for _ in 0..database::FACE_CROP_MAX_ATTEMPTS {
+        app.repository
+            .add_face_crop_failures(vec![stranger_media_face_id])
+            .await
+            .unwrap();
+    }
+    assert!(app
+        .repository
+        .get_media_with_missing_face_crops(10)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(app
+        .repository
+        .get_missing_face_crops("crowd".to_string())
+        .await
+        .unwrap()
+        .is_empty());
+
+    let crop_key = format!("face/{media_face_id}");
+    let (_, faces) = app.get_json("/faces", ALICE).await;
+    assert!(faces["faces"][0]["crop_url"]
+        .as_str()
+        .unwrap()
+        .contains(&crop_key));
+    assert!(faces["clusters"][0]["crop_url"].is_null());
+    let (_, previews) = app
+        .get_json(&format!("/face/{}", carol.face_id), ALICE)
+        .await;
+    assert!(previews[0]["crop_url"]
+        .as_str()
+        .unwrap()
+        .contains(&crop_key));
+}
@@ -0,0 +1,763 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, Request, StatusCode},
+};
+use common::{bearer, multipart_upload, TestApp, ALICE, BOB};
+use database::{
+    CaptureTime, DecodedImage, LogRepository, MediaMetadata, MediaRepository, Rendition,
+};
+
+fn decoded(width: i32, height: i32) -> DecodedImage {
+    DecodedImage {
+        width,
+        height,
+        media_type: Some("image/heic".to_string()),
+        blurhash: Some("LEHV6nWB2yk8pyo0adR*.7kCMdnj".to_string()),
+        dominant_colors: vec!["#a05030".to_string(), "#203040".to_string()],
+    }
+}
+
+fn ids(previews: &serde_json::Value) -> Vec<String> {
+    previews
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|preview| preview["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn upload_rejects_an_existing_checksum() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "beach", 1).await;
+
+    let (status, _) = app
+        .send(multipart_upload(ALICE, "hash-beach", "image/jpeg", Some(2)))
+        .await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let logs = app
+        .repository
+        .get_logs(ALICE.to_string(), 1, 10)
+        .await
+        .unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].level, "Error");
+    assert!(logs[0].message.contains("hash-beach"));
+}
+
+#[tokio::test]
+async fn upload_accepts_a_checksum_owned_by_another_user() {
+    let app = TestApp::new();
+    app.add_media(BOB, "beach", 1).await;
+
+    // Gets past duplicate detection and fails on the content type check instead
+    let (status, _) = app
+        .send(multipart_upload(ALICE, "hash-beach", "text/plain", Some(2)))
+        .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn upload_does_not_require_a_timestamp_header() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "beach", 1).await;
+
+    // Gets past the timestamp and fails on duplicate detection instead
+    let (status, _) = app
+        .send(multipart_upload(ALICE, "hash-beach", "image/jpeg", None))
+        .await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+}
+
+#[tokio::test]
+async fn upload_rejects_an_invalid_timestamp_header() {
+    let app = TestApp::new();
+    let mut request = multipart_upload(ALICE, "hash-beach", "image/jpeg", None);
+    request
+        .headers_mut()
+        .insert("Timestamp", HeaderValue::from_static("yesterday"));
+
+    let (status, _) = app.send(request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn previews_are_paginated_newest_first() {
+    let app = TestApp::new();
+    for i in 1..=25 {
+        app.add_media(ALICE, &format!("media-{i:02}"), i).await;
+    }
+
+    let (status, first_page) = app.get_json("/previews?page=1&page_size=10", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    let expected: Vec<String> = (16..=25).rev().map(|i| format!("media-{i:02}")).collect();
+    assert_eq!(ids(&first_page), expected);
+
+    let (_, last_page) = app.get_json("/previews?page=3&page_size=10", ALICE).await;
+    let expected: Vec<String> = (1..=5).rev().map(|i| format!("media-{i:02}")).collect();
+    assert_eq!(ids(&last_page), expected);
+
+    let (_, past_the_end) = app.get_json("/previews?page=4&page_size=10", ALICE).await;
+    assert!(ids(&past_the_end).is_empty());
+}
+
+#[tokio::test]
+async fn previews_clamp_the_page_size() {
+    let app = TestApp::new();
+    for i in 1..=40 {
+        app.add_media(ALICE, &format!("media-{i:02}"), i).await;
+    }
+
+    let (_, previews) = app.get_json("/previews?page_size=100", ALICE).await;
+    assert_eq!(ids(&previews).len(), 30);
+
+    let (_, previews) = app.get_json("/previews?page=0&page_size=0", ALICE).await;
+    assert_eq!(ids(&previews), vec!["media-40".to_string()]);
+}
+
+#[tokio::test]
+async fn previews_only_list_the_users_media() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "alice-photo", 1).await;
+    app.add_media(BOB, "bob-photo", 2).await;
+    app.repository
+        .update_media_preview(
+            "alice-photo".to_string(),
+            "prev/alice-photo".to_string(),
+            decoded(3024, 4032),
+        )
+        .await
+        .unwrap();
+
+    let (_, previews) = app.get_json("/previews", ALICE).await;
+    assert_eq!(ids(&previews), vec!["alice-photo".to_string()]);
+    assert_eq!(previews[0]["width"], 3024);
+    assert_eq!(previews[0]["height"], 4032);
+    assert_eq!(previews[0]["created_at"], 1);
+    assert_eq!(previews[0]["media_type"], "image/heic");
+    assert!(previews[0]["preview_url"]
+        .as_str()
+        .unwrap()
+        .contains("prev/alice-photo"));
+}
+
+#[tokio::test]
+async fn media_of_another_user_is_forbidden() {
+    let app = TestApp::new();
+    app.add_media(BOB, "bob-photo", 1).await;
+
+    let (status, _) = app.get("/media/bob-photo", ALICE).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app.get("/media/missing", ALICE).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn media_returns_the_metadata_of_the_users_media() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "alice-photo", 42).await;
+
+    let (status, media) = app.get_json("/media/alice-photo", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(media["id"], "alice-photo");
+    assert_eq!(media["created_at"], 42);
+    assert_eq!(media["file_name"], "alice-photo.jpg");
+    assert!(media["utc_offset"].is_null());
+}
+
+#[tokio::test]
+async fn media_returns_the_extended_exif() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "alice-photo", 42).await;
+    app.repository
+        .insert_metadata(
+            "alice-photo".to_string(),
+            MediaMetadata {
+                lens_model: Some("RF 50mm F1.8 STM".to_string()),
+                focal_length: Some(50.0),
+                altitude: Some(-12.5),
+                flash: Some("not fired".to_string()),
+                exif: Some(serde_json::json!({ "LensModel": "RF 50mm F1.8 STM" })),
+                rating: Some(4),
+                keywords: vec!["beach".to_string()],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let (_, media) = app.get_json("/media/alice-photo", ALICE).await;
+    assert_eq!(media["lens_model"], "RF 50mm F1.8 STM");
+    assert_eq!(media["focal_length"], 50.0);
+    assert_eq!(media["altitude"], -12.5);
+    assert_eq!(media["flash"], "not fired");
+    assert!(media["artist"].is_null());
+    assert_eq!(media["exif"]["LensModel"], "RF 50mm F1.8 STM");
+    assert_eq!(media["rating"], 4);
+    assert_eq!(media["keywords"], serde_json::json!(["beach"]));
+    assert_eq!(media["people"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn capture_time_moves_media_in_the_timeline() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "old-photo", 10).await;
+    app.add_media(ALICE, "new-photo", 20).await;
+
+    app.repository
+        .insert_metadata(
+            "new-photo".to_string(),
+            MediaMetadata {
+                capture_time: Some(CaptureTime {
+                    created_at: 5,
+                    utc_offset: Some(60),
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let (_, previews) = app.get_json("/previews", ALICE).await;
+    assert_eq!(ids(&previews), vec!["old-photo", "new-photo"]);
+
+    let (_, media) = app.get_json("/media/new-photo", ALICE).await;
+    assert_eq!(media["created_at"], 5);
+    assert_eq!(media["utc_offset"], 60);
+    assert!(media["uploaded_at"].as_i64().unwrap() > 20);
+}
+
+#[tokio::test]
+async fn similar_media_is_ordered_by_embedding_distance() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "beach", 1).await;
+    app.add_media(ALICE, "sea", 2).await;
+    app.add_media(ALICE, "forest", 3).await;
+    app.add_media(ALICE, "no-embeddings", 4).await;
+    app.add_media(BOB, "bob-beach", 5).await;
+    app.repository
+        .set_clip_embeddings("beach", &[1.0, 0.0, 0.0]);
+    app.repository.set_clip_embeddings("sea", &[0.9, 0.1, 0.0]);
+    app.repository
+        .set_clip_embeddings("forest", &[0.0, 0.0, 1.0]);
+    app.repository
+        .set_clip_embeddings("bob-beach", &[1.0, 0.0, 0.0]);
+
+    let (status, similar) = app.get_json("/media/beach/similar", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&similar), vec!["sea".to_string(), "forest".to_string()]);
+
+    let (_, similar) = app
+        .get_json("/media/beach/similar?page=2&page_size=1", ALICE)
+        .await;
+    assert_eq!(ids(&similar), vec!["forest".to_string()]);
+
+    let (status, similar) = app.get_json("/media/no-embeddings/similar", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(ids(&similar).is_empty());
+}
+
+#[tokio::test]
+async fn similar_media_fills_pages_when_other_users_are_closer() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "beach", 1).await;
+    app.repository.set_clip_embeddings("beach", &[1.0, 0.0]);
+    for i in 0..100 {
+        let media_id = format!("bob-{i}");
+        app.add_media(BOB, &media_id, i).await;
+        app.repository
+            .set_clip_embeddings(&media_id, &[1.0, 0.001 * i as f32]);
+    }
+    app.add_media(ALICE, "coast", 2).await;
+    app.repository.set_clip_embeddings("coast", &[0.6, 0.4]);
+    app.add_media(ALICE, "forest", 3).await;
+    app.repository.set_clip_embeddings("forest", &[0.0, 1.0]);
+
+    let (_, similar) = app
+        .get_json("/media/beach/similar?page_size=2", ALICE)
+        .await;
+    assert_eq!(
+        ids(&similar),
+        vec!["coast".to_string(), "forest".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn similar_media_of_another_user_is_forbidden() {
+    let app = TestApp::new();
+    app.add_media(BOB, "bob-photo", 1).await;
+    app.repository.set_clip_embeddings("bob-photo", &[1.0, 0.0]);
+
+    let (status, _) = app.get("/media/bob-photo/similar", ALICE).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn preview_of_another_user_is_unauthorized() {
+    let app = TestApp::new();
+    app.add_media(BOB, "bob-photo", 1).await;
+    app.repository
+        .update_media_preview(
+            "bob-photo".to_string(),
+            "prev/bob-photo".to_string(),
+            decoded(3024, 4032),
+        )
+        .await
+        .unwrap();
+
+    let (status, _) = app.get("/preview/bob-photo", ALICE).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = app.get("/preview/bob-photo", BOB).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn sync_partial_requires_a_since_header() {
+    let app = TestApp::new();
+    let (status, _) = app.get("/sync/partial", ALICE).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .send(
+            Request::get("/sync/partial")
+                .header(header::AUTHORIZATION, bearer(ALICE))
+                .header("Since", "yesterday")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn sync_partial_returns_media_changed_since() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "alice-photo", 1).await;
+    app.add_media(BOB, "bob-photo", 1).await;
+
+    let (status, body) = app
+        .send(
+            Request::get("/sync/partial")
+                .header(header::AUTHORIZATION, bearer(ALICE))
+                .header("Since", "0")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let changes: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(changes["uploaded"].as_array().unwrap().len(), 1);
+    assert_eq!(changes["uploaded"][0]["id"], "alice-photo");
+    assert!(changes["deleted"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sync_full_only_lists_the_users_media() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "alice-photo", 1).await;
+    app.add_media(BOB, "bob-photo", 1).await;
+
+    let (status, media) = app.get_json("/sync/full", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(media.as_array().unwrap().len(), 1);
+    assert_eq!(media[0]["hash"], "hash-alice-photo");
+}
+
+#[tokio::test]
+async fn placeholders_are_listed_with_previews_and_sync() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "alice-photo", 1).await;
+    app.add_media(ALICE, "pending-photo", 2).await;
+    app.repository
+        .update_media_preview(
+            "alice-photo".to_string(),
+            "prev/alice-photo".to_string(),
+            decoded(3024, 4032),
+        )
+        .await
+        .unwrap();
+
+    let (_, previews) = app.get_json("/previews", ALICE).await;
+    assert_eq!(previews[0]["id"], "pending-photo");
+    assert!(previews[0]["blurhash"].is_null());
+    assert_eq!(previews[0]["dominant_colors"], serde_json::json!([]));
+    assert_eq!(previews[1]["blurhash"], "LEHV6nWB2yk8pyo0adR*.7kCMdnj");
+    assert_eq!(
+        previews[1]["dominant_colors"],
+        serde_json::json!(["#a05030", "#203040"])
+    );
+
+    let (_, media) = app.get_json("/sync/full", ALICE).await;
+    let synced = media
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|media| media["id"] == "alice-photo")
+        .unwrap();
+    assert_eq!(synced["blurhash"], "LEHV6nWB2yk8pyo0adR*.7kCMdnj");
+    assert_eq!(synced["dominant_colors"][0], "#a05030");
+}
+
+#[tokio::test]
+async fn cluster_previews_of_another_user_are_empty() {
+    let app = TestApp::new();
+    app.add_media(BOB, "bob-photo", 1).await;
+    let cluster_id = app.repository.add_cluster(BOB, None);
+    app.repository
+        .add_media_face("bob-photo", vec![0, 0, 10, 10], Some(cluster_id));
+
+    let (status, previews) = app.get_json(&format!("/cluster/{cluster_id}"), ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(ids(&previews).is_empty());
+
+    let (_, previews) = app.get_json(&format!("/cluster/{cluster_id}"), BOB).await;
+    assert_eq!(ids(&previews), vec!["bob-photo".to_string()]);
+}
+
+#[tokio::test]
+async fn sidecar_of_another_user_is_forbidden() {
+    let app = TestApp::new();
+    app.add_media(BOB, "bob-photo", 1).await;
+
+    let (status, _) = app
+        .send(
+            Request::put("/media/bob-photo/sidecar")
+                .header(header::AUTHORIZATION, bearer(ALICE))
+                .body(Body::from("<x:xmpmeta></x:xmpmeta>"))
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn sidecar_must_be_an_xmp_packet() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "alice-photo", 1).await;
+
+    let (status, _) = app
+        .send(
+            Request::put("/media/alice-photo/sidecar")
+                .header(header::AUTHORIZATION, bearer(ALICE))
+                .body(Body::from("rating=5"))
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn bulk_edits_shift_the_capture_time_and_sync() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "first", 1_000).await;
+    app.add_media(ALICE, "second", 2_000).await;
+    app.add_media(ALICE, "untouched", 3_000).await;
+    let before_edit = chrono::Utc::now().timestamp_millis();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+    let (status, _) = app
+        .send_json(
+            "PATCH",
+            "/media",
+            ALICE,
+            serde_json::json!({ "media_ids": ["first", "second"], "shift_hours": -0.5 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, first) = app.get_json("/media/first", ALICE).await;
+    assert_eq!(first["created_at"], 1_000 - 1_800_000);
+    let (_, untouched) = app.get_json("/media/untouched", ALICE).await;
+    assert_eq!(untouched["created_at"], 3_000);
+
+    let (_, body) = app
+        .send(
+            Request::get("/sync/partial")
+                .header(header::AUTHORIZATION, bearer(ALICE))
+                .header("Since", before_edit.to_string())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    let changes: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let mut synced: Vec<&str> = changes["uploaded"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|media| media["id"].as_str().unwrap())
+        .collect();
+    synced.sort();
+    assert_eq!(synced, vec!["first", "second"]);
+}
+
+#[tokio::test]
+async fn edits_win_over_the_metadata_of_the_file() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "photo", 1_000).await;
+
+    let (status, _) = app
+        .send_json(
+            "PATCH",
+            "/media/photo",
+            ALICE,
+            serde_json::json!({ "latitude": 38.7, "longitude": -9.1, "created_at": 5_000, "utc_offset": 60 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    app.repository
+        .insert_metadata(
+            "photo".to_string(),
+            MediaMetadata {
+                latitude: Some(1.0),
+                longitude: Some(2.0),
+                make: Some("Canon".to_string()),
+                capture_time: Some(CaptureTime {
+                    created_at: 9_000,
+                    utc_offset: None,
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let (_, media) = app.get_json("/media/photo", ALICE).await;
+    assert_eq!(media["latitude"], 38.7);
+    assert_eq!(media["longitude"], -9.1);
+    assert_eq!(media["created_at"], 5_000);
+    assert_eq!(media["utc_offset"], 60);
+    assert_eq!(media["make"], "Canon");
+}
+
+#[tokio::test]
+async fn edits_are_all_or_nothing() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "alice-photo", 1_000).await;
+    app.add_media(BOB, "bob-photo", 1_000).await;
+
+    let (status, _) = app
+        .send_json(
+            "PATCH",
+            "/media",
+            ALICE,
+            serde_json::json!({ "media_ids": ["alice-photo", "bob-photo"], "shift_hours": 1 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, media) = app.get_json("/media/alice-photo", ALICE).await;
+    assert_eq!(media["created_at"], 1_000);
+
+    let invalid = [
+        serde_json::json!({ "latitude": 38.7 }),
+        serde_json::json!({ "created_at": 5_000, "shift_hours": 1 }),
+        serde_json::json!({ "utc_offset": 900 }),
+        serde_json::json!({ "rating": 6 }),
+        serde_json::json!({ "shift_hours": 24.0 * 366.0 * 100.0 + 1.0 }),
+        serde_json::json!({ "shift_hours": -1e300 }),
+        serde_json::json!({}),
+    ];
+    for edit in invalid {
+        let (status, _) = app
+            .send_json("PATCH", "/media/alice-photo", ALICE, edit)
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let media_ids: Vec<String> = (0..10001).map(|i| format!("photo-{i}")).collect();
+    let (status, _) = app
+        .send_json(
+            "PATCH",
+            "/media",
+            ALICE,
+            serde_json::json!({ "media_ids": media_ids, "shift_hours": 1 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn edited_annotations_win_over_the_metadata_of_the_file() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "photo", 1_000).await;
+
+    let (status, _) = app
+        .send_json(
+            "PATCH",
+            "/media/photo",
+            ALICE,
+            serde_json::json!({ "title": "Lisbon", "rating": 4 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    app.repository
+        .insert_metadata(
+            "photo".to_string(),
+            MediaMetadata {
+                title: Some("IMG_0001".to_string()),
+                rating: Some(1),
+                keywords: vec!["tram".to_string()],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let (_, media) = app.get_json("/media/photo", ALICE).await;
+    assert_eq!(media["title"], "Lisbon");
+    assert_eq!(media["rating"], 4);
+    assert_eq!(media["keywords"], serde_json::json!(["tram"]));
+}
+
+#[tokio::test]
+async fn export_of_another_user_is_forbidden() {
+    let app = TestApp::new();
+    app.add_media(BOB, "bob-photo", 1).await;
+
+    let (status, _) = app.get("/media/bob-photo/export", ALICE).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+fn rendition(media_id: &str, name: &str, extension: &str, height: i32) -> Rendition {
+    let content_type = match extension {
+        "jpg" => "image/jpeg".to_string(),
+        extension => format!("image/{extension}"),
+    };
+    Rendition {
+        name: name.to_string(),
+        content_type,
+        width: height * 4 / 3,
+        height,
+        object_id: format!("rend/{media_id}/{name}.{extension}"),
+    }
+}
+
+#[tokio::test]
+async fn previews_return_the_requested_rendition() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "photo", 1).await;
+    app.repository
+        .set_renditions(
+            "photo".to_string(),
+            vec![
+                rendition("photo", "grid", "avif", 256),
+                rendition("photo", "grid", "jpg", 256),
+                rendition("photo", "display", "avif", 1440),
+                rendition("photo", "display", "jpg", 1440),
+                rendition("photo", "full", "jpg", 3000),
+            ],
+        )
+        .await
+        .unwrap();
+    app.repository
+        .update_media_preview(
+            "photo".to_string(),
+            "rend/photo/grid.jpg".to_string(),
+            decoded(4000, 3000),
+        )
+        .await
+        .unwrap();
+
+    let requests = [
+        ("/previews", "rend/photo/grid.jpg"),
+        ("/previews?size=grid&format=avif", "rend/photo/grid.avif"),
+        ("/previews?size=display", "rend/photo/display.jpg"),
+        ("/previews?size=1000&format=avif", "rend/photo/display.avif"),
+        ("/previews?size=4000&format=webp", "rend/photo/display.jpg"),
+        ("/previews?size=2000", "rend/photo/display.jpg"),
+        ("/previews?size=missing", "rend/photo/grid.jpg"),
+    ];
+    for (uri, object_id) in requests {
+        let (_, previews) = app.get_json(uri, ALICE).await;
+        let url = previews[0]["preview_url"].as_str().unwrap();
+        assert!(url.contains(object_id), "{uri} returned {url}");
+    }
+
+    let (status, url) = app
+        .get("/preview/photo?size=display&format=avif", ALICE)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(String::from_utf8(url)
+        .unwrap()
+        .contains("rend/photo/display.avif"));
+}
+
+#[tokio::test]
+async fn heic_media_are_displayed_from_their_full_resolution_copy() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "heic", 1).await;
+    app.add_media(ALICE, "jpeg", 2).await;
+
+    let (_, media) = app.get_json("/media/jpeg", ALICE).await;
+    assert_eq!(media["display_url"], media["media_url"]);
+
+    // The copy is made with the renditions, until then there is nothing to display
+    app.repository
+        .update_media_preview(
+            "heic".to_string(),
+            "rend/heic/grid.jpg".to_string(),
+            decoded(4032, 3024),
+        )
+        .await
+        .unwrap();
+    let (_, media) = app.get_json("/media/heic", ALICE).await;
+    assert_eq!(media["display_url"], serde_json::Value::Null);
+
+    app.repository
+        .set_renditions(
+            "heic".to_string(),
+            vec![
+                rendition("heic", "grid", "jpg", 256),
+                rendition("heic", "full", "jpg", 3024),
+            ],
+        )
+        .await
+        .unwrap();
+    let (_, media) = app.get_json("/media/heic", ALICE).await;
+    assert!(media["display_url"]
+        .as_str()
+        .unwrap()
+        .contains("rend/heic/full.jpg"));
+    assert!(!media["media_url"].as_str().unwrap().contains("rend/"));
+}
+
+#[tokio::test]
+async fn resized_images_check_the_media_and_the_query() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "photo", 1).await;
+    app.add_media(BOB, "bob-photo", 1).await;
+
+    let (status, _) = app.get("/image/bob-photo?w=640", ALICE).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let invalid = [
+        "/image/photo",
+        "/image/photo?w=0",
+        "/image/photo?w=5000",
+        "/image/photo?w=640&fit=stretch",
+        "/image/photo?w=640&format=gif",
+        "/image/photo?w=640&fit=cover",
+    ];
+    for uri in invalid {
+        let (status, _) = app.get(uri, ALICE).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+
+    // HEIC is resized from its display copy, which the preview worker has not made yet
+    app.repository
+        .update_media_preview(
+            "photo".to_string(),
+            "rend/photo/grid.jpg".to_string(),
+            decoded(4032, 3024),
+        )
+        .await
+        .unwrap();
+    let (status, _) = app.get("/image/photo?w=640&h=640&fit=face", ALICE).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
@@ -0,0 +1,481 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{TestApp, ALICE, BOB};
+use database::{FaceRepository, MediaMetadata, MediaRepository};
+use futures_util::StreamExt;
+use messaging::MessageBus;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+fn ids(previews: &Value) -> Vec<String> {
+    previews
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|preview| preview["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+// Stands in for the clip search service, answering every query with the same ranking.
+// Returns the text of the queries it received
+async fn serve_search(app: &TestApp, ranking: &[&str]) -> Arc<Mutex<Vec<String>>> {
+    let queries = Arc::new(Mutex::new(Vec::new()));
+    let received = queries.clone();
+    let ranking: Vec<String> = ranking.iter().map(ToString::to_string).collect();
+    let mut requests = app
+        .bus
+        .subscribe("clip-process-search", "search_consumer")
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        while let Some(Ok(request)) = requests.next().await {
+            let params: Value = serde_json::from_slice(&request.payload).unwrap();
+            received
+                .lock()
+                .unwrap()
+                .push(params["query"].as_str().unwrap().to_string());
+            let page = params["page"].as_u64().unwrap() as usize;
+            let page_size = params["page_size"].as_u64().unwrap() as usize;
+            // Scores go down by a tenth for every rank
+            let items: Vec<Value> = ranking
+                .iter()
+                .enumerate()
+                .skip((page - 1) * page_size)
+                .take(page_size)
+                .map(|(rank, id)| {
+                    json!({
+                        "id": id,
+                        "preview_url": format!("http://previews/{id}"),
+                        "score": 0.9 - rank as f32 / 10.0,
+                    })
+                })
+                .collect();
+            request
+                .respond(json!(items).to_string().into())
+                .await
+                .unwrap();
+        }
+    });
+    queries
+}
+
+async fn set_metadata(
+    app: &TestApp,
+    media_id: &str,
+    make: &str,
+    model: Option<&str>,
+    coordinates: Option<(f64, f64)>,
+    iso: Option<&str>,
+) {
+    app.repository
+        .insert_metadata(
+            media_id.to_string(),
+            MediaMetadata {
+                longitude: coordinates.map(|(_, longitude)| longitude),
+                latitude: coordinates.map(|(latitude, _)| latitude),
+                make: Some(make.to_string()),
+                model: model.map(ToString::to_string),
+                photographic_sensitivity: iso.map(ToString::to_string),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+}
+
+async fn set_camera(app: &TestApp, media_id: &str, make: &str, gps: bool) {
+    set_metadata(app, media_id, make, None, gps.then_some((38.7, -9.1)), None).await;
+}
+
+#[tokio::test]
+async fn search_pages_are_cut_from_one_cached_search() {
+    let app = TestApp::new();
+    for (created_at, media_id) in ["a", "b", "c", "d", "e"].into_iter().enumerate() {
+        app.add_media(ALICE, media_id, created_at as i64).await;
+    }
+    let queries = serve_search(&app, &["a", "b", "c", "d", "e"]).await;
+
+    let (status, results) = app
+        .get_json("/search?query=beach&page=2&page_size=2", ALICE)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&results), vec!["c".to_string(), "d".to_string()]);
+    // Results are previews like those of the timeline
+    assert_eq!(results[0]["created_at"], 2);
+    assert!(results[0]["dominant_colors"].is_array());
+
+    let (_, results) = app
+        .get_json("/search?query=beach&page=3&page_size=2", ALICE)
+        .await;
+    assert_eq!(ids(&results), vec!["e".to_string()]);
+    assert_eq!(queries.lock().unwrap().len(), 1);
+
+    // Other users and queries are not served from the same results, nor the media of others
+    let (_, results) = app.get_json("/search?query=beach", BOB).await;
+    assert!(ids(&results).is_empty());
+    app.get_json("/search?query=sea", ALICE).await;
+    assert_eq!(queries.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn search_returns_scores_above_the_threshold() {
+    let app = TestApp::new();
+    for media_id in ["a", "b", "c", "d"] {
+        app.add_media(ALICE, media_id, 1).await;
+    }
+    serve_search(&app, &["a", "b", "c", "d"]).await;
+
+    let (status, results) = app.get_json("/search?query=beach", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    let score = results[1]["score"].as_f64().unwrap();
+    assert!((score - 0.8).abs() < 1e-6);
+
+    let (_, results) = app
+        .get_json("/search?query=beach&threshold=0.75", ALICE)
+        .await;
+    assert_eq!(ids(&results), vec!["a".to_string(), "b".to_string()]);
+}
+
+#[tokio::test]
+async fn search_times_out_when_the_service_does_not_answer() {
+    let app = TestApp::new();
+    let mut requests = app
+        .bus
+        .subscribe("clip-process-search", "search_consumer")
+        .await
+        .unwrap();
+    // Holds on to the requests without ever answering them
+    tokio::spawn(async move {
+        let mut pending = Vec::new();
+        while let Some(request) = requests.next().await {
+            pending.push(request);
+        }
+    });
+
+    let (status, _) = app.get("/search?query=beach", ALICE).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[tokio::test]
+async fn search_filters_keep_the_clip_ranking() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "old-beach", 1_000).await;
+    app.add_media(ALICE, "new-beach", 5_000).await;
+    app.add_media(ALICE, "new-sea", 6_000).await;
+    app.add_media(ALICE, "newest-sea", 9_000).await;
+    serve_search(&app, &["newest-sea", "old-beach", "new-sea", "new-beach"]).await;
+
+    let (status, results) = app
+        .get_json("/search?query=beach&after=2000&before=9000", ALICE)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        ids(&results),
+        vec!["new-sea".to_string(), "new-beach".to_string()]
+    );
+
+    let (_, results) = app
+        .get_json("/search?query=beach&after=2000&page=2&page_size=2", ALICE)
+        .await;
+    assert_eq!(ids(&results), vec!["new-beach".to_string()]);
+}
+
+#[tokio::test]
+async fn search_filters_by_camera_and_location() {
+    let app = TestApp::new();
+    for media_id in ["phone-gps", "phone", "camera-gps"] {
+        app.add_media(ALICE, media_id, 1).await;
+    }
+    set_camera(&app, "phone-gps", "Apple", true).await;
+    set_camera(&app, "phone", "Apple", false).await;
+    set_camera(&app, "camera-gps", "Canon", true).await;
+    serve_search(&app, &["phone-gps", "phone", "camera-gps"]).await;
+
+    let (_, results) = app.get_json("/search?query=x&make=apple", ALICE).await;
+    assert_eq!(
+        ids(&results),
+        vec!["phone-gps".to_string(), "phone".to_string()]
+    );
+
+    let (_, results) = app
+        .get_json("/search?query=x&make=Apple&has_gps=true", ALICE)
+        .await;
+    assert_eq!(ids(&results), vec!["phone-gps".to_string()]);
+
+    let (_, results) = app.get_json("/search?query=x&has_gps=false", ALICE).await;
+    assert_eq!(ids(&results), vec!["phone".to_string()]);
+}
+
+#[tokio::test]
+async fn search_filters_by_person() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "with-bob", 1).await;
+    app.add_media(ALICE, "alone", 2).await;
+    let cluster_id = app.repository.add_cluster(ALICE, None);
+    app.repository
+        .add_media_face("with-bob", vec![0, 0, 10, 10], Some(cluster_id));
+    serve_search(&app, &["alone", "with-bob"]).await;
+
+    let (_, results) = app
+        .get_json(&format!("/search?query=x&cluster_id={cluster_id}"), ALICE)
+        .await;
+    assert_eq!(ids(&results), vec!["with-bob".to_string()]);
+}
+
+#[tokio::test]
+async fn search_filters_drop_media_of_other_users() {
+    let app = TestApp::new();
+    app.add_media(BOB, "bob-photo", 5).await;
+    serve_search(&app, &["bob-photo"]).await;
+
+    let (_, results) = app.get_json("/search?query=x&after=1", ALICE).await;
+    assert!(ids(&results).is_empty());
+}
+
+#[tokio::test]
+async fn search_rejects_an_empty_date_range() {
+    let app = TestApp::new();
+    let (status, _) = app.get("/search?query=x&after=10&before=10", ALICE).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn search_query_terms_become_filters() {
+    let app = TestApp::new();
+    for media_id in ["iphone-high-iso", "iphone-low-iso", "pixel-high-iso"] {
+        app.add_media(ALICE, media_id, 1).await;
+    }
+    set_metadata(
+        &app,
+        "iphone-high-iso",
+        "Apple",
+        Some("iPhone 15"),
+        None,
+        Some("1600"),
+    )
+    .await;
+    set_metadata(
+        &app,
+        "iphone-low-iso",
+        "Apple",
+        Some("iPhone 15"),
+        None,
+        Some("100"),
+    )
+    .await;
+    set_metadata(
+        &app,
+        "pixel-high-iso",
+        "Google",
+        Some("Pixel 8"),
+        None,
+        Some("3200"),
+    )
+    .await;
+    let queries = serve_search(
+        &app,
+        &["pixel-high-iso", "iphone-low-iso", "iphone-high-iso"],
+    )
+    .await;
+
+    let (status, results) = app
+        .get_json(
+            "/search?query=camera%3A%22iphone%2015%22%20iso%3E800%20beach%20sunset",
+            ALICE,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&results), vec!["iphone-high-iso".to_string()]);
+    assert_eq!(*queries.lock().unwrap(), vec!["beach sunset".to_string()]);
+
+    let (_, results) = app
+        .get_json("/search?query=beach%20iso%3C%3D100", ALICE)
+        .await;
+    assert_eq!(ids(&results), vec!["iphone-low-iso".to_string()]);
+
+    let (status, results) = app
+        .get_json("/search?query=beach%20ISO%3E800%20Camera%3Aiphone", ALICE)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&results), vec!["iphone-high-iso".to_string()]);
+}
+
+#[tokio::test]
+async fn search_query_dates_cover_whole_periods() {
+    let app = TestApp::new();
+    // 2023-05-31, 2023-06-01 and 2023-08-31 at noon UTC
+    app.add_media(ALICE, "may", 1_685_534_400_000).await;
+    app.add_media(ALICE, "june", 1_685_620_800_000).await;
+    app.add_media(ALICE, "august", 1_693_483_200_000).await;
+    serve_search(&app, &["may", "june", "august"]).await;
+
+    let (_, results) = app
+        .get_json(
+            "/search?query=after%3A2023-06%20before%3A2023-09%20beach",
+            ALICE,
+        )
+        .await;
+    assert_eq!(
+        ids(&results),
+        vec!["june".to_string(), "august".to_string()]
+    );
+
+    let (_, results) = app
+        .get_json("/search?query=beach%20before%3A2023-06-01", ALICE)
+        .await;
+    assert_eq!(ids(&results), vec!["may".to_string()]);
+}
+
+#[tokio::test]
+async fn search_query_filters_by_person_name_and_location() {
+    let app = TestApp::new();
+    app.add_media(ALICE, "lisbon-with-carol", 1).await;
+    app.add_media(ALICE, "porto-with-carol", 2).await;
+    app.add_media(ALICE, "lisbon-alone", 3).await;
+    set_metadata(
+        &app,
+        "lisbon-with-carol",
+        "Apple",
+        None,
+        Some((38.72, -9.14)),
+        None,
+    )
+    .await;
+    set_metadata(
+        &app,
+        "porto-with-carol",
+        "Apple",
+        None,
+        Some((41.15, -8.61)),
+        None,
+    )
+    .await;
+    set_metadata(
+        &app,
+        "lisbon-alone",
+        "Apple",
+        None,
+        Some((38.71, -9.13)),
+        None,
+    )
+    .await;
+    let cluster_id = app.repository.add_cluster(ALICE, None);
+    for media_id in ["lisbon-with-carol", "porto-with-carol"] {
+        app.repository
+            .add_media_face(media_id, vec![0, 0, 10, 10], Some(cluster_id));
+    }
+    app.repository
+        .insert_face(ALICE.to_string(), vec![cluster_id], "Carol".to_string())
+        .await
+        .unwrap();
+    serve_search(
+        &app,
+        &["lisbon-alone", "porto-with-carol", "lisbon-with-carol"],
+    )
+    .await;
+
+    let (_, results) = app
+        .get_json("/search?query=person%3Acarol%20x", ALICE)
+        .await;
+    assert_eq!(
+        ids(&results),
+        vec![
+            "porto-with-carol".to_string(),
+            "lisbon-with-carol".to_string()
+        ]
+    );
+
+    let (_, results) = app
+        .get_json(
+            "/search?query=person%3ACarol%20near%3A38.72%2C-9.14%20x",
+            ALICE,
+        )
+        .await;
+    assert_eq!(ids(&results), vec!["lisbon-with-carol".to_string()]);
+}
+
+#[tokio::test]
+async fn search_query_keeps_text_that_is_not_a_field() {
+    let app = TestApp::new();
+    let queries = serve_search(&app, &[]).await;
+
+    let (status, _) = app
+        .get_json("/search?query=sunset%2010%3A30%20%22a%3Ab%22", ALICE)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        *queries.lock().unwrap(),
+        vec!["sunset 10:30 a:b".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn search_query_errors_point_at_the_term() {
+    let app = TestApp::new();
+    for (query, term) in [
+        ("beach%20color%3Ared", "color:red"),
+        ("beach%20near%3ALisbon", "near:Lisbon"),
+        ("beach%20after%3A2023-13", "after:2023-13"),
+        ("beach%20iso%3Ehigh", "iso>high"),
+        ("beach%20camera%3E5", "camera>5"),
+        ("beach%20camera%3A%22iPhone", "camera:iPhone"),
+    ] {
+        let (status, error) = app.get_json(&format!("/search?query={query}"), ALICE).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+        assert_eq!(error["term"], term, "{query}");
+        assert!(error["error"].is_string());
+    }
+
+    let (_, error) = app
+        .get_json("/search?query=beach%20near%3ALisbon", ALICE)
+        .await;
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Place names are not supported"));
+}
+
+#[tokio::test]
+async fn search_query_requires_text_or_filters() {
+    let app = TestApp::new();
+    let (status, _) = app.get("/search?query=%20", ALICE).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.get("/search", ALICE).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn search_query_with_only_filters_lists_matching_media() {
+    let app = TestApp::new();
+    for (media_id, created_at) in [("old-x100v", 1), ("new-x100v", 2), ("pixel", 3)] {
+        app.add_media(ALICE, media_id, created_at).await;
+    }
+    app.add_media(BOB, "bob-x100v", 4).await;
+    set_metadata(&app, "old-x100v", "FUJIFILM", Some("X100V"), None, None).await;
+    set_metadata(&app, "new-x100v", "FUJIFILM", Some("X100V"), None, None).await;
+    set_metadata(&app, "pixel", "Google", Some("Pixel 8"), None, None).await;
+    set_metadata(&app, "bob-x100v", "FUJIFILM", Some("X100V"), None, None).await;
+    // Nothing answers on the search subject, clip is not asked
+
+    let (status, results) = app.get_json("/search?query=camera%3Ax100v", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        ids(&results),
+        vec!["new-x100v".to_string(), "old-x100v".to_string()]
+    );
+    assert!(results[0]["score"].is_null());
+
+    let (_, results) = app
+        .get_json("/search?query=camera%3Ax100v&page=2&page_size=1", ALICE)
+        .await;
+    assert_eq!(ids(&results), vec!["old-x100v".to_string()]);
+
+    // Filters can be given without a query at all
+    let (status, results) = app.get_json("/search?model=X100V", ALICE).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        ids(&results),
+        vec!["new-x100v".to_string(), "old-x100v".to_string()]
+    );
+}
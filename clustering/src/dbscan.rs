@@ -0,0 +1,58 @@
+use database::vector::cosine_distance;
+
+// Density based clustering over cosine distances.
+// Returns the cluster of every point, numbered from 0, or None for noise.
+// Points are visited in order, so the same input always gives the same clusters
+pub fn dbscan(points: &[Vec<f32>], max_distance: f32, min_points: usize) -> Vec<Option<usize>> {
+    let neighbours = |index: usize| -> Vec<usize> {
+        (0..points.len())
+            .filter(|&other| cosine_distance(&points[index], &points[other]) <= max_distance)
+            .collect()
+    };
+
+    let mut labels: Vec<Option<usize>> = vec![None; points.len()];
+    let mut visited = vec![false; points.len()];
+    let mut clusters = 0;
+
+    for index in 0..points.len() {
+        if visited[index] {
+            continue;
+        }
+        visited[index] = true;
+
+        // A point counts as its own neighbour
+        let seeds = neighbours(index);
+        if seeds.len() < min_points {
+            continue;
+        }
+
+        let cluster = clusters;
+        clusters += 1;
+        labels[index] = Some(cluster);
+
+        // Points are labelled when they are queued, so each one is queued at most once
+        let mut queue = Vec::new();
+        let mut enqueue = |points: Vec<usize>, queue: &mut Vec<usize>| {
+            for point in points {
+                if labels[point].is_none() {
+                    labels[point] = Some(cluster);
+                    queue.push(point);
+                }
+            }
+        };
+        enqueue(seeds, &mut queue);
+        while let Some(other) = queue.pop() {
+            // Noise seen earlier becomes a border point of the cluster without being expanded
+            if visited[other] {
+                continue;
+            }
+            visited[other] = true;
+
+            let other_neighbours = neighbours(other);
+            if other_neighbours.len() >= min_points {
+                enqueue(other_neighbours, &mut queue);
+            }
+        }
+    }
+    labels
+}
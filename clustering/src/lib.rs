@@ -0,0 +1,142 @@
+mod dbscan;
+mod matching;
+mod plan;
+use database::{ClusterAssignment, DbErr, Repository};
+use futures_util::StreamExt;
+use log::{error, info};
+use messaging::{BusError, MessageBus};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+pub use dbscan::dbscan;
+pub use matching::{match_faces, FaceMatches};
+pub use plan::plan;
+
+#[derive(Debug, Clone)]
+pub struct ClusteringConfig {
+    // Cosine distance under which two faces are considered neighbours
+    pub max_distance: f32,
+    // Neighbours a face needs, itself included, to start a cluster
+    pub min_faces: usize,
+    // Distance to the centroid of a named face under which a detection joins the face
+    pub auto_assign_distance: f32,
+    // Distance under which the user is asked whether the detection is the face
+    pub suggestion_distance: f32,
+    // How often users with new detections are looked for
+    pub interval: Duration,
+}
+
+impl Default for ClusteringConfig {
+    fn default() -> Self {
+        ClusteringConfig {
+            max_distance: 0.35,
+            min_faces: 3,
+            auto_assign_distance: 0.3,
+            suggestion_distance: 0.45,
+            interval: Duration::from_secs(300),
+        }
+    }
+}
+
+// Attaches detections to the named faces they match, reclusters the rest
+// and stores the result along with new suggestions
+pub async fn cluster_user(
+    db: &dyn Repository,
+    user_id: &str,
+    config: &ClusteringConfig,
+) -> Result<usize, DbErr> {
+    let mut detections = db.get_face_embeddings(user_id.to_string()).await?;
+    let rejected: HashSet<(i32, i32)> = db
+        .get_rejected_suggestions(user_id.to_string())
+        .await?
+        .into_iter()
+        .collect();
+
+    // Matching and DBSCAN are quadratic in the detections of the user, they run off the runtime
+    let config = config.clone();
+    let (assignments, clustered, suggestions) = tokio::task::spawn_blocking(move || {
+        let matches = match_faces(&detections, &rejected, &config);
+        // Attached detections count as named, so the clustering leaves them where they are
+        for (media_face_id, assignment) in &matches.assignments {
+            if let ClusterAssignment::Cluster(cluster_id) = assignment {
+                let (face_id, hidden) = detections
+                    .iter()
+                    .find(|detection| detection.cluster_id == Some(*cluster_id))
+                    .map(|detection| (detection.face_id, detection.hidden))
+                    .unwrap_or_default();
+                if let Some(detection) = detections
+                    .iter_mut()
+                    .find(|detection| detection.media_face_id == *media_face_id)
+                {
+                    detection.cluster_id = Some(*cluster_id);
+                    detection.face_id = face_id;
+                    detection.hidden = hidden;
+                }
+            }
+        }
+
+        let mut assignments = plan(&detections, &config);
+        assignments.extend(matches.assignments);
+        assignments.sort_by_key(|(media_face_id, _)| *media_face_id);
+        // Noise stays out of any cluster, it is only looked at again with new detections
+        let clustered = detections
+            .iter()
+            .map(|detection| detection.media_face_id)
+            .collect();
+        (assignments, clustered, matches.suggestions)
+    })
+    .await
+    .map_err(|err| DbErr::Custom(format!("Clustering task failed: {err}")))?;
+    let moved = assignments.len();
+    db.save_clusters(user_id.to_string(), assignments, clustered)
+        .await?;
+    db.save_face_suggestions(user_id.to_string(), suggestions)
+        .await?;
+    Ok(moved)
+}
+
+// Reclusters the users that have detections the clustering has not seen yet
+pub async fn cluster_pending(db: &dyn Repository, config: &ClusteringConfig) -> Result<(), DbErr> {
+    for user_id in db.get_users_with_unclustered_faces().await? {
+        let moved = cluster_user(db, &user_id, config).await?;
+        info!("Clustered the faces of {user_id}, {moved} detections moved");
+    }
+    Ok(())
+}
+
+// Clusters on an interval and whenever a user id is published on the clustering subject,
+// e.g. by the face detection once it has stored new detections
+pub async fn run(
+    bus: Arc<dyn MessageBus>,
+    db: Arc<dyn Repository>,
+    config: ClusteringConfig,
+) -> Result<(), BusError> {
+    let mut requests = bus.subscribe("clustering", "clustering_consumer").await?;
+    let mut interval = tokio::time::interval(config.interval);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(err) = cluster_pending(&*db, &config).await {
+                    error!("Error clustering faces: {err}");
+                }
+            }
+            request = requests.next() => match request {
+                Some(Ok(msg)) => {
+                    let user_id = String::from_utf8_lossy(&msg.payload).to_string();
+                    match cluster_user(&*db, &user_id, &config).await {
+                        Ok(moved) => {
+                            info!("Clustered the faces of {user_id}, {moved} detections moved");
+                            let _ = msg.ack().await;
+                        }
+                        Err(err) => {
+                            error!("Error clustering the faces of {user_id}: {err}");
+                            let _ = msg.term().await;
+                        }
+                    }
+                }
+                Some(Err(err)) => error!("Error receiving message: {err}"),
+                None => return Ok(()),
+            }
+        }
+    }
+}
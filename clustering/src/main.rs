@@ -0,0 +1,68 @@
+use clustering::{run, ClusteringConfig};
+use database::DbManager;
+use messaging::NatsBus;
+use serde::Deserialize;
+use std::{error::Error, sync::Arc, time::Duration};
+
+#[derive(Deserialize, Debug)]
+pub struct EnvVars {
+    #[serde(alias = "NATS_ENDPOINT")]
+    #[serde(default = "nats_endpoint_default")]
+    pub nats_endpoint: String,
+    #[serde(alias = "CLUSTERING_MAX_DISTANCE")]
+    pub clustering_max_distance: Option<f32>,
+    #[serde(alias = "CLUSTERING_MIN_FACES")]
+    pub clustering_min_faces: Option<usize>,
+    #[serde(alias = "CLUSTERING_AUTO_ASSIGN_DISTANCE")]
+    pub clustering_auto_assign_distance: Option<f32>,
+    #[serde(alias = "CLUSTERING_SUGGESTION_DISTANCE")]
+    pub clustering_suggestion_distance: Option<f32>,
+    #[serde(alias = "CLUSTERING_INTERVAL_SECONDS")]
+    pub clustering_interval_seconds: Option<u64>,
+}
+
+fn nats_endpoint_default() -> String {
+    "http://localhost".to_string()
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    dotenvy::dotenv().ok();
+    let envs = match envy::from_env::<EnvVars>() {
+        Ok(vars) => vars,
+        Err(err) => panic!("{}", err),
+    };
+
+    let db = match DbManager::new().await {
+        Ok(database) => database,
+        Err(err) => panic!("{}", err),
+    };
+
+    let bus = match NatsBus::connect(envs.nats_endpoint).await {
+        Ok(bus) => bus,
+        Err(err) => {
+            panic!("Couldn't connect nats client: {err}");
+        }
+    };
+
+    let defaults = ClusteringConfig::default();
+    let config = ClusteringConfig {
+        max_distance: envs
+            .clustering_max_distance
+            .unwrap_or(defaults.max_distance),
+        min_faces: envs.clustering_min_faces.unwrap_or(defaults.min_faces),
+        auto_assign_distance: envs
+            .clustering_auto_assign_distance
+            .unwrap_or(defaults.auto_assign_distance),
+        suggestion_distance: envs
+            .clustering_suggestion_distance
+            .unwrap_or(defaults.suggestion_distance),
+        interval: envs
+            .clustering_interval_seconds
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.interval),
+    };
+
+    run(Arc::new(bus), Arc::new(db), config).await?;
+    Ok(())
+}
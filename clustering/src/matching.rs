@@ -0,0 +1,95 @@
+use std::collections::{BTreeMap, HashSet};
+
+use database::{vector::cosine_distance, ClusterAssignment, FaceEmbedding, NewFaceSuggestion};
+
+use crate::ClusteringConfig;
+
+#[derive(Debug, Default)]
+pub struct FaceMatches {
+    // Detections close enough to a named face to be attached to it
+    pub assignments: Vec<(i32, ClusterAssignment)>,
+    // Detections the user is asked about
+    pub suggestions: Vec<NewFaceSuggestion>,
+}
+
+struct KnownFace {
+    face_id: i32,
+    // Detections are attached to the oldest cluster of the face
+    cluster_id: i32,
+    centroid: Vec<f32>,
+    // New detections still join hidden faces, but the user isn't asked about them
+    hidden: bool,
+}
+
+// Compares every detection outside of a named face or a hidden cluster with the centroids of the named faces.
+// Pairs in `rejected` (media_face_id, face_id) were turned down by the user and are skipped
+pub fn match_faces(
+    detections: &[FaceEmbedding],
+    rejected: &HashSet<(i32, i32)>,
+    config: &ClusteringConfig,
+) -> FaceMatches {
+    let known_faces = known_faces(detections);
+    let mut matches = FaceMatches::default();
+
+    for detection in detections
+        .iter()
+        .filter(|d| d.face_id.is_none() && !d.hidden)
+    {
+        let closest = known_faces
+            .iter()
+            .filter(|face| !rejected.contains(&(detection.media_face_id, face.face_id)))
+            .map(|face| (face, cosine_distance(&detection.embedding, &face.centroid)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let Some((face, distance)) = closest else {
+            continue;
+        };
+
+        if distance <= config.auto_assign_distance {
+            matches.assignments.push((
+                detection.media_face_id,
+                ClusterAssignment::Cluster(face.cluster_id),
+            ));
+        } else if distance <= config.suggestion_distance && !face.hidden {
+            matches.suggestions.push(NewFaceSuggestion {
+                media_face_id: detection.media_face_id,
+                face_id: face.face_id,
+                distance,
+            });
+        }
+    }
+    matches
+}
+
+fn known_faces(detections: &[FaceEmbedding]) -> Vec<KnownFace> {
+    let mut faces: BTreeMap<i32, Vec<&FaceEmbedding>> = BTreeMap::new();
+    for detection in detections {
+        if let Some(face_id) = detection.face_id {
+            faces.entry(face_id).or_default().push(detection);
+        }
+    }
+
+    faces
+        .into_iter()
+        .filter_map(|(face_id, detections)| {
+            let cluster_id = detections.iter().filter_map(|d| d.cluster_id).min()?;
+            let hidden = detections.iter().all(|d| d.hidden);
+            let dimensions = detections[0].embedding.len();
+            let mut centroid = vec![0.0; dimensions];
+            for detection in detections
+                .iter()
+                .filter(|d| d.embedding.len() == dimensions)
+            {
+                for (sum, value) in centroid.iter_mut().zip(&detection.embedding) {
+                    *sum += value;
+                }
+            }
+            // Only the direction matters for the cosine distance, so the sum is as good as the mean
+            Some(KnownFace {
+                face_id,
+                cluster_id,
+                centroid,
+                hidden,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,94 @@
+use std::collections::{BTreeMap, HashSet};
+
+use database::{ClusterAssignment, FaceEmbedding};
+
+use crate::{dbscan::dbscan, ClusteringConfig};
+
+// Decides where every detection of a user goes, returning only the detections that move.
+// Detections in clusters named or hidden by the user never move, the others join the named
+// or hidden cluster most of their group is in, so hidden strangers stay hidden. Groups without
+// one keep the unnamed cluster most of them already are in, so cluster ids stay stable between runs
+pub fn plan(
+    detections: &[FaceEmbedding],
+    config: &ClusteringConfig,
+) -> Vec<(i32, ClusterAssignment)> {
+    let embeddings: Vec<Vec<f32>> = detections
+        .iter()
+        .map(|detection| detection.embedding.clone())
+        .collect();
+    let labels = dbscan(&embeddings, config.max_distance, config.min_faces);
+
+    let mut groups: BTreeMap<usize, Vec<&FaceEmbedding>> = BTreeMap::new();
+    let mut noise = Vec::new();
+    for (detection, label) in detections.iter().zip(labels) {
+        match label {
+            Some(label) => groups.entry(label).or_default().push(detection),
+            None => noise.push(detection),
+        }
+    }
+
+    // Bigger groups pick their existing cluster first
+    let mut groups: Vec<Vec<&FaceEmbedding>> = groups.into_values().collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+
+    let mut claimed_clusters = HashSet::new();
+    let mut assignments = Vec::new();
+    for (number, group) in groups.iter().enumerate() {
+        let named = most_common_cluster(group.iter().filter(|d| is_fixed(d)), |_| true);
+        let target = match named {
+            Some(cluster_id) => ClusterAssignment::Cluster(cluster_id),
+            None => match most_common_cluster(group.iter(), |cluster_id| {
+                !claimed_clusters.contains(&cluster_id)
+            }) {
+                Some(cluster_id) => {
+                    claimed_clusters.insert(cluster_id);
+                    ClusterAssignment::Cluster(cluster_id)
+                }
+                None => ClusterAssignment::NewCluster(number),
+            },
+        };
+
+        for detection in group.iter().filter(|d| !is_fixed(d)) {
+            if target != current(detection) {
+                assignments.push((detection.media_face_id, target));
+            }
+        }
+    }
+
+    for detection in noise.iter().filter(|d| !is_fixed(d)) {
+        if current(detection) != ClusterAssignment::Unclustered {
+            assignments.push((detection.media_face_id, ClusterAssignment::Unclustered));
+        }
+    }
+
+    assignments.sort_by_key(|(media_face_id, _)| *media_face_id);
+    assignments
+}
+
+fn is_fixed(detection: &FaceEmbedding) -> bool {
+    detection.face_id.is_some() || detection.hidden
+}
+
+fn current(detection: &FaceEmbedding) -> ClusterAssignment {
+    match detection.cluster_id {
+        Some(cluster_id) => ClusterAssignment::Cluster(cluster_id),
+        None => ClusterAssignment::Unclustered,
+    }
+}
+
+// Ties go to the oldest cluster
+fn most_common_cluster<'a>(
+    detections: impl Iterator<Item = &'a &'a FaceEmbedding>,
+    allowed: impl Fn(i32) -> bool,
+) -> Option<i32> {
+    let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+    for cluster_id in detections.filter_map(|detection| detection.cluster_id) {
+        if allowed(cluster_id) {
+            *counts.entry(cluster_id).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(cluster_id, count)| (*count, std::cmp::Reverse(*cluster_id)))
+        .map(|(cluster_id, _)| cluster_id)
+}
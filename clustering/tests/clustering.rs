@@ -0,0 +1,241 @@
+use clustering::{cluster_pending, cluster_user, ClusteringConfig};
+use database::{FaceRepository, InMemoryRepository, MediaRepository};
+use std::collections::HashMap;
+
+const ALICE: &str = "alice";
+
+async fn add_detection(
+    repository: &InMemoryRepository,
+    media_id: &str,
+    embedding: &[f32],
+    cluster_id: Option<i32>,
+) -> i32 {
+    repository
+        .add_media(
+            ALICE.to_string(),
+            media_id.to_string(),
+            format!("hash-{media_id}"),
+            0,
+            1024,
+            format!("{media_id}.jpg"),
+        )
+        .await
+        .unwrap();
+    let media_face_id = repository.add_media_face(media_id, vec![0, 0, 10, 10], cluster_id);
+    repository.set_face_embedding(media_face_id, embedding);
+    media_face_id
+}
+
+async fn clusters(repository: &InMemoryRepository) -> HashMap<i32, Option<i32>> {
+    repository
+        .get_face_embeddings(ALICE.to_string())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|detection| (detection.media_face_id, detection.cluster_id))
+        .collect()
+}
+
+#[tokio::test]
+async fn unclustered_detections_are_grouped_into_new_clusters() {
+    let repository = InMemoryRepository::new();
+    let a = add_detection(&repository, "a-1", &[1.0, 0.0, 0.0], None).await;
+    let b = add_detection(&repository, "a-2", &[0.99, 0.05, 0.0], None).await;
+    let c = add_detection(&repository, "a-3", &[0.98, 0.0, 0.05], None).await;
+    let d = add_detection(&repository, "b-1", &[0.0, 1.0, 0.0], None).await;
+    let e = add_detection(&repository, "b-2", &[0.05, 0.99, 0.0], None).await;
+    let f = add_detection(&repository, "b-3", &[0.0, 0.98, 0.05], None).await;
+    let stranger = add_detection(&repository, "stranger", &[0.0, 0.0, 1.0], None).await;
+
+    let moved = cluster_user(&repository, ALICE, &ClusteringConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(moved, 6);
+
+    let clusters = clusters(&repository).await;
+    assert!(clusters[&a].is_some());
+    assert_eq!(clusters[&a], clusters[&b]);
+    assert_eq!(clusters[&a], clusters[&c]);
+    assert!(clusters[&d].is_some());
+    assert_eq!(clusters[&d], clusters[&e]);
+    assert_eq!(clusters[&d], clusters[&f]);
+    assert_ne!(clusters[&a], clusters[&d]);
+    assert_eq!(clusters[&stranger], None);
+
+    // Nothing changes when clustering again
+    let moved = cluster_user(&repository, ALICE, &ClusteringConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(moved, 0);
+}
+
+#[tokio::test]
+async fn named_clusters_keep_their_detections() {
+    let repository = InMemoryRepository::new();
+    let carol_cluster = repository.add_cluster(ALICE, None);
+    let named = add_detection(
+        &repository,
+        "carol-1",
+        &[1.0, 0.0, 0.0],
+        Some(carol_cluster),
+    )
+    .await;
+    // Far from the others, but named by the user
+    let outlier = add_detection(
+        &repository,
+        "carol-2",
+        &[0.0, 0.0, 1.0],
+        Some(carol_cluster),
+    )
+    .await;
+    repository
+        .insert_face(ALICE.to_string(), vec![carol_cluster], "Carol".to_string())
+        .await
+        .unwrap();
+    let b = add_detection(&repository, "photo-1", &[0.99, 0.05, 0.0], None).await;
+    let c = add_detection(&repository, "photo-2", &[0.98, 0.0, 0.05], None).await;
+
+    cluster_user(&repository, ALICE, &ClusteringConfig::default())
+        .await
+        .unwrap();
+
+    let clusters = clusters(&repository).await;
+    assert_eq!(clusters[&named], Some(carol_cluster));
+    assert_eq!(clusters[&outlier], Some(carol_cluster));
+    assert_eq!(clusters[&b], Some(carol_cluster));
+    assert_eq!(clusters[&c], Some(carol_cluster));
+}
+
+#[tokio::test]
+async fn detections_close_to_a_named_face_join_it_or_are_suggested() {
+    let repository = InMemoryRepository::new();
+    let carol_cluster = repository.add_cluster(ALICE, None);
+    add_detection(&repository, "carol", &[1.0, 0.0, 0.0], Some(carol_cluster)).await;
+    repository
+        .insert_face(ALICE.to_string(), vec![carol_cluster], "Carol".to_string())
+        .await
+        .unwrap();
+    let close = add_detection(&repository, "close", &[0.99, 0.05, 0.0], None).await;
+    let unsure = add_detection(&repository, "unsure", &[0.6, 0.8, 0.0], None).await;
+    let stranger = add_detection(&repository, "stranger", &[0.0, 0.0, 1.0], None).await;
+
+    cluster_user(&repository, ALICE, &ClusteringConfig::default())
+        .await
+        .unwrap();
+
+    let clusters = clusters(&repository).await;
+    assert_eq!(clusters[&close], Some(carol_cluster));
+    assert_eq!(clusters[&unsure], None);
+    assert_eq!(clusters[&stranger], None);
+    let suggestions = repository
+        .get_face_suggestions(ALICE.to_string())
+        .await
+        .unwrap();
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].media_face_id, unsure);
+    assert_eq!(suggestions[0].name, "Carol");
+
+    // Rejections survive the next run
+    repository
+        .reject_face_suggestion(ALICE.to_string(), suggestions[0].suggestion_id)
+        .await
+        .unwrap();
+    cluster_user(&repository, ALICE, &ClusteringConfig::default())
+        .await
+        .unwrap();
+    assert!(repository
+        .get_face_suggestions(ALICE.to_string())
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn hidden_clusters_keep_their_detections_and_absorb_new_ones() {
+    let repository = InMemoryRepository::new();
+    let hidden_cluster = repository.add_cluster(ALICE, None);
+    let hidden = add_detection(
+        &repository,
+        "crowd-1",
+        &[1.0, 0.0, 0.0],
+        Some(hidden_cluster),
+    )
+    .await;
+    repository
+        .set_cluster_hidden(ALICE.to_string(), hidden_cluster, true)
+        .await
+        .unwrap();
+    let b = add_detection(&repository, "crowd-2", &[0.99, 0.05, 0.0], None).await;
+    let c = add_detection(&repository, "crowd-3", &[0.98, 0.0, 0.05], None).await;
+
+    cluster_user(&repository, ALICE, &ClusteringConfig::default())
+        .await
+        .unwrap();
+
+    let clusters = clusters(&repository).await;
+    assert_eq!(clusters[&hidden], Some(hidden_cluster));
+    assert_eq!(clusters[&b], Some(hidden_cluster));
+    assert_eq!(clusters[&c], Some(hidden_cluster));
+    let (_, visible_clusters) = repository
+        .get_faces(ALICE.to_string(), false)
+        .await
+        .unwrap();
+    assert!(visible_clusters.is_empty());
+}
+
+#[tokio::test]
+async fn noise_is_not_clustered_again_until_new_detections_come() {
+    let repository = InMemoryRepository::new();
+    add_detection(&repository, "a-1", &[1.0, 0.0, 0.0], None).await;
+    add_detection(&repository, "a-2", &[0.99, 0.05, 0.0], None).await;
+    add_detection(&repository, "a-3", &[0.98, 0.0, 0.05], None).await;
+    let stranger = add_detection(&repository, "stranger", &[0.0, 0.0, 1.0], None).await;
+    assert_eq!(
+        repository.get_users_with_unclustered_faces().await.unwrap(),
+        vec![ALICE.to_string()]
+    );
+
+    cluster_pending(&repository, &ClusteringConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(clusters(&repository).await[&stranger], None);
+    assert!(repository
+        .get_users_with_unclustered_faces()
+        .await
+        .unwrap()
+        .is_empty());
+
+    add_detection(&repository, "b-1", &[0.0, 1.0, 0.0], None).await;
+    assert_eq!(
+        repository.get_users_with_unclustered_faces().await.unwrap(),
+        vec![ALICE.to_string()]
+    );
+}
+
+#[tokio::test]
+async fn detections_without_embeddings_are_not_pending() {
+    let repository = InMemoryRepository::new();
+    repository
+        .add_media(
+            ALICE.to_string(),
+            "tagged".to_string(),
+            "hash-tagged".to_string(),
+            0,
+            1024,
+            "tagged.jpg".to_string(),
+        )
+        .await
+        .unwrap();
+    let media_face_id = repository.add_media_face("tagged", vec![0, 0, 10, 10], None);
+    assert!(repository
+        .get_users_with_unclustered_faces()
+        .await
+        .unwrap()
+        .is_empty());
+
+    repository.set_face_embedding(media_face_id, &[1.0, 0.0, 0.0]);
+    assert_eq!(
+        repository.get_users_with_unclustered_faces().await.unwrap(),
+        vec![ALICE.to_string()]
+    );
+}
@@ -0,0 +1,55 @@
+use clustering::dbscan;
+
+#[test]
+fn close_points_are_grouped_and_outliers_are_noise() {
+    let points = vec![
+        vec![1.0, 0.0, 0.0],
+        vec![0.0, 1.0, 0.0],
+        vec![0.99, 0.05, 0.0],
+        vec![0.05, 0.99, 0.0],
+        vec![0.98, 0.0, 0.05],
+        vec![0.0, 0.98, 0.05],
+        vec![0.0, 0.0, 1.0],
+    ];
+
+    let labels = dbscan(&points, 0.1, 3);
+
+    assert_eq!(
+        labels,
+        vec![Some(0), Some(1), Some(0), Some(1), Some(0), Some(1), None]
+    );
+}
+
+#[test]
+fn groups_smaller_than_min_points_are_noise() {
+    let points = vec![vec![1.0, 0.0], vec![0.99, 0.05]];
+
+    assert_eq!(dbscan(&points, 0.1, 3), vec![None, None]);
+    assert_eq!(dbscan(&points, 0.1, 2), vec![Some(0), Some(0)]);
+}
+
+#[test]
+fn noise_seen_first_becomes_a_border_point() {
+    // The first point has too few neighbours to start a cluster but is close to one
+    let points = vec![
+        vec![1.0, 0.3],
+        vec![1.0, 0.1],
+        vec![1.0, 0.0],
+        vec![1.0, -0.1],
+        vec![0.0, 1.0],
+    ];
+
+    assert_eq!(
+        dbscan(&points, 0.02, 3),
+        vec![Some(0), Some(0), Some(0), Some(0), None]
+    );
+}
+
+#[test]
+fn dense_groups_are_expanded_once_per_point() {
+    let points = vec![vec![1.0, 0.0]; 2000];
+
+    assert!(dbscan(&points, 0.1, 3)
+        .into_iter()
+        .all(|label| label == Some(0)));
+}
@@ -7,6 +7,22 @@ mod m004_face;
 mod m005_cluster;
 mod m006_media_face;
 mod m007_log;
+mod m008_media_clip_index;
+mod m009_face_user;
+mod m010_face_suggestion;
+mod m011_hidden_faces;
+mod m012_manual_media_face;
+mod m013_media_face_crop;
+mod m014_media_capture_time;
+mod m015_media_exif;
+mod m016_media_annotations;
+mod m017_media_edits;
+mod m018_media_annotations_edited;
+mod m019_media_dimensions;
+mod m020_rendition;
+mod m021_media_placeholder;
+mod m022_media_face_clustered_at;
+mod m023_media_face_crop_attempts;
 
 pub struct Migrator;
 
@@ -21,6 +37,22 @@ impl MigratorTrait for Migrator {
             Box::new(m005_cluster::Migration),
             Box::new(m006_media_face::Migration),
             Box::new(m007_log::Migration),
+            Box::new(m008_media_clip_index::Migration),
+            Box::new(m009_face_user::Migration),
+            Box::new(m010_face_suggestion::Migration),
+            Box::new(m011_hidden_faces::Migration),
+            Box::new(m012_manual_media_face::Migration),
+            Box::new(m013_media_face_crop::Migration),
+            Box::new(m014_media_capture_time::Migration),
+            Box::new(m015_media_exif::Migration),
+            Box::new(m016_media_annotations::Migration),
+            Box::new(m017_media_edits::Migration),
+            Box::new(m018_media_annotations_edited::Migration),
+            Box::new(m019_media_dimensions::Migration),
+            Box::new(m020_rendition::Migration),
+            Box::new(m021_media_placeholder::Migration),
+            Box::new(m022_media_face_clustered_at::Migration),
+            Box::new(m023_media_face_crop_attempts::Migration),
         ]
     }
 }
@@ -0,0 +1,26 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Cosine distance index for similarity searches over the clip embeddings
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS media_clip_embeddings_idx ON media USING hnsw (clip_embeddings vector_cosine_ops)",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DROP INDEX IF EXISTS media_clip_embeddings_idx")
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{m002_user::User, m004_face::Face};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Face::Table)
+                    .add_column(ColumnDef::new(FaceUser::UserId).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Faces were owned through their clusters, the oldest cluster decides
+        db.execute_unprepared(
+            r#"UPDATE "face" SET "user_id" = (SELECT "cluster"."user_id" FROM "cluster" WHERE "cluster"."face_id" = "face"."id" ORDER BY "cluster"."id" LIMIT 1)"#,
+        )
+        .await?;
+        // Clusters of other users can't stay on a face they don't own
+        db.execute_unprepared(
+            r#"UPDATE "cluster" SET "face_id" = NULL FROM "face" WHERE "cluster"."face_id" = "face"."id" AND "cluster"."user_id" <> "face"."user_id""#,
+        )
+        .await?;
+        // Faces without clusters could not be reached by anyone
+        db.execute_unprepared(r#"DELETE FROM "face" WHERE "user_id" IS NULL"#)
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Face::Table)
+                    .modify_column(ColumnDef::new(FaceUser::UserId).string().not_null())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("face_user_id")
+                            .from_tbl(Face::Table)
+                            .from_col(FaceUser::UserId)
+                            .to_tbl(User::Table)
+                            .to_col(User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("face_user_id_idx")
+                    .table(Face::Table)
+                    .col(FaceUser::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("face_user_id_idx")
+                    .table(Face::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Face::Table)
+                    .drop_foreign_key(Alias::new("face_user_id"))
+                    .drop_column(FaceUser::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum FaceUser {
+    UserId,
+}
@@ -0,0 +1,67 @@
+use crate::{m004_face::Face, m006_media_face::MediaFace};
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(FaceSuggestion::Table)
+                    .if_not_exists()
+                    .col(integer(FaceSuggestion::Id).primary_key().auto_increment())
+                    .col(integer(FaceSuggestion::MediaFaceId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("face_suggestion_media_face_id")
+                            .from(FaceSuggestion::Table, FaceSuggestion::MediaFaceId)
+                            .to(MediaFace::Table, MediaFace::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(integer(FaceSuggestion::FaceId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("face_suggestion_face_id")
+                            .from(FaceSuggestion::Table, FaceSuggestion::FaceId)
+                            .to(Face::Table, Face::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(float(FaceSuggestion::Distance))
+                    .col(boolean(FaceSuggestion::Rejected).default(false))
+                    .to_owned(),
+            )
+            .await?;
+
+        // A detection is suggested at most once for every face, rejections included
+        manager
+            .create_index(
+                Index::create()
+                    .name("face_suggestion_media_face_id_face_id_idx")
+                    .table(FaceSuggestion::Table)
+                    .col(FaceSuggestion::MediaFaceId)
+                    .col(FaceSuggestion::FaceId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(FaceSuggestion::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum FaceSuggestion {
+    Table,
+    Id,
+    MediaFaceId,
+    FaceId,
+    Distance,
+    Rejected,
+}
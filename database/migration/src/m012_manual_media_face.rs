@@ -0,0 +1,52 @@
+use crate::m006_media_face::MediaFace;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFace::Table)
+                    .add_column(
+                        ColumnDef::new(ManualMediaFace::Manual)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Faces tagged by the user wait for their embedding
+        db.execute_unprepared(r#"ALTER TABLE "media_face" ALTER COLUMN "embedding" DROP NOT NULL"#)
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(r#"DELETE FROM "media_face" WHERE "embedding" IS NULL"#)
+            .await?;
+        db.execute_unprepared(r#"ALTER TABLE "media_face" ALTER COLUMN "embedding" SET NOT NULL"#)
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFace::Table)
+                    .drop_column(ManualMediaFace::Manual)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ManualMediaFace {
+    Manual,
+}
@@ -0,0 +1,49 @@
+use crate::m003_media::Media;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Media::Table)
+                    .add_column(
+                        ColumnDef::new(MediaCaptureTime::UploadedAt)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(ColumnDef::new(MediaCaptureTime::UtcOffset).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        // The upload time of existing media is unknown, the last change is the closest to it
+        db.execute_unprepared(r#"UPDATE "media" SET "uploaded_at" = "last_modified_at""#)
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Media::Table)
+                    .drop_column(MediaCaptureTime::UploadedAt)
+                    .drop_column(MediaCaptureTime::UtcOffset)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MediaCaptureTime {
+    UploadedAt,
+    UtcOffset,
+}
@@ -0,0 +1,75 @@
+use crate::m003_media::Media;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Media::Table)
+                    .add_column(ColumnDef::new(MediaExif::LensMake).string().null())
+                    .add_column(ColumnDef::new(MediaExif::LensModel).string().null())
+                    .add_column(ColumnDef::new(MediaExif::FocalLength).double().null())
+                    .add_column(ColumnDef::new(MediaExif::FocalLength35mm).integer().null())
+                    .add_column(ColumnDef::new(MediaExif::Altitude).double().null())
+                    .add_column(ColumnDef::new(MediaExif::ImageDirection).double().null())
+                    .add_column(ColumnDef::new(MediaExif::Flash).string().null())
+                    .add_column(ColumnDef::new(MediaExif::WhiteBalance).string().null())
+                    .add_column(ColumnDef::new(MediaExif::MeteringMode).string().null())
+                    .add_column(ColumnDef::new(MediaExif::ExposureProgram).string().null())
+                    .add_column(ColumnDef::new(MediaExif::ExposureBias).double().null())
+                    .add_column(ColumnDef::new(MediaExif::Software).string().null())
+                    .add_column(ColumnDef::new(MediaExif::Artist).string().null())
+                    .add_column(ColumnDef::new(MediaExif::Exif).json_binary().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Media::Table)
+                    .drop_column(MediaExif::LensMake)
+                    .drop_column(MediaExif::LensModel)
+                    .drop_column(MediaExif::FocalLength)
+                    .drop_column(MediaExif::FocalLength35mm)
+                    .drop_column(MediaExif::Altitude)
+                    .drop_column(MediaExif::ImageDirection)
+                    .drop_column(MediaExif::Flash)
+                    .drop_column(MediaExif::WhiteBalance)
+                    .drop_column(MediaExif::MeteringMode)
+                    .drop_column(MediaExif::ExposureProgram)
+                    .drop_column(MediaExif::ExposureBias)
+                    .drop_column(MediaExif::Software)
+                    .drop_column(MediaExif::Artist)
+                    .drop_column(MediaExif::Exif)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MediaExif {
+    LensMake,
+    LensModel,
+    FocalLength,
+    #[sea_orm(iden = "focal_length_35mm")]
+    FocalLength35mm,
+    Altitude,
+    ImageDirection,
+    Flash,
+    WhiteBalance,
+    MeteringMode,
+    ExposureProgram,
+    ExposureBias,
+    Software,
+    Artist,
+    Exif,
+}
@@ -0,0 +1,57 @@
+use crate::m003_media::Media;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Media::Table)
+                    .add_column(ColumnDef::new(MediaAnnotations::Rating).integer().null())
+                    .add_column(ColumnDef::new(MediaAnnotations::Title).string().null())
+                    .add_column(ColumnDef::new(MediaAnnotations::Description).text().null())
+                    .add_column(
+                        ColumnDef::new(MediaAnnotations::Keywords)
+                            .array(ColumnType::Text)
+                            .not_null()
+                            .default(Expr::cust("'{}'")),
+                    )
+                    .add_column(
+                        ColumnDef::new(MediaAnnotations::People)
+                            .array(ColumnType::Text)
+                            .not_null()
+                            .default(Expr::cust("'{}'")),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Media::Table)
+                    .drop_column(MediaAnnotations::Rating)
+                    .drop_column(MediaAnnotations::Title)
+                    .drop_column(MediaAnnotations::Description)
+                    .drop_column(MediaAnnotations::Keywords)
+                    .drop_column(MediaAnnotations::People)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MediaAnnotations {
+    Rating,
+    Title,
+    Description,
+    Keywords,
+    People,
+}
@@ -0,0 +1,41 @@
+use crate::m003_media::Media;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Media::Table)
+                    .add_column(ColumnDef::new(MediaDimensions::Width).integer().null())
+                    .add_column(ColumnDef::new(MediaDimensions::Height).integer().null())
+                    .add_column(ColumnDef::new(MediaDimensions::MediaType).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Media::Table)
+                    .drop_column(MediaDimensions::Width)
+                    .drop_column(MediaDimensions::Height)
+                    .drop_column(MediaDimensions::MediaType)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MediaDimensions {
+    Width,
+    Height,
+    MediaType,
+}
@@ -0,0 +1,65 @@
+use crate::m003_media::Media;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Rendition::Table)
+                    .if_not_exists()
+                    .col(integer(Rendition::Id).primary_key().auto_increment())
+                    .col(string(Rendition::MediaId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("rendition_media_id")
+                            .from(Rendition::Table, Rendition::MediaId)
+                            .to(Media::Table, Media::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(string(Rendition::Name))
+                    .col(string(Rendition::ContentType))
+                    .col(integer(Rendition::Width))
+                    .col(integer(Rendition::Height))
+                    .col(string(Rendition::ObjectId))
+                    .to_owned(),
+            )
+            .await?;
+
+        // A media has one rendition of every size in every format
+        manager
+            .create_index(
+                Index::create()
+                    .name("rendition_media_id_name_content_type_idx")
+                    .table(Rendition::Table)
+                    .col(Rendition::MediaId)
+                    .col(Rendition::Name)
+                    .col(Rendition::ContentType)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Rendition::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Rendition {
+    Table,
+    Id,
+    MediaId,
+    Name,
+    ContentType,
+    Width,
+    Height,
+    ObjectId,
+}
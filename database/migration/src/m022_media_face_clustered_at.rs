@@ -0,0 +1,56 @@
+use crate::m006_media_face::MediaFace;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFace::Table)
+                    .add_column(
+                        ColumnDef::new(MediaFaceClusteredAt::ClusteredAt)
+                            .big_integer()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Detections in a cluster went through the clustering, noise is clustered once more
+        db.execute_unprepared(
+            r#"UPDATE "media_face" SET "clustered_at" = CAST(EXTRACT(EPOCH FROM NOW()) * 1000 AS BIGINT) WHERE "cluster_id" IS NOT NULL"#,
+        )
+        .await?;
+        // What the clustering worker looks for on every run
+        db.execute_unprepared(
+            r#"CREATE INDEX IF NOT EXISTS media_face_pending_clustering_idx ON "media_face" ("media_id") WHERE "clustered_at" IS NULL AND "embedding" IS NOT NULL"#,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DROP INDEX IF EXISTS media_face_pending_clustering_idx")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MediaFace::Table)
+                    .drop_column(MediaFaceClusteredAt::ClusteredAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MediaFaceClusteredAt {
+    ClusteredAt,
+}
@@ -1,17 +1,28 @@
+pub mod memory;
+mod repository;
 pub mod schema;
+pub mod vector;
 
+pub use memory::InMemoryRepository;
+pub use repository::{FaceRepository, LogRepository, MediaRepository, Repository, UserRepository};
+pub use sea_orm::DbErr;
+
+use async_trait::async_trait;
 use migration::{Migrator, MigratorTrait};
-use schema::{
-    cluster, face, log,
-    media::{self, ActiveModel},
-    media_face, user,
-};
+use schema::{cluster, face, face_suggestion, log, media, media_face, rendition, user};
 use sea_orm::{
-    entity::*, query::*, sqlx::types::chrono::Utc, ColumnTrait, ConnectOptions, Database,
-    DatabaseConnection, DbErr, EntityTrait, FromQueryResult, QueryFilter,
+    entity::*,
+    query::*,
+    sea_query::{self, Alias, Expr, Func, Query},
+    sqlx::types::chrono::Utc,
+    ColumnTrait, ConnectOptions, ConnectionTrait, Database, DatabaseConnection, EntityTrait,
+    FromQueryResult, QueryFilter, QueryResult, TransactionTrait,
 };
 use serde::{Deserialize, Serialize};
-use std::string::ToString;
+use std::{
+    collections::{HashMap, HashSet},
+    string::ToString,
+};
 
 #[derive(Deserialize, Debug)]
 struct DbEnvs {
@@ -31,6 +42,32 @@ fn default_port() -> u16 {
     5432
 }
 
+// Everything a `RemoteMediaAdded` is read from
+const SYNC_COLUMNS: [media::Column; 5] = [
+    media::Column::Id,
+    media::Column::CreatedAt,
+    media::Column::Hash,
+    media::Column::Blurhash,
+    media::Column::DominantColors,
+];
+
+// How many times the preview worker tries to crop a detection before leaving it without a crop,
+// e.g. when its bounding box is outside of the media or the original can't be decoded
+pub const FACE_CROP_MAX_ATTEMPTS: i32 = 3;
+
+// Everything a `MediaPreview` is read from
+const PREVIEW_COLUMNS: [media::Column; 9] = [
+    media::Column::Id,
+    media::Column::PreviewId,
+    media::Column::CreatedAt,
+    media::Column::UtcOffset,
+    media::Column::Width,
+    media::Column::Height,
+    media::Column::MediaType,
+    media::Column::Blurhash,
+    media::Column::DominantColors,
+];
+
 #[derive(Clone)]
 pub struct DbManager {
     pub connection: DatabaseConnection,
@@ -47,14 +84,227 @@ impl DbManager {
             db_config.database_port,
             db_config.database_name
         );
-        let mut opt = ConnectOptions::new(&connection_string);
-        opt.max_connections(100).min_connections(5);
+        DbManager::connect(&connection_string, 100).await
+    }
+
+    pub async fn connect(connection_string: &str, max_connections: u32) -> Result<Self, DbErr> {
+        let mut opt = ConnectOptions::new(connection_string);
+        opt.max_connections(max_connections)
+            .min_connections(max_connections.min(5));
         let connection: DatabaseConnection = Database::connect(opt).await?;
         Migrator::up(&connection, None).await?;
         Ok(DbManager { connection })
     }
 
-    pub async fn query_media(&self, user_id: String, checksum: String) -> Result<bool, &str> {
+    async fn user_has_face<C: ConnectionTrait>(
+        connection: &C,
+        user_id: &str,
+        face_id: i32,
+    ) -> Result<bool, DbErr> {
+        let faces = face::Entity::find_by_id(face_id)
+            .filter(face::Column::UserId.eq(user_id))
+            .count(connection)
+            .await?;
+        Ok(faces > 0)
+    }
+
+    // Unnamed clusters are listed by one of their detections, so those of the given ones that
+    // have none left are removed. Clusters of a face are kept, the face would lose its last one
+    async fn delete_empty_clusters<C: ConnectionTrait>(
+        connection: &C,
+        cluster_ids: Vec<i32>,
+    ) -> Result<(), DbErr> {
+        if cluster_ids.is_empty() {
+            return Ok(());
+        }
+        cluster::Entity::delete_many()
+            .filter(cluster::Column::Id.is_in(cluster_ids))
+            .filter(cluster::Column::FaceId.is_null())
+            .filter(
+                cluster::Column::Id.not_in_subquery(
+                    Query::select()
+                        .column(media_face::Column::ClusterId)
+                        .from(media_face::Entity)
+                        .and_where(media_face::Column::ClusterId.is_not_null())
+                        .to_owned(),
+                ),
+            )
+            .exec(connection)
+            .await?;
+        Ok(())
+    }
+
+    // Media showing at least `min_faces` different faces among `face_ids`
+    fn media_with_faces(face_ids: &[i32], min_faces: usize) -> sea_query::SelectStatement {
+        Query::select()
+            .column((media_face::Entity, media_face::Column::MediaId))
+            .from(media_face::Entity)
+            .inner_join(
+                cluster::Entity,
+                Expr::col((cluster::Entity, cluster::Column::Id))
+                    .equals((media_face::Entity, media_face::Column::ClusterId)),
+            )
+            .and_where(
+                Expr::col((cluster::Entity, cluster::Column::FaceId)).is_in(face_ids.to_vec()),
+            )
+            .group_by_col((media_face::Entity, media_face::Column::MediaId))
+            .and_having(
+                Expr::expr(Expr::col((cluster::Entity, cluster::Column::FaceId)).count_distinct())
+                    .gte(min_faces as i64),
+            )
+            .to_owned()
+    }
+
+    // Media of the user matching the filter
+    fn filtered_media(user_id: String, filter: MediaFilter) -> Select<media::Entity> {
+        let mut query = media::Entity::find()
+            .filter(media::Column::UserId.eq(user_id.clone()))
+            .filter(media::Column::Deleted.eq(false));
+
+        if let Some(after) = filter.after {
+            query = query.filter(media::Column::CreatedAt.gte(after));
+        }
+        if let Some(before) = filter.before {
+            query = query.filter(media::Column::CreatedAt.lt(before));
+        }
+        if let Some(make) = filter.make {
+            query = query.filter(
+                Expr::expr(Func::lower(Expr::col(media::Column::Make))).eq(make.to_lowercase()),
+            );
+        }
+        if let Some(model) = filter.model {
+            query = query.filter(
+                Expr::expr(Func::lower(Expr::col(media::Column::Model))).eq(model.to_lowercase()),
+            );
+        }
+        if let Some(camera) = filter.camera {
+            query = query.filter(Expr::cust_with_values(
+                r#"strpos(lower(concat_ws(' ', "media"."make", "media"."model")), $1) > 0"#,
+                [camera.to_lowercase()],
+            ));
+        }
+        match filter.has_gps {
+            Some(true) => {
+                query = query
+                    .filter(media::Column::Latitude.is_not_null())
+                    .filter(media::Column::Longitude.is_not_null());
+            }
+            Some(false) => {
+                query = query.filter(
+                    Condition::any()
+                        .add(media::Column::Latitude.is_null())
+                        .add(media::Column::Longitude.is_null()),
+                );
+            }
+            None => {}
+        }
+        if let Some(near) = filter.near {
+            let (min_latitude, max_latitude, min_longitude, max_longitude) = near.bounds();
+            query = query
+                .filter(media::Column::Latitude.between(min_latitude, max_latitude))
+                .filter(media::Column::Longitude.between(min_longitude, max_longitude));
+        }
+        // The sensitivity is stored as text, anything that is not a plain number is never matched
+        let iso = || {
+            Expr::expr(Expr::cust(
+                r#"CASE WHEN "media"."photographic_sensitivity" ~ '^[0-9]{1,9}$' THEN CAST("media"."photographic_sensitivity" AS INTEGER) END"#,
+            ))
+        };
+        if let Some(min_iso) = filter.min_iso {
+            query = query.filter(iso().gte(min_iso));
+        }
+        if let Some(max_iso) = filter.max_iso {
+            query = query.filter(iso().lte(max_iso));
+        }
+        if let Some(cluster_id) = filter.cluster_id {
+            query = query.filter(
+                media::Column::Id.in_subquery(
+                    Query::select()
+                        .column(media_face::Column::MediaId)
+                        .from(media_face::Entity)
+                        .and_where(media_face::Column::ClusterId.eq(cluster_id))
+                        .to_owned(),
+                ),
+            );
+        }
+        if let Some(face_id) = filter.face_id {
+            query = query.filter(
+                media::Column::Id.in_subquery(
+                    Query::select()
+                        .column((media_face::Entity, media_face::Column::MediaId))
+                        .from(media_face::Entity)
+                        .inner_join(
+                            cluster::Entity,
+                            Expr::col((cluster::Entity, cluster::Column::Id))
+                                .equals((media_face::Entity, media_face::Column::ClusterId)),
+                        )
+                        .and_where(
+                            Expr::col((cluster::Entity, cluster::Column::FaceId)).eq(face_id),
+                        )
+                        .to_owned(),
+                ),
+            );
+        }
+
+        if let Some(person) = filter.person {
+            query = query.filter(
+                media::Column::Id.in_subquery(
+                    Query::select()
+                        .column((media_face::Entity, media_face::Column::MediaId))
+                        .from(media_face::Entity)
+                        .inner_join(
+                            cluster::Entity,
+                            Expr::col((cluster::Entity, cluster::Column::Id))
+                                .equals((media_face::Entity, media_face::Column::ClusterId)),
+                        )
+                        .inner_join(
+                            face::Entity,
+                            Expr::col((face::Entity, face::Column::Id))
+                                .equals((cluster::Entity, cluster::Column::FaceId)),
+                        )
+                        .and_where(
+                            Expr::expr(Func::lower(Expr::col((face::Entity, face::Column::Name))))
+                                .eq(person.to_lowercase()),
+                        )
+                        .and_where(
+                            Expr::col((face::Entity, face::Column::UserId)).eq(user_id.clone()),
+                        )
+                        .to_owned(),
+                ),
+            );
+        }
+
+        query
+    }
+
+    async fn _delete_media(&self, media_id: i32, user_id: i32) -> Result<(), &'static str> {
+        // Find the photo to be deleted
+        let media = media::Entity::find()
+            .filter(media::Column::Id.eq(media_id))
+            .filter(media::Column::UserId.eq(user_id))
+            .one(&self.connection)
+            .await;
+
+        match media {
+            Ok(Some(media)) => {
+                let mut media_model = media.into_active_model();
+                media_model.deleted = Set(true);
+                media_model.last_modified_at = Set(Utc::now().timestamp_millis());
+                // Save changes to the database
+                match media_model.update(&self.connection).await {
+                    Ok(_) => Ok(()), // Return success if updated
+                    Err(..) => Err("Failed to delete media"),
+                }
+            }
+            Ok(None) => Err("Failed to delete media"),
+            Err(..) => Err("Error "),
+        }
+    }
+}
+
+#[async_trait]
+impl MediaRepository for DbManager {
+    async fn query_media(&self, user_id: String, checksum: String) -> Result<bool, &'static str> {
         match media::Entity::find()
             .filter(media::Column::Hash.eq(checksum))
             .filter(media::Column::UserId.eq(user_id))
@@ -70,7 +320,7 @@ impl DbManager {
         }
     }
 
-    pub async fn add_media(
+    async fn add_media(
         &self,
         user_id: String,
         media_id: String,
@@ -78,12 +328,13 @@ impl DbManager {
         timestamp: i64,
         file_size: i64,
         file_name: String,
-    ) -> Result<InsertResult<ActiveModel>, DbErr> {
+    ) -> Result<(), DbErr> {
         let media_to_insert = media::ActiveModel {
             id: Set(media_id),
             user_id: Set(user_id),
             hash: Set(checksum),
             created_at: Set(timestamp),
+            uploaded_at: Set(Utc::now().timestamp_millis()),
             last_modified_at: Set(Utc::now().timestamp_millis()),
             deleted: Set(false),
             file_name: Set(file_name),
@@ -93,11 +344,12 @@ impl DbManager {
 
         media::Entity::insert(media_to_insert)
             .exec(&self.connection)
-            .await
+            .await?;
+        Ok(())
     }
 
-    pub async fn get_media(&self, media_id: String) -> Result<Option<media::Model>, DbErr> {
-        match media::Entity::find_by_id(&media_id)
+    async fn get_media(&self, media_id: String) -> Result<Option<media::Model>, DbErr> {
+        media::Entity::find_by_id(&media_id)
             .select_only()
             .columns([
                 media::Column::Id,
@@ -105,6 +357,8 @@ impl DbManager {
                 media::Column::PreviewId,
                 media::Column::Hash,
                 media::Column::CreatedAt,
+                media::Column::UploadedAt,
+                media::Column::UtcOffset,
                 media::Column::LastModifiedAt,
                 media::Column::Deleted,
                 media::Column::FileSize,
@@ -113,85 +367,49 @@ impl DbManager {
                 media::Column::Latitude,
                 media::Column::ImageWidth,
                 media::Column::ImageLength,
+                media::Column::Width,
+                media::Column::Height,
+                media::Column::MediaType,
+                media::Column::Blurhash,
+                media::Column::DominantColors,
                 media::Column::Make,
                 media::Column::Model,
                 media::Column::Fnumber,
                 media::Column::ExposureTime,
                 media::Column::PhotographicSensitivity,
                 media::Column::Orientation,
+                media::Column::LensMake,
+                media::Column::LensModel,
+                media::Column::FocalLength,
+                media::Column::FocalLength35mm,
+                media::Column::Altitude,
+                media::Column::ImageDirection,
+                media::Column::Flash,
+                media::Column::WhiteBalance,
+                media::Column::MeteringMode,
+                media::Column::ExposureProgram,
+                media::Column::ExposureBias,
+                media::Column::Software,
+                media::Column::Artist,
+                media::Column::Exif,
+                media::Column::Rating,
+                media::Column::Title,
+                media::Column::Description,
+                media::Column::Keywords,
+                media::Column::People,
+                media::Column::LocationEdited,
+                media::Column::CaptureTimeEdited,
+                media::Column::AnnotationsEdited,
             ])
             .one(&self.connection)
             .await
-        {
-            Ok(result) => Ok(result),
-            Err(e) => Err(e),
-        }
-    }
-
-    async fn _delete_media(&self, media_id: i32, user_id: i32) -> Result<(), &'static str> {
-        // Find the photo to be deleted
-        let media = media::Entity::find()
-            .filter(media::Column::Id.eq(media_id))
-            .filter(media::Column::UserId.eq(user_id))
-            .one(&self.connection)
-            .await;
-
-        match media {
-            Ok(Some(media)) => {
-                let mut media_model = media.into_active_model();
-                media_model.deleted = Set(true);
-                media_model.last_modified_at = Set(Utc::now().timestamp_millis());
-                // Save changes to the database
-                match media_model.update(&self.connection).await {
-                    Ok(_) => Ok(()), // Return success if updated
-                    Err(..) => Err("Failed to delete media"),
-                }
-            }
-            Ok(None) => Err("Failed to delete media"),
-            Err(..) => Err("Error "),
-        }
-    }
-
-    pub async fn get_user(&self, username: String) -> Result<user::Model, GetUserError> {
-        match user::Entity::find()
-            .filter(user::Column::Username.eq(username))
-            .one(&self.connection)
-            .await
-        {
-            Ok(Some(user)) => Ok(user),
-            Ok(None) => Err(GetUserError::NotFound),
-            Err(..) => Err(GetUserError::InternalError),
-        }
-    }
-
-    pub async fn add_user(
-        &self,
-        id: String,
-        username: String,
-        password: String,
-    ) -> Result<(), AddUserError> {
-        match self.get_user(username.clone()).await {
-            Ok(_) => Err(AddUserError::AlreadyExists),
-            Err(GetUserError::NotFound) => {
-                let new_user = user::ActiveModel {
-                    id: Set(id),
-                    username: Set(username),
-                    password: Set(password),
-                };
-
-                match new_user.insert(&self.connection).await {
-                    Ok(_) => Ok(()),
-                    Err(_) => Err(AddUserError::InternalError),
-                }
-            }
-            Err(GetUserError::InternalError) => Err(AddUserError::InternalError),
-        }
     }
 
-    pub async fn update_media_preview(
+    async fn update_media_preview(
         &self,
         media_id: String,
         preview_id: String,
+        image: DecodedImage,
     ) -> Result<(), String> {
         let Ok(media) = media::Entity::find_by_id(&media_id)
             .one(&self.connection)
@@ -211,6 +429,12 @@ impl DbManager {
         let mut media: media::ActiveModel = media.into();
         // WARN: should i rewrite this no matter what?
         media.preview_id = Set(Some(preview_id));
+        media.width = Set(Some(image.width));
+        media.height = Set(Some(image.height));
+        media.media_type = Set(image.media_type);
+        media.blurhash = Set(image.blurhash);
+        media.dominant_colors = Set(image.dominant_colors);
+        media.last_modified_at = Set(Utc::now().timestamp_millis());
         match media.update(&self.connection).await {
             Ok(_) => Ok(()),
             Err(_) => Err(format!(
@@ -220,14 +444,47 @@ impl DbManager {
         }
     }
 
-    pub async fn sync_full(&self, user_id: String) -> Result<Vec<RemoteMediaAdded>, &str> {
+    async fn set_renditions(
+        &self,
+        media_id: String,
+        renditions: Vec<Rendition>,
+    ) -> Result<(), DbErr> {
+        let txn = self.connection.begin().await?;
+        rendition::Entity::delete_many()
+            .filter(rendition::Column::MediaId.eq(media_id.clone()))
+            .exec(&txn)
+            .await?;
+        if !renditions.is_empty() {
+            rendition::Entity::insert_many(renditions.into_iter().map(|rendition| {
+                rendition::ActiveModel {
+                    media_id: Set(media_id.clone()),
+                    name: Set(rendition.name),
+                    content_type: Set(rendition.content_type),
+                    width: Set(rendition.width),
+                    height: Set(rendition.height),
+                    object_id: Set(rendition.object_id),
+                    ..Default::default()
+                }
+            }))
+            .exec(&txn)
+            .await?;
+        }
+        txn.commit().await
+    }
+
+    async fn get_renditions(&self, media_ids: Vec<String>) -> Result<Vec<rendition::Model>, DbErr> {
+        rendition::Entity::find()
+            .filter(rendition::Column::MediaId.is_in(media_ids))
+            .all(&self.connection)
+            .await
+    }
+
+    async fn sync_full(&self, user_id: String) -> Result<Vec<RemoteMediaAdded>, &'static str> {
         match media::Entity::find()
             .filter(media::Column::UserId.eq(user_id))
             .filter(media::Column::Deleted.eq(false))
             .select_only()
-            .select_column(media::Column::Id)
-            .select_column(media::Column::CreatedAt)
-            .select_column(media::Column::Hash)
+            .columns(SYNC_COLUMNS)
             .into_model::<RemoteMediaAdded>()
             .all(&self.connection)
             .await
@@ -240,17 +497,15 @@ impl DbManager {
         }
     }
 
-    pub async fn sync_partial(
+    async fn sync_partial(
         &self,
         user_id: String,
         since: i64,
-    ) -> Result<(Vec<RemoteMediaAdded>, Vec<RemoteMediaDeleted>), &str> {
+    ) -> Result<(Vec<RemoteMediaAdded>, Vec<RemoteMediaDeleted>), &'static str> {
         // Query for added media
         let changed_media = media::Entity::find()
             .select_only()
-            .select_column(media::Column::Id)
-            .select_column(media::Column::CreatedAt)
-            .select_column(media::Column::Hash)
+            .columns(SYNC_COLUMNS)
             .filter(media::Column::UserId.eq(user_id))
             .filter(media::Column::LastModifiedAt.gt(since));
 
@@ -275,7 +530,7 @@ impl DbManager {
         }
     }
 
-    pub async fn user_has_media(&self, user_id: String, media_id: &String) -> Result<bool, &str> {
+    async fn user_has_media(&self, user_id: String, media_id: &str) -> Result<bool, &'static str> {
         match media::Entity::find()
             .select_only()
             .select_column(media::Column::Id)
@@ -292,24 +547,23 @@ impl DbManager {
         }
     }
 
-    pub async fn get_previews(
+    async fn get_previews(
         &self,
         user_id: String,
         page: u64,
         page_size: u64,
-    ) -> Result<Vec<(String, Option<String>)>, GetPreviewError> {
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
         let offset = (page - 1) * page_size;
 
         match media::Entity::find()
             .order_by_desc(media::Column::CreatedAt)
             .select_only()
-            .select_column(media::Column::Id)
-            .select_column(media::Column::PreviewId)
+            .columns(PREVIEW_COLUMNS)
             .filter(media::Column::UserId.eq(user_id))
             .filter(media::Column::Deleted.eq(false))
             .offset(offset)
             .limit(page_size)
-            .into_tuple::<(String, Option<String>)>()
+            .into_model::<MediaPreview>()
             .all(&self.connection)
             .await
         {
@@ -318,10 +572,10 @@ impl DbManager {
         }
     }
 
-    pub async fn get_preview_from_user(
+    async fn get_preview_from_user(
         &self,
         user_id: String,
-        media_id: &String,
+        media_id: &str,
     ) -> Result<String, GetPreviewError> {
         match media::Entity::find()
             .select_only()
@@ -339,110 +593,459 @@ impl DbManager {
         }
     }
 
-    pub async fn get_logs(
+    async fn get_similar_media(
         &self,
         user_id: String,
+        media_id: &str,
         page: u64,
         page_size: u64,
-    ) -> Result<Vec<LogEntry>, GetLogError> {
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
         let offset = (page - 1) * page_size;
 
-        match log::Entity::find()
-            .order_by_desc(log::Column::Date)
-            .filter(log::Column::UserId.eq(user_id))
+        let has_clip_embeddings = match media::Entity::find()
+            .select_only()
+            .expr_as(
+                Expr::col(media::Column::ClipEmbeddings).is_not_null(),
+                "has_clip_embeddings",
+            )
+            .filter(media::Column::Id.eq(media_id))
+            .filter(media::Column::UserId.eq(user_id.clone()))
+            .filter(media::Column::Deleted.eq(false))
+            .into_tuple::<bool>()
+            .one(&self.connection)
+            .await
+        {
+            Ok(Some(has_clip_embeddings)) => has_clip_embeddings,
+            Ok(None) => return Err(GetPreviewError::NotFound),
+            Err(_) => return Err(GetPreviewError::InternalError),
+        };
+        // Embeddings are generated asynchronously, there is nothing to compare until they exist
+        if !has_clip_embeddings {
+            return Ok(vec![]);
+        }
+
+        let Ok(txn) = self.connection.begin().await else {
+            return Err(GetPreviewError::InternalError);
+        };
+        // The index is shared by every user and yields ef_search candidates before the user is
+        // filtered, iterative scans (pgvector 0.8) keep going until the page is filled
+        let ef_search = (offset + page_size).clamp(40, 1000);
+        for setting in [
+            format!("SET LOCAL hnsw.ef_search = {ef_search}"),
+            "SET LOCAL hnsw.iterative_scan = strict_order".to_string(),
+        ] {
+            if txn.execute_unprepared(&setting).await.is_err() {
+                return Err(GetPreviewError::InternalError);
+            }
+        }
+
+        let similar_media = media::Entity::find()
+            .select_only()
+            .columns(PREVIEW_COLUMNS)
+            .filter(media::Column::UserId.eq(user_id))
+            .filter(media::Column::Deleted.eq(false))
+            .filter(media::Column::Id.ne(media_id))
+            .filter(media::Column::ClipEmbeddings.is_not_null())
+            .order_by(
+                Expr::cust_with_values(
+                    r#""media"."clip_embeddings" <=> (SELECT "clip_embeddings" FROM "media" WHERE "id" = $1)"#,
+                    [media_id],
+                ),
+                Order::Asc,
+            )
             .offset(offset)
             .limit(page_size)
+            .into_model::<MediaPreview>()
+            .all(&txn)
+            .await;
+
+        match (similar_media, txn.commit().await) {
+            (Ok(similar_media), Ok(())) => Ok(similar_media),
+            _ => Err(GetPreviewError::InternalError),
+        }
+    }
+
+    async fn filter_media(
+        &self,
+        user_id: String,
+        media_ids: Vec<String>,
+        filter: MediaFilter,
+    ) -> Result<Vec<String>, GetPreviewError> {
+        match Self::filtered_media(user_id, filter)
+            .select_only()
+            .select_column(media::Column::Id)
+            .filter(media::Column::Id.is_in(media_ids))
+            .into_tuple::<String>()
             .all(&self.connection)
             .await
         {
-            Ok(log_models) => {
-                let log_entries = log_models
-                    .into_iter()
-                    .map(|model| LogEntry {
-                        id: model.id,
-                        level: model.level,
-                        date: model.date,
-                        message: model.message,
-                    })
-                    .collect();
-                Ok(log_entries)
-            }
-            Err(_) => Err(GetLogError::InternalError),
+            Ok(media_ids) => Ok(media_ids),
+            Err(_) => Err(GetPreviewError::InternalError),
         }
     }
 
-    pub async fn add_log(
+    async fn get_media_previews(
         &self,
         user_id: String,
-        level: LogLevel,
-        date: i64,
-        message: String,
-    ) -> Result<(), AddLogError> {
-        let new_log = log::ActiveModel {
-            user_id: Set(user_id),
-            level: Set(level.to_string()),
-            date: Set(date),
-            message: Set(message),
-            ..Default::default()
-        };
-
-        match new_log.insert(&self.connection).await {
-            Ok(_) => Ok(()),
-            Err(_) => Err(AddLogError::InternalError),
+        media_ids: Vec<String>,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
+        match media::Entity::find()
+            .filter(media::Column::UserId.eq(user_id))
+            .filter(media::Column::Deleted.eq(false))
+            .filter(media::Column::Id.is_in(media_ids))
+            .select_only()
+            .columns(PREVIEW_COLUMNS)
+            .into_model::<MediaPreview>()
+            .all(&self.connection)
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(_) => Err(GetPreviewError::InternalError),
         }
     }
 
-    pub async fn get_faces(&self, user_id: String) -> Result<(Vec<Face>, Vec<Cluster>), DbErr> {
-        // Query 1: Get all clusters *without* faces
-        let clusters_without_faces: Vec<(cluster::Model, Option<face::Model>)> =
-            cluster::Entity::find()
-                .filter(cluster::Column::UserId.eq(user_id.clone()))
-                .filter(face::Column::Id.is_null())
-                .find_also_related(face::Entity)
-                .all(&self.connection)
-                .await?;
+    async fn search_media(
+        &self,
+        user_id: String,
+        filter: MediaFilter,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
+        let offset = (page - 1) * page_size;
 
-        // Query 2: Get distinct clusters *with* faces based on FaceId
-        let clusters_with_faces: Vec<(cluster::Model, Option<face::Model>)> =
-            cluster::Entity::find()
-                .filter(cluster::Column::UserId.eq(user_id.clone()))
-                .filter(face::Column::Id.is_not_null())
-                .find_also_related(face::Entity)
-                .distinct_on([cluster::Column::FaceId])
-                .all(&self.connection)
-                .await?;
+        match Self::filtered_media(user_id, filter)
+            .order_by_desc(media::Column::CreatedAt)
+            .select_only()
+            .columns(PREVIEW_COLUMNS)
+            .offset(offset)
+            .limit(page_size)
+            .into_model::<MediaPreview>()
+            .all(&self.connection)
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(_) => Err(GetPreviewError::InternalError),
+        }
+    }
 
-        // Combine results
-        let faces_with_clusters: Vec<(cluster::Model, Option<face::Model>)> =
-            clusters_without_faces
-                .into_iter()
-                .chain(clusters_with_faces.into_iter())
-                .collect();
+    async fn insert_metadata(
+        &self,
+        media_id: String,
+        metadata: MediaMetadata,
+    ) -> Result<(), String> {
+        let Ok(media) = media::Entity::find_by_id(&media_id)
+            .one(&self.connection)
+            .await
+        else {
+            return Err(format!(
+                "Database error while fetching media: {}",
+                media_id.clone()
+            ));
+        };
+
+        let Some(media) = media else {
+            return Err(format!(
+                "Could not find media: {} in the database",
+                media_id.clone()
+            ));
+        };
+
+        // What the user edited wins over the file
+        let location_edited = media.location_edited;
+        let capture_time_edited = media.capture_time_edited;
+        let annotations_edited = media.annotations_edited;
+        let mut media: media::ActiveModel = media.into();
+        if !location_edited {
+            media.longitude = Set(metadata.longitude);
+            media.latitude = Set(metadata.latitude);
+        }
+        media.image_width = Set(metadata.image_width);
+        media.image_length = Set(metadata.image_length);
+        media.make = Set(metadata.make);
+        media.model = Set(metadata.model);
+        media.fnumber = Set(metadata.fnumber);
+        media.exposure_time = Set(metadata.exposure_time);
+        media.photographic_sensitivity = Set(metadata.photographic_sensitivity);
+        media.orientation = Set(metadata.orientation);
+        media.lens_make = Set(metadata.lens_make);
+        media.lens_model = Set(metadata.lens_model);
+        media.focal_length = Set(metadata.focal_length);
+        media.focal_length_35mm = Set(metadata.focal_length_35mm);
+        media.altitude = Set(metadata.altitude);
+        media.image_direction = Set(metadata.image_direction);
+        media.flash = Set(metadata.flash);
+        media.white_balance = Set(metadata.white_balance);
+        media.metering_mode = Set(metadata.metering_mode);
+        media.exposure_program = Set(metadata.exposure_program);
+        media.exposure_bias = Set(metadata.exposure_bias);
+        media.software = Set(metadata.software);
+        media.artist = Set(metadata.artist);
+        media.exif = Set(metadata.exif);
+        if !annotations_edited {
+            media.rating = Set(metadata.rating);
+            media.title = Set(metadata.title);
+            media.description = Set(metadata.description);
+        }
+        media.keywords = Set(metadata.keywords);
+        media.people = Set(metadata.people);
+        // Moving the media in the timeline counts as a change for the clients to sync
+        if let Some(capture_time) = metadata.capture_time.filter(|_| !capture_time_edited) {
+            media.created_at = Set(capture_time.created_at);
+            media.utc_offset = Set(capture_time.utc_offset);
+            media.last_modified_at = Set(Utc::now().timestamp_millis());
+        }
+
+        match media.update(&self.connection).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!(
+                "Could not update media preview id for: {}",
+                media_id.clone()
+            )),
+        }
+    }
+
+    async fn edit_media(
+        &self,
+        user_id: String,
+        media_ids: Vec<String>,
+        edit: MediaEdit,
+    ) -> Result<(), EditMediaError> {
+        let media_ids: Vec<String> = media_ids
+            .into_iter()
+            .collect::<HashSet<String>>()
+            .into_iter()
+            .collect();
+        let transaction = self.connection.begin().await?;
+        let owned = media::Entity::find()
+            .filter(media::Column::Id.is_in(media_ids.clone()))
+            .filter(media::Column::UserId.eq(user_id))
+            .filter(media::Column::Deleted.eq(false))
+            .count(&transaction)
+            .await?;
+        if owned != media_ids.len() as u64 {
+            return Err(EditMediaError::NotFound);
+        }
+
+        let mut update = media::Entity::update_many()
+            .col_expr(
+                media::Column::LastModifiedAt,
+                Expr::value(Utc::now().timestamp_millis()),
+            )
+            .filter(media::Column::Id.is_in(media_ids));
+        if let Some(latitude) = edit.latitude {
+            update = update.col_expr(media::Column::Latitude, Expr::value(latitude));
+        }
+        if let Some(longitude) = edit.longitude {
+            update = update.col_expr(media::Column::Longitude, Expr::value(longitude));
+        }
+        if edit.edits_location() {
+            update = update.col_expr(media::Column::LocationEdited, Expr::value(true));
+        }
+        if let Some(created_at) = edit.created_at {
+            update = update.col_expr(media::Column::CreatedAt, Expr::value(created_at));
+        }
+        if let Some(shift) = edit.shift {
+            update = update.col_expr(
+                media::Column::CreatedAt,
+                Expr::col(media::Column::CreatedAt).add(shift),
+            );
+        }
+        if let Some(utc_offset) = edit.utc_offset {
+            update = update.col_expr(media::Column::UtcOffset, Expr::value(utc_offset));
+        }
+        if edit.edits_capture_time() {
+            update = update.col_expr(media::Column::CaptureTimeEdited, Expr::value(true));
+        }
+        if edit.edits_annotations() {
+            update = update.col_expr(media::Column::AnnotationsEdited, Expr::value(true));
+        }
+        if let Some(title) = edit.title {
+            update = update.col_expr(media::Column::Title, Expr::value(title));
+        }
+        if let Some(description) = edit.description {
+            update = update.col_expr(media::Column::Description, Expr::value(description));
+        }
+        if let Some(rating) = edit.rating {
+            update = update.col_expr(media::Column::Rating, Expr::value(rating));
+        }
+        update.exec(&transaction).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for DbManager {
+    async fn get_user(&self, username: String) -> Result<user::Model, GetUserError> {
+        match user::Entity::find()
+            .filter(user::Column::Username.eq(username))
+            .one(&self.connection)
+            .await
+        {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err(GetUserError::NotFound),
+            Err(..) => Err(GetUserError::InternalError),
+        }
+    }
+
+    async fn add_user(
+        &self,
+        id: String,
+        username: String,
+        password: String,
+    ) -> Result<(), AddUserError> {
+        match self.get_user(username.clone()).await {
+            Ok(_) => Err(AddUserError::AlreadyExists),
+            Err(GetUserError::NotFound) => {
+                let new_user = user::ActiveModel {
+                    id: Set(id),
+                    username: Set(username),
+                    password: Set(password),
+                };
+
+                match new_user.insert(&self.connection).await {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(AddUserError::InternalError),
+                }
+            }
+            Err(GetUserError::InternalError) => Err(AddUserError::InternalError),
+        }
+    }
+}
+
+#[async_trait]
+impl LogRepository for DbManager {
+    async fn get_logs(
+        &self,
+        user_id: String,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<LogEntry>, GetLogError> {
+        let offset = (page - 1) * page_size;
+
+        match log::Entity::find()
+            .order_by_desc(log::Column::Date)
+            .filter(log::Column::UserId.eq(user_id))
+            .offset(offset)
+            .limit(page_size)
+            .all(&self.connection)
+            .await
+        {
+            Ok(log_models) => {
+                let log_entries = log_models
+                    .into_iter()
+                    .map(|model| LogEntry {
+                        id: model.id,
+                        level: model.level,
+                        date: model.date,
+                        message: model.message,
+                    })
+                    .collect();
+                Ok(log_entries)
+            }
+            Err(_) => Err(GetLogError::InternalError),
+        }
+    }
+
+    async fn add_log(
+        &self,
+        user_id: String,
+        level: LogLevel,
+        date: i64,
+        message: String,
+    ) -> Result<(), AddLogError> {
+        let new_log = log::ActiveModel {
+            user_id: Set(user_id),
+            level: Set(level.to_string()),
+            date: Set(date),
+            message: Set(message),
+            ..Default::default()
+        };
+
+        match new_log.insert(&self.connection).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(AddLogError::InternalError),
+        }
+    }
+}
+
+#[async_trait]
+impl FaceRepository for DbManager {
+    async fn get_faces(
+        &self,
+        user_id: String,
+        hidden: bool,
+    ) -> Result<(Vec<Face>, Vec<Cluster>), DbErr> {
+        // Query 1: Get all clusters *without* faces
+        let clusters_without_faces: Vec<(cluster::Model, Option<face::Model>)> =
+            cluster::Entity::find()
+                .filter(cluster::Column::UserId.eq(user_id.clone()))
+                .filter(cluster::Column::Hidden.eq(hidden))
+                .filter(face::Column::Id.is_null())
+                .find_also_related(face::Entity)
+                .all(&self.connection)
+                .await?;
+
+        // Query 2: Get distinct clusters *with* faces based on FaceId
+        let clusters_with_faces: Vec<(cluster::Model, Option<face::Model>)> =
+            cluster::Entity::find()
+                .filter(cluster::Column::UserId.eq(user_id.clone()))
+                .filter(face::Column::Id.is_not_null())
+                .filter(face::Column::UserId.eq(user_id.clone()))
+                .filter(face::Column::Hidden.eq(hidden))
+                .find_also_related(face::Entity)
+                .distinct_on([cluster::Column::FaceId])
+                .all(&self.connection)
+                .await?;
+
+        // Combine results
+        let faces_with_clusters: Vec<(cluster::Model, Option<face::Model>)> =
+            clusters_without_faces
+                .into_iter()
+                .chain(clusters_with_faces)
+                .collect();
 
         let mut faces: Vec<Face> = vec![];
         let mut clusters: Vec<Cluster> = vec![];
         for (cluster, face_opt) in faces_with_clusters {
             if let Some(face) = face_opt {
-                let (photo_id, bbox) = if let Some(featured_photo_id) = &face.featured_photo_id {
-                    media_face::Entity::find()
-                        .select_only()
-                        .select_column(media_face::Column::MediaId)
-                        .select_column(media_face::Column::FaceBoundingBox)
-                        .filter(media_face::Column::MediaId.eq(featured_photo_id.clone()))
-                        .into_tuple()
-                        .one(&self.connection)
-                        .await?
-                        .unwrap()
-                } else {
-                    media_face::Entity::find()
-                        .select_only()
-                        .select_column(media_face::Column::MediaId)
-                        .select_column(media_face::Column::FaceBoundingBox)
-                        .filter(media_face::Column::ClusterId.eq(cluster.id))
-                        .into_tuple()
-                        .one(&self.connection)
-                        .await?
-                        .unwrap()
+                // The featured photo may show several people, so the detection has to be one of this face
+                let featured = match &face.featured_photo_id {
+                    Some(featured_photo_id) => {
+                        media_face::Entity::find()
+                            .join(JoinType::InnerJoin, media_face::Relation::Cluster.def())
+                            .filter(cluster::Column::FaceId.eq(face.id))
+                            .filter(media_face::Column::MediaId.eq(featured_photo_id.clone()))
+                            .select_only()
+                            .select_column(media_face::Column::MediaId)
+                            .select_column(media_face::Column::FaceBoundingBox)
+                            .select_column(media_face::Column::CropId)
+                            .into_tuple()
+                            .one(&self.connection)
+                            .await?
+                    }
+                    None => None,
+                };
+                // Any detection of any cluster of the face, the face is still listed without one
+                let photo = match featured {
+                    Some(featured) => Some(featured),
+                    None => {
+                        media_face::Entity::find()
+                            .join(JoinType::InnerJoin, media_face::Relation::Cluster.def())
+                            .filter(cluster::Column::FaceId.eq(face.id))
+                            .order_by_asc(media_face::Column::Id)
+                            .select_only()
+                            .select_column(media_face::Column::MediaId)
+                            .select_column(media_face::Column::FaceBoundingBox)
+                            .select_column(media_face::Column::CropId)
+                            .into_tuple()
+                            .one(&self.connection)
+                            .await?
+                    }
+                };
+                let (photo_id, bbox, crop_id) = match photo {
+                    Some((photo_id, bbox, crop_id)) => (Some(photo_id), bbox, crop_id),
+                    None => (None, vec![], None),
                 };
 
                 faces.push(Face {
@@ -450,34 +1053,41 @@ impl DbManager {
                     name: face.name.clone(),
                     photo_id,
                     bbox,
+                    crop_id,
                 });
             } else {
-                let (photo_id, bbox) = media_face::Entity::find()
+                // Clusters are emptied and removed in the same transaction, one is only
+                // seen without a detection while that happens
+                let Some((photo_id, bbox, crop_id)) = media_face::Entity::find()
                     .select_only()
                     .select_column(media_face::Column::MediaId)
                     .select_column(media_face::Column::FaceBoundingBox)
+                    .select_column(media_face::Column::CropId)
                     .filter(media_face::Column::ClusterId.eq(cluster.id))
                     .into_tuple()
                     .one(&self.connection)
                     .await?
-                    .unwrap();
+                else {
+                    continue;
+                };
                 clusters.push(Cluster {
                     cluster_id: cluster.id,
                     photo_id,
                     bbox,
+                    crop_id,
                 });
             }
         }
         Ok((faces, clusters))
     }
 
-    pub async fn get_cluster_previews(
+    async fn get_cluster_previews(
         &self,
         user_id: String,
         cluster_id: i32,
         page: u64,
         page_size: u64,
-    ) -> Result<Vec<(String, Option<String>)>, GetPreviewError> {
+    ) -> Result<Vec<DetectionPreview>, GetPreviewError> {
         let offset = (page - 1) * page_size;
 
         match media_face::Entity::find()
@@ -487,11 +1097,11 @@ impl DbManager {
             .filter(media::Column::Deleted.eq(false))
             .order_by_desc(media::Column::CreatedAt)
             .select_only()
-            .column_as(media::Column::Id, "media_id")
-            .column_as(media::Column::PreviewId, "preview_id")
+            .columns(PREVIEW_COLUMNS)
+            .column(media_face::Column::CropId)
             .offset(offset)
             .limit(page_size)
-            .into_tuple::<(String, Option<String>)>()
+            .into_model::<DetectionPreview>()
             .all(&self.connection)
             .await
         {
@@ -500,28 +1110,35 @@ impl DbManager {
         }
     }
 
-    pub async fn get_face_previews(
+    async fn get_face_previews(
         &self,
         user_id: String,
         face_id: i32,
         page: u64,
         page_size: u64,
-    ) -> Result<Vec<(String, Option<String>)>, GetPreviewError> {
+    ) -> Result<Vec<DetectionPreview>, GetPreviewError> {
+        match Self::user_has_face(&self.connection, &user_id, face_id).await {
+            Ok(true) => {}
+            Ok(false) => return Err(GetPreviewError::NotFound),
+            Err(_) => return Err(GetPreviewError::InternalError),
+        }
+
         let offset = (page - 1) * page_size;
 
         match media_face::Entity::find()
             .join(JoinType::InnerJoin, media_face::Relation::Cluster.def())
             .filter(cluster::Column::FaceId.eq(face_id))
+            .filter(cluster::Column::UserId.eq(user_id.clone()))
             .join(JoinType::LeftJoin, media_face::Relation::Media.def())
             .filter(media::Column::UserId.eq(user_id))
             .filter(media::Column::Deleted.eq(false))
             .order_by_desc(media::Column::CreatedAt)
             .select_only()
-            .column_as(media::Column::Id, "media_id")
-            .column_as(media::Column::PreviewId, "preview_id")
+            .columns(PREVIEW_COLUMNS)
+            .column(media_face::Column::CropId)
             .offset(offset)
             .limit(page_size)
-            .into_tuple::<(String, Option<String>)>()
+            .into_model::<DetectionPreview>()
             .all(&self.connection)
             .await
         {
@@ -530,60 +1147,61 @@ impl DbManager {
         }
     }
 
-    pub async fn insert_metadata(
+    async fn get_people_previews(
         &self,
-        media_id: String,
-        longitude: Option<f64>,
-        latitude: Option<f64>,
-        image_width: Option<i32>,
-        image_length: Option<i32>,
-        make: Option<String>,
-        model: Option<String>,
-        fnumber: Option<String>,
-        exposure_time: Option<String>,
-        photographic_sensitivity: Option<String>,
-        orientation: Option<i32>,
-    ) -> Result<(), String> {
-        let Ok(media) = media::Entity::find_by_id(&media_id)
-            .one(&self.connection)
+        user_id: String,
+        people: PeopleQuery,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
+        let face_ids: HashSet<i32> = people
+            .all
+            .iter()
+            .chain(&people.any)
+            .chain(&people.none)
+            .copied()
+            .collect();
+        let owned_faces = face::Entity::find()
+            .filter(face::Column::Id.is_in(face_ids.iter().copied()))
+            .filter(face::Column::UserId.eq(user_id.clone()))
+            .count(&self.connection)
             .await
-        else {
-            return Err(format!(
-                "Database error while fetching media: {}",
-                media_id.clone()
-            ));
-        };
-
-        let Some(media) = media else {
-            return Err(format!(
-                "Could not find media: {} in the database",
-                media_id.clone()
-            ));
-        };
-
-        let mut media: media::ActiveModel = media.into();
-        media.longitude = Set(longitude);
-        media.latitude = Set(latitude);
-        media.image_width = Set(image_width);
-        media.image_length = Set(image_length);
-        media.make = Set(make);
-        media.model = Set(model);
-        media.fnumber = Set(fnumber);
-        media.exposure_time = Set(exposure_time);
-        media.photographic_sensitivity = Set(photographic_sensitivity);
-        media.orientation = Set(orientation);
+            .map_err(|_| GetPreviewError::InternalError)?;
+        if owned_faces != face_ids.len() as u64 {
+            return Err(GetPreviewError::NotFound);
+        }
 
-        match media.update(&self.connection).await {
-            Ok(_) => Ok(()),
-            Err(_) => Err(format!(
-                "Could not update media preview id for: {}",
-                media_id.clone()
-            )),
+        let mut query = media::Entity::find()
+            .filter(media::Column::UserId.eq(user_id))
+            .filter(media::Column::Deleted.eq(false));
+        if !people.all.is_empty() {
+            let all: HashSet<i32> = people.all.iter().copied().collect();
+            query = query.filter(
+                media::Column::Id.in_subquery(Self::media_with_faces(&people.all, all.len())),
+            );
+        }
+        if !people.any.is_empty() {
+            query =
+                query.filter(media::Column::Id.in_subquery(Self::media_with_faces(&people.any, 1)));
+        }
+        if !people.none.is_empty() {
+            query = query
+                .filter(media::Column::Id.not_in_subquery(Self::media_with_faces(&people.none, 1)));
         }
-    }
 
+        query
+            .order_by_desc(media::Column::CreatedAt)
+            .select_only()
+            .columns(PREVIEW_COLUMNS)
+            .offset((page - 1) * page_size)
+            .limit(page_size)
+            .into_model::<MediaPreview>()
+            .all(&self.connection)
+            .await
+            .map_err(|_| GetPreviewError::InternalError)
+    }
 
-    pub async fn insert_face(
+    async fn insert_face(
         &self,
         user_id: String,
         cluster_ids: Vec<i32>,
@@ -591,13 +1209,14 @@ impl DbManager {
     ) -> Result<(), DbErr> {
         let new_face = face::ActiveModel {
             name: Set(name),
+            user_id: Set(user_id.clone()),
             ..Default::default()
         };
         let face_result = face::Entity::insert(new_face)
             .exec(&self.connection)
             .await?;
         let face_id = face_result.last_insert_id;
-    
+
         for cluster_id in cluster_ids {
             cluster::Entity::update_many()
                 .filter(
@@ -612,33 +1231,825 @@ impl DbManager {
                 .exec(&self.connection)
                 .await?;
         }
-    
+
         Ok(())
     }
 
-}
-
-pub enum GetPreviewError {
-    NotFound,
-    InternalError,
-}
+    async fn get_media_with_missing_face_crops(&self, limit: u64) -> Result<Vec<String>, DbErr> {
+        media_face::Entity::find()
+            .join(JoinType::InnerJoin, media_face::Relation::Media.def())
+            .filter(media_face::Column::CropId.is_null())
+            .filter(media_face::Column::CropAttempts.lt(FACE_CROP_MAX_ATTEMPTS))
+            .filter(media::Column::Deleted.eq(false))
+            .select_only()
+            .column(media_face::Column::MediaId)
+            .distinct()
+            .limit(limit)
+            .into_tuple()
+            .all(&self.connection)
+            .await
+    }
 
-pub enum GetLogError {
-    InternalError,
-    NotFound,
-}
+    async fn get_missing_face_crops(
+        &self,
+        media_id: String,
+    ) -> Result<Vec<(i32, Vec<i32>)>, DbErr> {
+        media_face::Entity::find()
+            .filter(media_face::Column::MediaId.eq(media_id))
+            .filter(media_face::Column::CropId.is_null())
+            .filter(media_face::Column::CropAttempts.lt(FACE_CROP_MAX_ATTEMPTS))
+            .order_by_asc(media_face::Column::Id)
+            .select_only()
+            .column(media_face::Column::Id)
+            .column(media_face::Column::FaceBoundingBox)
+            .into_tuple()
+            .all(&self.connection)
+            .await
+    }
 
-pub enum AddLogError {
-    InternalError,
-}
+    async fn set_face_crop(&self, media_face_id: i32, crop_id: String) -> Result<(), DbErr> {
+        media_face::Entity::update_many()
+            .col_expr(media_face::Column::CropId, Expr::value(crop_id))
+            .filter(media_face::Column::Id.eq(media_face_id))
+            .exec(&self.connection)
+            .await?;
+        Ok(())
+    }
 
-pub enum GetUserError {
-    NotFound,
-    InternalError,
-}
+    async fn add_face_crop_failures(&self, media_face_ids: Vec<i32>) -> Result<(), DbErr> {
+        if media_face_ids.is_empty() {
+            return Ok(());
+        }
+        media_face::Entity::update_many()
+            .col_expr(
+                media_face::Column::CropAttempts,
+                Expr::col(media_face::Column::CropAttempts).add(1),
+            )
+            .filter(media_face::Column::Id.is_in(media_face_ids))
+            .exec(&self.connection)
+            .await?;
+        Ok(())
+    }
 
-pub enum AddUserError {
-    AlreadyExists,
+    async fn get_face_boxes(&self, media_id: String) -> Result<Vec<Vec<i32>>, DbErr> {
+        media_face::Entity::find()
+            .filter(media_face::Column::MediaId.eq(media_id))
+            .order_by_asc(media_face::Column::Id)
+            .select_only()
+            .column(media_face::Column::FaceBoundingBox)
+            .into_tuple()
+            .all(&self.connection)
+            .await
+    }
+
+    async fn get_face_embeddings(&self, user_id: String) -> Result<Vec<FaceEmbedding>, DbErr> {
+        type Detection = (
+            i32,
+            Option<String>,
+            Option<i32>,
+            Option<i32>,
+            Option<bool>,
+            Option<bool>,
+        );
+        let detections: Vec<Detection> = media_face::Entity::find()
+            .join(JoinType::InnerJoin, media_face::Relation::Media.def())
+            .join(JoinType::LeftJoin, media_face::Relation::Cluster.def())
+            .join(JoinType::LeftJoin, cluster::Relation::Face.def())
+            .filter(media::Column::UserId.eq(user_id))
+            .filter(media::Column::Deleted.eq(false))
+            .filter(media_face::Column::Embedding.is_not_null())
+            .order_by_asc(media_face::Column::Id)
+            .select_only()
+            .column(media_face::Column::Id)
+            .column_as(
+                Expr::col((media_face::Entity, media_face::Column::Embedding))
+                    .cast_as(Alias::new("text")),
+                "embedding",
+            )
+            .column(media_face::Column::ClusterId)
+            .column(cluster::Column::FaceId)
+            .column_as(cluster::Column::Hidden, "cluster_hidden")
+            .column_as(face::Column::Hidden, "face_hidden")
+            .into_tuple()
+            .all(&self.connection)
+            .await?;
+
+        Ok(detections
+            .into_iter()
+            .filter_map(
+                |(media_face_id, embedding, cluster_id, face_id, cluster_hidden, face_hidden)| {
+                    Some(FaceEmbedding {
+                        media_face_id,
+                        embedding: vector::parse(&embedding?)?,
+                        cluster_id,
+                        face_id,
+                        hidden: cluster_hidden.unwrap_or(false) || face_hidden.unwrap_or(false),
+                    })
+                },
+            )
+            .collect())
+    }
+
+    async fn get_users_with_unclustered_faces(&self) -> Result<Vec<String>, DbErr> {
+        media_face::Entity::find()
+            .join(JoinType::InnerJoin, media_face::Relation::Media.def())
+            .filter(media_face::Column::ClusteredAt.is_null())
+            .filter(media_face::Column::Embedding.is_not_null())
+            .filter(media::Column::Deleted.eq(false))
+            .select_only()
+            .column(media::Column::UserId)
+            .distinct()
+            .into_tuple()
+            .all(&self.connection)
+            .await
+    }
+
+    async fn save_clusters(
+        &self,
+        user_id: String,
+        assignments: Vec<(i32, ClusterAssignment)>,
+        clustered: Vec<i32>,
+    ) -> Result<(), DbErr> {
+        let transaction = self.connection.begin().await?;
+
+        let mut new_clusters: HashMap<usize, i32> = HashMap::new();
+        for (media_face_id, assignment) in assignments {
+            let cluster_id = match assignment {
+                ClusterAssignment::Cluster(cluster_id) => Some(cluster_id),
+                ClusterAssignment::Unclustered => None,
+                ClusterAssignment::NewCluster(number) => match new_clusters.get(&number) {
+                    Some(cluster_id) => Some(*cluster_id),
+                    None => {
+                        let cluster = cluster::ActiveModel {
+                            user_id: Set(user_id.clone()),
+                            face_id: Set(None),
+                            ..Default::default()
+                        };
+                        let cluster_id = cluster::Entity::insert(cluster)
+                            .exec(&transaction)
+                            .await?
+                            .last_insert_id;
+                        new_clusters.insert(number, cluster_id);
+                        Some(cluster_id)
+                    }
+                },
+            };
+            media_face::Entity::update_many()
+                .col_expr(media_face::Column::ClusterId, Expr::value(cluster_id))
+                .filter(media_face::Column::Id.eq(media_face_id))
+                .exec(&transaction)
+                .await?;
+        }
+
+        // Unnamed clusters whose detections all moved away
+        cluster::Entity::delete_many()
+            .filter(cluster::Column::UserId.eq(user_id))
+            .filter(cluster::Column::FaceId.is_null())
+            .filter(
+                cluster::Column::Id.not_in_subquery(
+                    Query::select()
+                        .column(media_face::Column::ClusterId)
+                        .from(media_face::Entity)
+                        .and_where(media_face::Column::ClusterId.is_not_null())
+                        .to_owned(),
+                ),
+            )
+            .exec(&transaction)
+            .await?;
+
+        // In chunks, a statement can't bind more than 65535 values
+        let now = Utc::now().timestamp_millis();
+        for chunk in clustered.chunks(10000) {
+            media_face::Entity::update_many()
+                .col_expr(media_face::Column::ClusteredAt, Expr::value(now))
+                .filter(media_face::Column::Id.is_in(chunk.to_vec()))
+                .filter(media_face::Column::ClusteredAt.is_null())
+                .exec(&transaction)
+                .await?;
+        }
+
+        transaction.commit().await
+    }
+
+    async fn rename_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        name: String,
+    ) -> Result<(), UpdateFaceError> {
+        if !Self::user_has_face(&self.connection, &user_id, face_id).await? {
+            return Err(UpdateFaceError::NotFound);
+        }
+        face::Entity::update_many()
+            .col_expr(face::Column::Name, Expr::value(name))
+            .filter(face::Column::Id.eq(face_id))
+            .exec(&self.connection)
+            .await?;
+        Ok(())
+    }
+
+    async fn merge_faces(
+        &self,
+        user_id: String,
+        face_id: i32,
+        merged_face_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        if face_id == merged_face_id {
+            return Err(UpdateFaceError::InvalidRequest);
+        }
+        let transaction = self.connection.begin().await?;
+        if !Self::user_has_face(&transaction, &user_id, face_id).await?
+            || !Self::user_has_face(&transaction, &user_id, merged_face_id).await?
+        {
+            return Err(UpdateFaceError::NotFound);
+        }
+        cluster::Entity::update_many()
+            .col_expr(cluster::Column::FaceId, Expr::value(face_id))
+            .filter(cluster::Column::FaceId.eq(merged_face_id))
+            .filter(cluster::Column::UserId.eq(user_id))
+            .exec(&transaction)
+            .await?;
+        face::Entity::delete_by_id(merged_face_id)
+            .exec(&transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_face(&self, user_id: String, face_id: i32) -> Result<(), UpdateFaceError> {
+        let transaction = self.connection.begin().await?;
+        if !Self::user_has_face(&transaction, &user_id, face_id).await? {
+            return Err(UpdateFaceError::NotFound);
+        }
+        let cluster_ids: Vec<i32> = cluster::Entity::find()
+            .filter(cluster::Column::FaceId.eq(face_id))
+            .filter(cluster::Column::UserId.eq(user_id.clone()))
+            .select_only()
+            .column(cluster::Column::Id)
+            .into_tuple()
+            .all(&transaction)
+            .await?;
+        cluster::Entity::update_many()
+            .col_expr(cluster::Column::FaceId, Expr::value(Option::<i32>::None))
+            .filter(cluster::Column::FaceId.eq(face_id))
+            .filter(cluster::Column::UserId.eq(user_id))
+            .exec(&transaction)
+            .await?;
+        Self::delete_empty_clusters(&transaction, cluster_ids).await?;
+        face::Entity::delete_by_id(face_id)
+            .exec(&transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn tag_face(
+        &self,
+        user_id: String,
+        media_id: String,
+        bbox: Vec<i32>,
+        target: TagTarget,
+    ) -> Result<(i32, i32), UpdateFaceError> {
+        let transaction = self.connection.begin().await?;
+        let media = media::Entity::find_by_id(media_id.clone())
+            .filter(media::Column::UserId.eq(user_id.clone()))
+            .filter(media::Column::Deleted.eq(false))
+            .count(&transaction)
+            .await?;
+        if media == 0 {
+            return Err(UpdateFaceError::NotFound);
+        }
+
+        let (face_id, cluster_id) = match target {
+            TagTarget::Face(face_id) => {
+                if !Self::user_has_face(&transaction, &user_id, face_id).await? {
+                    return Err(UpdateFaceError::NotFound);
+                }
+                let cluster_id = cluster::Entity::find()
+                    .filter(cluster::Column::FaceId.eq(face_id))
+                    .filter(cluster::Column::UserId.eq(user_id.clone()))
+                    .order_by_asc(cluster::Column::Id)
+                    .select_only()
+                    .column(cluster::Column::Id)
+                    .into_tuple::<i32>()
+                    .one(&transaction)
+                    .await?;
+                (face_id, cluster_id)
+            }
+            TagTarget::NewFace(name) => {
+                let face = face::ActiveModel {
+                    name: Set(name),
+                    user_id: Set(user_id.clone()),
+                    ..Default::default()
+                };
+                let face_id = face::Entity::insert(face)
+                    .exec(&transaction)
+                    .await?
+                    .last_insert_id;
+                (face_id, None)
+            }
+        };
+        // Faces keep their detections in clusters, one without any gets a new one
+        let cluster_id = match cluster_id {
+            Some(cluster_id) => cluster_id,
+            None => {
+                let cluster = cluster::ActiveModel {
+                    user_id: Set(user_id),
+                    face_id: Set(Some(face_id)),
+                    ..Default::default()
+                };
+                cluster::Entity::insert(cluster)
+                    .exec(&transaction)
+                    .await?
+                    .last_insert_id
+            }
+        };
+
+        let media_face = media_face::ActiveModel {
+            media_id: Set(media_id),
+            embedding: Set(None),
+            face_bounding_box: Set(bbox),
+            cluster_id: Set(Some(cluster_id)),
+            manual: Set(true),
+            ..Default::default()
+        };
+        let media_face_id = media_face::Entity::insert(media_face)
+            .exec(&transaction)
+            .await?
+            .last_insert_id;
+        transaction.commit().await?;
+        Ok((media_face_id, face_id))
+    }
+
+    async fn set_face_hidden(
+        &self,
+        user_id: String,
+        face_id: i32,
+        hidden: bool,
+    ) -> Result<(), UpdateFaceError> {
+        let result = face::Entity::update_many()
+            .col_expr(face::Column::Hidden, Expr::value(hidden))
+            .filter(face::Column::Id.eq(face_id))
+            .filter(face::Column::UserId.eq(user_id))
+            .exec(&self.connection)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(UpdateFaceError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn set_cluster_hidden(
+        &self,
+        user_id: String,
+        cluster_id: i32,
+        hidden: bool,
+    ) -> Result<(), UpdateFaceError> {
+        let result = cluster::Entity::update_many()
+            .col_expr(cluster::Column::Hidden, Expr::value(hidden))
+            .filter(cluster::Column::Id.eq(cluster_id))
+            .filter(cluster::Column::UserId.eq(user_id))
+            .exec(&self.connection)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(UpdateFaceError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn add_cluster_to_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        cluster_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        if !Self::user_has_face(&self.connection, &user_id, face_id).await? {
+            return Err(UpdateFaceError::NotFound);
+        }
+        let result = cluster::Entity::update_many()
+            .col_expr(cluster::Column::FaceId, Expr::value(face_id))
+            .filter(cluster::Column::Id.eq(cluster_id))
+            .filter(cluster::Column::UserId.eq(user_id))
+            .exec(&self.connection)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(UpdateFaceError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn remove_cluster_from_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        cluster_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        let transaction = self.connection.begin().await?;
+        let result = cluster::Entity::update_many()
+            .col_expr(cluster::Column::FaceId, Expr::value(Option::<i32>::None))
+            .filter(cluster::Column::Id.eq(cluster_id))
+            .filter(cluster::Column::FaceId.eq(face_id))
+            .filter(cluster::Column::UserId.eq(user_id))
+            .exec(&transaction)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(UpdateFaceError::NotFound);
+        }
+        Self::delete_empty_clusters(&transaction, vec![cluster_id]).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn move_media_face(
+        &self,
+        user_id: String,
+        media_face_id: i32,
+        target: MediaFaceTarget,
+    ) -> Result<(), UpdateFaceError> {
+        let transaction = self.connection.begin().await?;
+        let Some(detection) = media_face::Entity::find_by_id(media_face_id)
+            .join(JoinType::InnerJoin, media_face::Relation::Media.def())
+            .filter(media::Column::UserId.eq(user_id.clone()))
+            .one(&transaction)
+            .await?
+        else {
+            return Err(UpdateFaceError::NotFound);
+        };
+
+        let target_cluster_id = match target {
+            MediaFaceTarget::Cluster(cluster_id) => {
+                let Some(cluster) = cluster::Entity::find_by_id(cluster_id)
+                    .filter(cluster::Column::UserId.eq(user_id))
+                    .one(&transaction)
+                    .await?
+                else {
+                    return Err(UpdateFaceError::NotFound);
+                };
+                cluster.id
+            }
+            MediaFaceTarget::Face(face_id) => {
+                if !Self::user_has_face(&transaction, &user_id, face_id).await? {
+                    return Err(UpdateFaceError::NotFound);
+                }
+                let cluster = cluster::Entity::find()
+                    .filter(cluster::Column::FaceId.eq(face_id))
+                    .filter(cluster::Column::UserId.eq(user_id.clone()))
+                    .order_by_asc(cluster::Column::Id)
+                    .one(&transaction)
+                    .await?;
+                match cluster {
+                    Some(cluster) => cluster.id,
+                    // Every cluster of the face was removed from it, the detection starts a new one
+                    None => {
+                        let cluster = cluster::ActiveModel {
+                            user_id: Set(user_id),
+                            face_id: Set(Some(face_id)),
+                            ..Default::default()
+                        };
+                        cluster::Entity::insert(cluster)
+                            .exec(&transaction)
+                            .await?
+                            .last_insert_id
+                    }
+                }
+            }
+        };
+        if detection.cluster_id == Some(target_cluster_id) {
+            return Ok(());
+        }
+
+        media_face::Entity::update_many()
+            .col_expr(
+                media_face::Column::ClusterId,
+                Expr::value(target_cluster_id),
+            )
+            .filter(media_face::Column::Id.eq(media_face_id))
+            .exec(&transaction)
+            .await?;
+
+        if let Some(previous_cluster_id) = detection.cluster_id {
+            Self::delete_empty_clusters(&transaction, vec![previous_cluster_id]).await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_rejected_suggestions(&self, user_id: String) -> Result<Vec<(i32, i32)>, DbErr> {
+        face_suggestion::Entity::find()
+            .join(JoinType::InnerJoin, face_suggestion::Relation::Face.def())
+            .filter(face::Column::UserId.eq(user_id))
+            .filter(face_suggestion::Column::Rejected.eq(true))
+            .select_only()
+            .column(face_suggestion::Column::MediaFaceId)
+            .column(face_suggestion::Column::FaceId)
+            .into_tuple()
+            .all(&self.connection)
+            .await
+    }
+
+    async fn save_face_suggestions(
+        &self,
+        user_id: String,
+        suggestions: Vec<NewFaceSuggestion>,
+    ) -> Result<(), DbErr> {
+        let transaction = self.connection.begin().await?;
+        face_suggestion::Entity::delete_many()
+            .filter(face_suggestion::Column::Rejected.eq(false))
+            .filter(
+                face_suggestion::Column::FaceId.in_subquery(
+                    Query::select()
+                        .column(face::Column::Id)
+                        .from(face::Entity)
+                        .and_where(face::Column::UserId.eq(user_id))
+                        .to_owned(),
+                ),
+            )
+            .exec(&transaction)
+            .await?;
+
+        if !suggestions.is_empty() {
+            // Rejected suggestions are kept as they are
+            face_suggestion::Entity::insert_many(suggestions.into_iter().map(|suggestion| {
+                face_suggestion::ActiveModel {
+                    media_face_id: Set(suggestion.media_face_id),
+                    face_id: Set(suggestion.face_id),
+                    distance: Set(suggestion.distance),
+                    rejected: Set(false),
+                    ..Default::default()
+                }
+            }))
+            .on_conflict(
+                sea_query::OnConflict::columns([
+                    face_suggestion::Column::MediaFaceId,
+                    face_suggestion::Column::FaceId,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .do_nothing()
+            .exec(&transaction)
+            .await?;
+        }
+        transaction.commit().await
+    }
+
+    async fn get_face_suggestions(&self, user_id: String) -> Result<Vec<FaceSuggestion>, DbErr> {
+        face_suggestion::Entity::find()
+            .join(JoinType::InnerJoin, face_suggestion::Relation::Face.def())
+            .join(
+                JoinType::InnerJoin,
+                face_suggestion::Relation::MediaFace.def(),
+            )
+            .join(JoinType::InnerJoin, media_face::Relation::Media.def())
+            .join(JoinType::LeftJoin, media_face::Relation::Cluster.def())
+            .filter(face::Column::UserId.eq(user_id.clone()))
+            .filter(media::Column::UserId.eq(user_id))
+            .filter(media::Column::Deleted.eq(false))
+            .filter(face_suggestion::Column::Rejected.eq(false))
+            // Detections that were named in the meantime are no longer suggested
+            .filter(cluster::Column::FaceId.is_null())
+            .order_by_asc(face_suggestion::Column::Distance)
+            .order_by_asc(face_suggestion::Column::Id)
+            .select_only()
+            .column_as(face_suggestion::Column::Id, "suggestion_id")
+            .column(face_suggestion::Column::MediaFaceId)
+            .column(media_face::Column::MediaId)
+            .column_as(media_face::Column::FaceBoundingBox, "bbox")
+            .column(face_suggestion::Column::FaceId)
+            .column(face::Column::Name)
+            .column(face_suggestion::Column::Distance)
+            .into_model()
+            .all(&self.connection)
+            .await
+    }
+
+    async fn accept_face_suggestion(
+        &self,
+        user_id: String,
+        suggestion_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        let Some(suggestion) = face_suggestion::Entity::find_by_id(suggestion_id)
+            .join(JoinType::InnerJoin, face_suggestion::Relation::Face.def())
+            .filter(face::Column::UserId.eq(user_id.clone()))
+            .filter(face_suggestion::Column::Rejected.eq(false))
+            .one(&self.connection)
+            .await?
+        else {
+            return Err(UpdateFaceError::NotFound);
+        };
+        self.move_media_face(
+            user_id,
+            suggestion.media_face_id,
+            MediaFaceTarget::Face(suggestion.face_id),
+        )
+        .await?;
+        // The detection now belongs to a face, the other suggestions for it are moot
+        face_suggestion::Entity::delete_many()
+            .filter(face_suggestion::Column::MediaFaceId.eq(suggestion.media_face_id))
+            .filter(face_suggestion::Column::Rejected.eq(false))
+            .exec(&self.connection)
+            .await?;
+        Ok(())
+    }
+
+    async fn reject_face_suggestion(
+        &self,
+        user_id: String,
+        suggestion_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        let result = face_suggestion::Entity::update_many()
+            .col_expr(face_suggestion::Column::Rejected, Expr::value(true))
+            .filter(face_suggestion::Column::Id.eq(suggestion_id))
+            .filter(
+                face_suggestion::Column::FaceId.in_subquery(
+                    Query::select()
+                        .column(face::Column::Id)
+                        .from(face::Entity)
+                        .and_where(face::Column::UserId.eq(user_id))
+                        .to_owned(),
+                ),
+            )
+            .exec(&self.connection)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(UpdateFaceError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn set_featured_photo(
+        &self,
+        user_id: String,
+        face_id: i32,
+        media_id: String,
+    ) -> Result<(), UpdateFaceError> {
+        if !Self::user_has_face(&self.connection, &user_id, face_id).await? {
+            return Err(UpdateFaceError::NotFound);
+        }
+        let detections = media_face::Entity::find()
+            .join(JoinType::InnerJoin, media_face::Relation::Cluster.def())
+            .join(JoinType::InnerJoin, media_face::Relation::Media.def())
+            .filter(cluster::Column::FaceId.eq(face_id))
+            .filter(media::Column::Id.eq(media_id.clone()))
+            .filter(media::Column::UserId.eq(user_id))
+            .filter(media::Column::Deleted.eq(false))
+            .count(&self.connection)
+            .await?;
+        if detections == 0 {
+            return Err(UpdateFaceError::InvalidRequest);
+        }
+        face::Entity::update_many()
+            .col_expr(face::Column::FeaturedPhotoId, Expr::value(media_id))
+            .filter(face::Column::Id.eq(face_id))
+            .exec(&self.connection)
+            .await?;
+        Ok(())
+    }
+}
+
+// A face detection as seen by the clustering
+#[derive(Debug, Clone)]
+pub struct FaceEmbedding {
+    pub media_face_id: i32,
+    pub embedding: Vec<f32>,
+    pub cluster_id: Option<i32>,
+    // Set when the cluster of the detection was named by the user
+    pub face_id: Option<i32>,
+    // Set when the user hid the cluster or the face of the detection
+    pub hidden: bool,
+}
+
+// Where the clustering puts a detection. New clusters are told apart by their number,
+// detections with the same number end up in the same new cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterAssignment {
+    Cluster(i32),
+    NewCluster(usize),
+    Unclustered,
+}
+
+// A detection that is close to a named face, but not close enough to be attached to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewFaceSuggestion {
+    pub media_face_id: i32,
+    pub face_id: i32,
+    pub distance: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, FromQueryResult)]
+pub struct FaceSuggestion {
+    pub suggestion_id: i32,
+    pub media_face_id: i32,
+    pub media_id: String,
+    pub bbox: Vec<i32>,
+    pub face_id: i32,
+    pub name: String,
+    pub distance: f32,
+}
+
+#[derive(Debug)]
+pub enum UpdateFaceError {
+    NotFound,
+    InvalidRequest,
+    InternalError,
+}
+
+impl From<DbErr> for UpdateFaceError {
+    fn from(_: DbErr) -> Self {
+        UpdateFaceError::InternalError
+    }
+}
+
+// Which people have to be in a photo. A photo matches when it shows every face of `all`,
+// at least one face of `any` when it is not empty, and none of the faces of `none`
+#[derive(Debug, Clone, Default)]
+pub struct PeopleQuery {
+    pub all: Vec<i32>,
+    pub any: Vec<i32>,
+    pub none: Vec<i32>,
+}
+
+// Who a face tagged by the user is, an existing face or a new one with the given name
+#[derive(Debug, Clone)]
+pub enum TagTarget {
+    Face(i32),
+    NewFace(String),
+}
+
+// Where a single face detection is moved to, a face takes it into its oldest cluster
+#[derive(Debug, Clone, Copy)]
+pub enum MediaFaceTarget {
+    Cluster(i32),
+    Face(i32),
+}
+
+// A change the user makes to the metadata of media, fields that are not set are left as they are.
+// The capture time is either set or shifted by a number of milliseconds, a new utc offset alone
+// keeps the instant the media was captured at
+#[derive(Debug, Clone, Default)]
+pub struct MediaEdit {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub created_at: Option<i64>,
+    pub shift: Option<i64>,
+    pub utc_offset: Option<i32>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub rating: Option<i32>,
+}
+
+impl MediaEdit {
+    pub fn edits_location(&self) -> bool {
+        self.latitude.is_some() || self.longitude.is_some()
+    }
+
+    pub fn edits_capture_time(&self) -> bool {
+        self.created_at.is_some() || self.shift.is_some() || self.utc_offset.is_some()
+    }
+
+    pub fn edits_annotations(&self) -> bool {
+        self.title.is_some() || self.description.is_some() || self.rating.is_some()
+    }
+}
+
+#[derive(Debug)]
+pub enum EditMediaError {
+    NotFound,
+    InternalError,
+}
+
+impl From<DbErr> for EditMediaError {
+    fn from(_: DbErr) -> Self {
+        EditMediaError::InternalError
+    }
+}
+
+#[derive(Debug)]
+pub enum GetPreviewError {
+    NotFound,
+    InternalError,
+}
+
+#[derive(Debug)]
+pub enum GetLogError {
+    InternalError,
+    NotFound,
+}
+
+#[derive(Debug)]
+pub enum AddLogError {
+    InternalError,
+}
+
+#[derive(Debug)]
+pub enum GetUserError {
+    NotFound,
+    InternalError,
+}
+
+#[derive(Debug)]
+pub enum AddUserError {
+    AlreadyExists,
     InternalError,
 }
 
@@ -667,6 +2078,105 @@ pub struct RemoteMediaAdded {
     pub id: String,
     pub created_at: i64,
     pub hash: String,
+    pub blurhash: Option<String>,
+    pub dominant_colors: Vec<String>,
+}
+
+impl From<&media::Model> for RemoteMediaAdded {
+    fn from(media: &media::Model) -> Self {
+        RemoteMediaAdded {
+            id: media.id.clone(),
+            created_at: media.created_at,
+            hash: media.hash.clone(),
+            blurhash: media.blurhash.clone(),
+            dominant_colors: media.dominant_colors.clone(),
+        }
+    }
+}
+
+// A media as shown in a grid, the width and height are the ones of the decoded image once
+// it is upright, set with the media type by the preview worker
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, FromQueryResult)]
+pub struct MediaPreview {
+    pub id: String,
+    pub preview_id: Option<String>,
+    pub created_at: i64,
+    pub utc_offset: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub media_type: Option<String>,
+    pub blurhash: Option<String>,
+    pub dominant_colors: Vec<String>,
+}
+
+impl From<&media::Model> for MediaPreview {
+    fn from(media: &media::Model) -> Self {
+        MediaPreview {
+            id: media.id.clone(),
+            preview_id: media.preview_id.clone(),
+            created_at: media.created_at,
+            utc_offset: media.utc_offset,
+            width: media.width,
+            height: media.height,
+            media_type: media.media_type.clone(),
+            blurhash: media.blurhash.clone(),
+            dominant_colors: media.dominant_colors.clone(),
+        }
+    }
+}
+
+// A detection in a grid of the photos of a face, with the crop of the face
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectionPreview {
+    pub media: MediaPreview,
+    pub crop_id: Option<String>,
+}
+
+impl FromQueryResult for DetectionPreview {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(DetectionPreview {
+            media: MediaPreview::from_query_result(res, pre)?,
+            crop_id: res.try_get(pre, "crop_id")?,
+        })
+    }
+}
+
+// Name of the full resolution copy made for media browsers can't show
+pub const FULL_RENDITION: &str = "full";
+const WEB_MEDIA_TYPES: [&str; 5] = [
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/gif",
+    "image/avif",
+];
+
+// Whether browsers need a copy of a media of this type to show it, e.g. HEIC
+pub fn needs_display_copy(media_type: &str) -> bool {
+    !WEB_MEDIA_TYPES.contains(&media_type)
+}
+
+// A resized copy of a media made by the preview worker, stored under `object_id`.
+// `name` is the size it was made for, e.g. grid or display
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendition {
+    pub name: String,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub object_id: String,
+}
+
+// What the preview worker learns about an original while decoding it, the blurhash and
+// dominant colors, as #rrggbb from the most to the least common, are painted until the
+// preview is loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedImage {
+    pub width: i32,
+    pub height: i32,
+    pub media_type: Option<String>,
+    pub blurhash: Option<String>,
+    pub dominant_colors: Vec<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, FromQueryResult)]
@@ -679,8 +2189,10 @@ pub struct RemoteMediaDeleted {
 pub struct Face {
     pub face_id: i32,
     pub name: String,
-    pub photo_id: String,
+    // Missing, with an empty bbox, when every detection of the face was moved away
+    pub photo_id: Option<String>,
     pub bbox: Vec<i32>,
+    pub crop_id: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, FromQueryResult)]
@@ -688,4 +2200,109 @@ pub struct Cluster {
     pub cluster_id: i32,
     pub photo_id: String,
     pub bbox: Vec<i32>,
+    pub crop_id: Option<String>,
+}
+
+// When the media was captured according to its metadata, in milliseconds since the epoch,
+// with the minutes east of UTC of the camera when the metadata records them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureTime {
+    pub created_at: i64,
+    pub utc_offset: Option<i32>,
+}
+
+// Everything the metadata worker reads from a media file, fields it could not find are None.
+// The exif dump maps every tag name of the primary image to its displayed value, the rating,
+// title, description, keywords and names of the people in the photo come from XMP or IPTC
+#[derive(Debug, Clone, Default)]
+pub struct MediaMetadata {
+    pub longitude: Option<f64>,
+    pub latitude: Option<f64>,
+    pub image_width: Option<i32>,
+    pub image_length: Option<i32>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub fnumber: Option<String>,
+    pub exposure_time: Option<String>,
+    pub photographic_sensitivity: Option<String>,
+    pub orientation: Option<i32>,
+    pub lens_make: Option<String>,
+    pub lens_model: Option<String>,
+    pub focal_length: Option<f64>,
+    pub focal_length_35mm: Option<i32>,
+    pub altitude: Option<f64>,
+    pub image_direction: Option<f64>,
+    pub flash: Option<String>,
+    pub white_balance: Option<String>,
+    pub metering_mode: Option<String>,
+    pub exposure_program: Option<String>,
+    pub exposure_bias: Option<f64>,
+    pub software: Option<String>,
+    pub artist: Option<String>,
+    pub exif: Option<JsonValue>,
+    pub rating: Option<i32>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub people: Vec<String>,
+    pub capture_time: Option<CaptureTime>,
+}
+
+// Structured search filters, fields that are not set match all media
+#[derive(Debug, Clone, Default)]
+pub struct MediaFilter {
+    pub after: Option<i64>,
+    pub before: Option<i64>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    // Part of the make and model, e.g. "iPhone 15"
+    pub camera: Option<String>,
+    pub has_gps: Option<bool>,
+    pub near: Option<Location>,
+    pub min_iso: Option<i32>,
+    pub max_iso: Option<i32>,
+    pub face_id: Option<i32>,
+    pub cluster_id: Option<i32>,
+    // Name of a face
+    pub person: Option<String>,
+}
+
+impl MediaFilter {
+    pub fn is_empty(&self) -> bool {
+        self.after.is_none()
+            && self.before.is_none()
+            && self.make.is_none()
+            && self.model.is_none()
+            && self.camera.is_none()
+            && self.has_gps.is_none()
+            && self.near.is_none()
+            && self.min_iso.is_none()
+            && self.max_iso.is_none()
+            && self.face_id.is_none()
+            && self.cluster_id.is_none()
+            && self.person.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_km: f64,
+}
+
+impl Location {
+    // Approximates the radius with a box, which is good enough for the distances photos are searched by.
+    // Returns (min latitude, max latitude, min longitude, max longitude)
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let latitude_delta = self.radius_km / 111.0;
+        let longitude_delta =
+            self.radius_km / (111.0 * self.latitude.to_radians().cos().abs().max(0.01));
+        (
+            self.latitude - latitude_delta,
+            self.latitude + latitude_delta,
+            self.longitude - longitude_delta,
+            self.longitude + longitude_delta,
+        )
+    }
 }
@@ -0,0 +1,1565 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use sea_orm::{sqlx::types::chrono::Utc, DbErr};
+
+use crate::{
+    schema::{cluster, face, face_suggestion, log, media, media_face, rendition, user},
+    vector, AddLogError, AddUserError, Cluster, ClusterAssignment, DecodedImage, DetectionPreview,
+    EditMediaError, Face, FaceEmbedding, FaceRepository, FaceSuggestion, GetLogError,
+    GetPreviewError, GetUserError, LogEntry, LogLevel, LogRepository, MediaEdit, MediaFaceTarget,
+    MediaFilter, MediaMetadata, MediaPreview, MediaRepository, NewFaceSuggestion, PeopleQuery,
+    RemoteMediaAdded, RemoteMediaDeleted, Rendition, TagTarget, UpdateFaceError, UserRepository,
+    FACE_CROP_MAX_ATTEMPTS,
+};
+
+// Keeps every table in memory so handlers can be exercised without postgres
+#[derive(Clone, Default)]
+pub struct InMemoryRepository {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    users: Vec<user::Model>,
+    media: Vec<media::Model>,
+    logs: Vec<log::Model>,
+    faces: Vec<face::Model>,
+    clusters: Vec<cluster::Model>,
+    media_faces: Vec<media_face::Model>,
+    face_suggestions: Vec<face_suggestion::Model>,
+    renditions: Vec<rendition::Model>,
+}
+
+impl State {
+    fn visible_media(&self, user_id: &str, media_id: &str) -> Option<&media::Model> {
+        self.media
+            .iter()
+            .find(|media| media.id == media_id && media.user_id == user_id && !media.deleted)
+    }
+
+    // Newest media first, the same order the timeline uses
+    fn previews<'a>(
+        &self,
+        media: impl Iterator<Item = &'a media::Model>,
+        page: u64,
+        page_size: u64,
+    ) -> Vec<MediaPreview> {
+        let mut media: Vec<&media::Model> = media.collect();
+        media.sort_by_key(|media| Reverse(media.created_at));
+        media
+            .into_iter()
+            .skip(((page - 1) * page_size) as usize)
+            .take(page_size as usize)
+            .map(MediaPreview::from)
+            .collect()
+    }
+
+    // Like `previews`, with the crop of every detection
+    fn detection_previews<'a>(
+        &self,
+        user_id: &str,
+        media_faces: impl Iterator<Item = &'a media_face::Model>,
+        page: u64,
+        page_size: u64,
+    ) -> Vec<DetectionPreview> {
+        let mut detections: Vec<(&media::Model, &media_face::Model)> = media_faces
+            .filter_map(|media_face| {
+                self.visible_media(user_id, &media_face.media_id)
+                    .map(|media| (media, media_face))
+            })
+            .collect();
+        detections.sort_by_key(|(media, _)| Reverse(media.created_at));
+        detections
+            .into_iter()
+            .skip(((page - 1) * page_size) as usize)
+            .take(page_size as usize)
+            .map(|(media, media_face)| DetectionPreview {
+                media: MediaPreview::from(media),
+                crop_id: media_face.crop_id.clone(),
+            })
+            .collect()
+    }
+
+    fn matches(&self, media: &media::Model, filter: &MediaFilter) -> bool {
+        let equals_ignoring_case = |value: &Option<String>, expected: &Option<String>| {
+            expected.as_ref().is_none_or(|expected| {
+                value
+                    .as_ref()
+                    .is_some_and(|value| value.to_lowercase() == expected.to_lowercase())
+            })
+        };
+        let camera = [media.make.as_deref(), media.model.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .to_lowercase();
+        let coordinates = media.latitude.zip(media.longitude);
+        let iso = media
+            .photographic_sensitivity
+            .as_ref()
+            .and_then(|iso| iso.parse::<i32>().ok());
+        let face_ids: Option<HashSet<i32>> = filter.person.as_ref().map(|person| {
+            self.faces
+                .iter()
+                .filter(|face| {
+                    face.user_id == media.user_id
+                        && face.name.to_lowercase() == person.to_lowercase()
+                })
+                .map(|face| face.id)
+                .collect()
+        });
+        let cluster_ids: HashSet<i32> = self
+            .media_faces
+            .iter()
+            .filter(|media_face| media_face.media_id == media.id)
+            .filter_map(|media_face| media_face.cluster_id)
+            .collect();
+        let in_faces = |face_ids: &HashSet<i32>| {
+            self.clusters.iter().any(|cluster| {
+                cluster_ids.contains(&cluster.id)
+                    && cluster
+                        .face_id
+                        .is_some_and(|face_id| face_ids.contains(&face_id))
+            })
+        };
+
+        filter.after.is_none_or(|after| media.created_at >= after)
+            && filter.before.is_none_or(|before| media.created_at < before)
+            && equals_ignoring_case(&media.make, &filter.make)
+            && equals_ignoring_case(&media.model, &filter.model)
+            && filter
+                .camera
+                .as_ref()
+                .is_none_or(|expected| camera.contains(&expected.to_lowercase()))
+            && filter
+                .has_gps
+                .is_none_or(|expected| coordinates.is_some() == expected)
+            && filter.near.is_none_or(|near| {
+                let (min_latitude, max_latitude, min_longitude, max_longitude) = near.bounds();
+                coordinates.is_some_and(|(latitude, longitude)| {
+                    (min_latitude..=max_latitude).contains(&latitude)
+                        && (min_longitude..=max_longitude).contains(&longitude)
+                })
+            })
+            && filter
+                .min_iso
+                .is_none_or(|min_iso| iso.is_some_and(|iso| iso >= min_iso))
+            && filter
+                .max_iso
+                .is_none_or(|max_iso| iso.is_some_and(|iso| iso <= max_iso))
+            && filter
+                .cluster_id
+                .is_none_or(|cluster_id| cluster_ids.contains(&cluster_id))
+            && filter
+                .face_id
+                .is_none_or(|face_id| in_faces(&HashSet::from([face_id])))
+            && face_ids.as_ref().is_none_or(in_faces)
+    }
+
+    fn has_face(&self, user_id: &str, face_id: i32) -> bool {
+        self.faces
+            .iter()
+            .any(|face| face.id == face_id && face.user_id == user_id)
+    }
+
+    fn face_of_cluster(&self, cluster_id: Option<i32>) -> Option<i32> {
+        self.clusters
+            .iter()
+            .find(|cluster| Some(cluster.id) == cluster_id)
+            .and_then(|cluster| cluster.face_id)
+    }
+
+    fn is_hidden(&self, cluster_id: Option<i32>) -> bool {
+        let Some(cluster) = self
+            .clusters
+            .iter()
+            .find(|cluster| Some(cluster.id) == cluster_id)
+        else {
+            return false;
+        };
+        cluster.hidden
+            || self
+                .faces
+                .iter()
+                .any(|face| Some(face.id) == cluster.face_id && face.hidden)
+    }
+
+    fn face_photo(&self, face_id: i32) -> Option<(String, Vec<i32>, Option<String>)> {
+        self.media_faces
+            .iter()
+            .find(|media_face| self.face_of_cluster(media_face.cluster_id) == Some(face_id))
+            .map(|media_face| {
+                (
+                    media_face.media_id.clone(),
+                    media_face.face_bounding_box.clone(),
+                    media_face.crop_id.clone(),
+                )
+            })
+    }
+
+    fn cluster_photo(&self, cluster_id: i32) -> Option<(String, Vec<i32>, Option<String>)> {
+        self.media_faces
+            .iter()
+            .find(|media_face| media_face.cluster_id == Some(cluster_id))
+            .map(|media_face| {
+                (
+                    media_face.media_id.clone(),
+                    media_face.face_bounding_box.clone(),
+                    media_face.crop_id.clone(),
+                )
+            })
+    }
+
+    // Unnamed clusters are listed by one of their detections, so those of the given ones that
+    // have none left are removed. Clusters of a face are kept, the face would lose its last one
+    fn delete_empty_clusters(&mut self, cluster_ids: &[i32]) {
+        let media_faces = &self.media_faces;
+        self.clusters.retain(|cluster| {
+            !cluster_ids.contains(&cluster.id)
+                || cluster.face_id.is_some()
+                || media_faces
+                    .iter()
+                    .any(|media_face| media_face.cluster_id == Some(cluster.id))
+        });
+    }
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        InMemoryRepository::default()
+    }
+
+    // Clusters and detections come from the machine learning services, these stand in for them
+    pub fn add_cluster(&self, user_id: &str, face_id: Option<i32>) -> i32 {
+        let mut state = self.state.lock().unwrap();
+        let id = state
+            .clusters
+            .iter()
+            .map(|cluster| cluster.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        state.clusters.push(cluster::Model {
+            id,
+            user_id: user_id.to_string(),
+            face_id,
+            hidden: false,
+        });
+        id
+    }
+
+    pub fn set_clip_embeddings(&self, media_id: &str, clip_embeddings: &[f32]) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(media) = state.media.iter_mut().find(|media| media.id == media_id) {
+            media.clip_embeddings = Some(vector::format(clip_embeddings));
+        }
+    }
+
+    pub fn set_face_embedding(&self, media_face_id: i32, embedding: &[f32]) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(media_face) = state
+            .media_faces
+            .iter_mut()
+            .find(|media_face| media_face.id == media_face_id)
+        {
+            media_face.embedding = Some(vector::format(embedding));
+            media_face.clustered_at = None;
+        }
+    }
+
+    pub fn add_media_face(
+        &self,
+        media_id: &str,
+        face_bounding_box: Vec<i32>,
+        cluster_id: Option<i32>,
+    ) -> i32 {
+        let mut state = self.state.lock().unwrap();
+        let id = state
+            .media_faces
+            .iter()
+            .map(|media_face| media_face.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        state.media_faces.push(media_face::Model {
+            id,
+            media_id: media_id.to_string(),
+            embedding: None,
+            face_bounding_box,
+            cluster_id,
+            manual: false,
+            crop_id: None,
+            clustered_at: None,
+            crop_attempts: 0,
+        });
+        id
+    }
+}
+
+#[async_trait]
+impl MediaRepository for InMemoryRepository {
+    async fn query_media(&self, user_id: String, checksum: String) -> Result<bool, &'static str> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .media
+            .iter()
+            .any(|media| media.hash == checksum && media.user_id == user_id))
+    }
+
+    async fn add_media(
+        &self,
+        user_id: String,
+        media_id: String,
+        checksum: String,
+        timestamp: i64,
+        file_size: i64,
+        file_name: String,
+    ) -> Result<(), DbErr> {
+        let mut state = self.state.lock().unwrap();
+        if state.media.iter().any(|media| media.id == media_id) {
+            return Err(DbErr::RecordNotInserted);
+        }
+        state.media.push(media::Model {
+            id: media_id,
+            user_id,
+            preview_id: None,
+            hash: checksum,
+            created_at: timestamp,
+            uploaded_at: Utc::now().timestamp_millis(),
+            utc_offset: None,
+            last_modified_at: Utc::now().timestamp_millis(),
+            deleted: false,
+            file_size,
+            file_name,
+            longitude: None,
+            latitude: None,
+            image_width: None,
+            image_length: None,
+            width: None,
+            height: None,
+            media_type: None,
+            blurhash: None,
+            dominant_colors: Vec::new(),
+            make: None,
+            model: None,
+            fnumber: None,
+            exposure_time: None,
+            photographic_sensitivity: None,
+            orientation: None,
+            lens_make: None,
+            lens_model: None,
+            focal_length: None,
+            focal_length_35mm: None,
+            altitude: None,
+            image_direction: None,
+            flash: None,
+            white_balance: None,
+            metering_mode: None,
+            exposure_program: None,
+            exposure_bias: None,
+            software: None,
+            artist: None,
+            exif: None,
+            rating: None,
+            title: None,
+            description: None,
+            keywords: Vec::new(),
+            people: Vec::new(),
+            location_edited: false,
+            capture_time_edited: false,
+            annotations_edited: false,
+            clip_embeddings: None,
+        });
+        Ok(())
+    }
+
+    async fn get_media(&self, media_id: String) -> Result<Option<media::Model>, DbErr> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .media
+            .iter()
+            .find(|media| media.id == media_id)
+            .cloned())
+    }
+
+    async fn update_media_preview(
+        &self,
+        media_id: String,
+        preview_id: String,
+        image: DecodedImage,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let Some(media) = state.media.iter_mut().find(|media| media.id == media_id) else {
+            return Err(format!(
+                "Could not find media: {} in the database",
+                media_id
+            ));
+        };
+        media.preview_id = Some(preview_id);
+        media.width = Some(image.width);
+        media.height = Some(image.height);
+        media.media_type = image.media_type;
+        media.blurhash = image.blurhash;
+        media.dominant_colors = image.dominant_colors;
+        media.last_modified_at = Utc::now().timestamp_millis();
+        Ok(())
+    }
+
+    async fn set_renditions(
+        &self,
+        media_id: String,
+        renditions: Vec<Rendition>,
+    ) -> Result<(), DbErr> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .renditions
+            .retain(|rendition| rendition.media_id != media_id);
+        for rendition in renditions {
+            let id = state.renditions.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+            state.renditions.push(rendition::Model {
+                id,
+                media_id: media_id.clone(),
+                name: rendition.name,
+                content_type: rendition.content_type,
+                width: rendition.width,
+                height: rendition.height,
+                object_id: rendition.object_id,
+            });
+        }
+        Ok(())
+    }
+
+    async fn get_renditions(&self, media_ids: Vec<String>) -> Result<Vec<rendition::Model>, DbErr> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .renditions
+            .iter()
+            .filter(|rendition| media_ids.contains(&rendition.media_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn sync_full(&self, user_id: String) -> Result<Vec<RemoteMediaAdded>, &'static str> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .media
+            .iter()
+            .filter(|media| media.user_id == user_id && !media.deleted)
+            .map(RemoteMediaAdded::from)
+            .collect())
+    }
+
+    async fn sync_partial(
+        &self,
+        user_id: String,
+        since: i64,
+    ) -> Result<(Vec<RemoteMediaAdded>, Vec<RemoteMediaDeleted>), &'static str> {
+        let state = self.state.lock().unwrap();
+        let (deleted, added): (Vec<&media::Model>, Vec<&media::Model>) = state
+            .media
+            .iter()
+            .filter(|media| media.user_id == user_id && media.last_modified_at > since)
+            .partition(|media| media.deleted);
+        Ok((
+            added.into_iter().map(RemoteMediaAdded::from).collect(),
+            deleted
+                .into_iter()
+                .map(|media| RemoteMediaDeleted {
+                    id: media.id.clone(),
+                })
+                .collect(),
+        ))
+    }
+
+    async fn user_has_media(&self, user_id: String, media_id: &str) -> Result<bool, &'static str> {
+        let state = self.state.lock().unwrap();
+        Ok(state.visible_media(&user_id, media_id).is_some())
+    }
+
+    async fn get_previews(
+        &self,
+        user_id: String,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        let media = state
+            .media
+            .iter()
+            .filter(|media| media.user_id == user_id && !media.deleted);
+        Ok(state.previews(media, page, page_size))
+    }
+
+    async fn get_preview_from_user(
+        &self,
+        user_id: String,
+        media_id: &str,
+    ) -> Result<String, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        match state
+            .visible_media(&user_id, media_id)
+            .and_then(|media| media.preview_id.clone())
+        {
+            Some(preview_id) => Ok(preview_id),
+            None => Err(GetPreviewError::NotFound),
+        }
+    }
+
+    async fn get_similar_media(
+        &self,
+        user_id: String,
+        media_id: &str,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        let Some(source) = state.visible_media(&user_id, media_id) else {
+            return Err(GetPreviewError::NotFound);
+        };
+        let Some(source_embeddings) = source.clip_embeddings.as_deref().and_then(vector::parse)
+        else {
+            return Ok(vec![]);
+        };
+
+        let mut similar_media: Vec<(f32, &media::Model)> = state
+            .media
+            .iter()
+            .filter(|media| media.user_id == user_id && !media.deleted && media.id != media_id)
+            .filter_map(|media| {
+                let embeddings = vector::parse(media.clip_embeddings.as_deref()?)?;
+                Some((
+                    vector::cosine_distance(&source_embeddings, &embeddings),
+                    media,
+                ))
+            })
+            .collect();
+        similar_media.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Ok(similar_media
+            .into_iter()
+            .skip(((page - 1) * page_size) as usize)
+            .take(page_size as usize)
+            .map(|(_, media)| MediaPreview::from(media))
+            .collect())
+    }
+
+    async fn filter_media(
+        &self,
+        user_id: String,
+        media_ids: Vec<String>,
+        filter: MediaFilter,
+    ) -> Result<Vec<String>, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        Ok(media_ids
+            .into_iter()
+            .filter(|media_id| {
+                state
+                    .visible_media(&user_id, media_id)
+                    .is_some_and(|media| state.matches(media, &filter))
+            })
+            .collect())
+    }
+
+    async fn get_media_previews(
+        &self,
+        user_id: String,
+        media_ids: Vec<String>,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .media
+            .iter()
+            .filter(|media| {
+                media.user_id == user_id && !media.deleted && media_ids.contains(&media.id)
+            })
+            .map(MediaPreview::from)
+            .collect())
+    }
+
+    async fn search_media(
+        &self,
+        user_id: String,
+        filter: MediaFilter,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        let media = state
+            .media
+            .iter()
+            .filter(|media| media.user_id == user_id && !media.deleted)
+            .filter(|media| state.matches(media, &filter));
+        Ok(state.previews(media, page, page_size))
+    }
+
+    async fn insert_metadata(
+        &self,
+        media_id: String,
+        metadata: MediaMetadata,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let Some(media) = state.media.iter_mut().find(|media| media.id == media_id) else {
+            return Err(format!(
+                "Could not find media: {} in the database",
+                media_id
+            ));
+        };
+        if !media.location_edited {
+            media.longitude = metadata.longitude;
+            media.latitude = metadata.latitude;
+        }
+        media.image_width = metadata.image_width;
+        media.image_length = metadata.image_length;
+        media.make = metadata.make;
+        media.model = metadata.model;
+        media.fnumber = metadata.fnumber;
+        media.exposure_time = metadata.exposure_time;
+        media.photographic_sensitivity = metadata.photographic_sensitivity;
+        media.orientation = metadata.orientation;
+        media.lens_make = metadata.lens_make;
+        media.lens_model = metadata.lens_model;
+        media.focal_length = metadata.focal_length;
+        media.focal_length_35mm = metadata.focal_length_35mm;
+        media.altitude = metadata.altitude;
+        media.image_direction = metadata.image_direction;
+        media.flash = metadata.flash;
+        media.white_balance = metadata.white_balance;
+        media.metering_mode = metadata.metering_mode;
+        media.exposure_program = metadata.exposure_program;
+        media.exposure_bias = metadata.exposure_bias;
+        media.software = metadata.software;
+        media.artist = metadata.artist;
+        media.exif = metadata.exif;
+        if !media.annotations_edited {
+            media.rating = metadata.rating;
+            media.title = metadata.title;
+            media.description = metadata.description;
+        }
+        media.keywords = metadata.keywords;
+        media.people = metadata.people;
+        if let Some(capture_time) = metadata.capture_time.filter(|_| !media.capture_time_edited) {
+            media.created_at = capture_time.created_at;
+            media.utc_offset = capture_time.utc_offset;
+            media.last_modified_at = Utc::now().timestamp_millis();
+        }
+        Ok(())
+    }
+
+    async fn edit_media(
+        &self,
+        user_id: String,
+        media_ids: Vec<String>,
+        edit: MediaEdit,
+    ) -> Result<(), EditMediaError> {
+        let mut state = self.state.lock().unwrap();
+        if media_ids
+            .iter()
+            .any(|media_id| state.visible_media(&user_id, media_id).is_none())
+        {
+            return Err(EditMediaError::NotFound);
+        }
+        for media in state
+            .media
+            .iter_mut()
+            .filter(|media| media_ids.contains(&media.id))
+        {
+            if let Some(latitude) = edit.latitude {
+                media.latitude = Some(latitude);
+            }
+            if let Some(longitude) = edit.longitude {
+                media.longitude = Some(longitude);
+            }
+            media.location_edited |= edit.edits_location();
+            if let Some(created_at) = edit.created_at {
+                media.created_at = created_at;
+            }
+            if let Some(shift) = edit.shift {
+                media.created_at += shift;
+            }
+            if let Some(utc_offset) = edit.utc_offset {
+                media.utc_offset = Some(utc_offset);
+            }
+            media.capture_time_edited |= edit.edits_capture_time();
+            if let Some(title) = &edit.title {
+                media.title = Some(title.clone());
+            }
+            if let Some(description) = &edit.description {
+                media.description = Some(description.clone());
+            }
+            if let Some(rating) = edit.rating {
+                media.rating = Some(rating);
+            }
+            media.annotations_edited |= edit.edits_annotations();
+            media.last_modified_at = Utc::now().timestamp_millis();
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryRepository {
+    async fn get_user(&self, username: String) -> Result<user::Model, GetUserError> {
+        let state = self.state.lock().unwrap();
+        match state.users.iter().find(|user| user.username == username) {
+            Some(user) => Ok(user.clone()),
+            None => Err(GetUserError::NotFound),
+        }
+    }
+
+    async fn add_user(
+        &self,
+        id: String,
+        username: String,
+        password: String,
+    ) -> Result<(), AddUserError> {
+        let mut state = self.state.lock().unwrap();
+        if state.users.iter().any(|user| user.username == username) {
+            return Err(AddUserError::AlreadyExists);
+        }
+        state.users.push(user::Model {
+            id,
+            username,
+            password,
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LogRepository for InMemoryRepository {
+    async fn get_logs(
+        &self,
+        user_id: String,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<LogEntry>, GetLogError> {
+        let state = self.state.lock().unwrap();
+        let mut logs: Vec<&log::Model> = state
+            .logs
+            .iter()
+            .filter(|log| log.user_id == user_id)
+            .collect();
+        logs.sort_by_key(|log| Reverse(log.date));
+        Ok(logs
+            .into_iter()
+            .skip(((page - 1) * page_size) as usize)
+            .take(page_size as usize)
+            .map(|model| LogEntry {
+                id: model.id,
+                level: model.level.clone(),
+                date: model.date,
+                message: model.message.clone(),
+            })
+            .collect())
+    }
+
+    async fn add_log(
+        &self,
+        user_id: String,
+        level: LogLevel,
+        date: i64,
+        message: String,
+    ) -> Result<(), AddLogError> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.logs.len() as i32 + 1;
+        state.logs.push(log::Model {
+            id,
+            user_id,
+            level: level.to_string(),
+            date,
+            message,
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FaceRepository for InMemoryRepository {
+    async fn get_faces(
+        &self,
+        user_id: String,
+        hidden: bool,
+    ) -> Result<(Vec<Face>, Vec<Cluster>), DbErr> {
+        let state = self.state.lock().unwrap();
+        let mut faces: Vec<Face> = vec![];
+        let mut clusters: Vec<Cluster> = vec![];
+        let mut seen_faces = HashSet::new();
+
+        for cluster in state.clusters.iter().filter(|c| c.user_id == user_id) {
+            match cluster.face_id {
+                Some(face_id) => {
+                    if !seen_faces.insert(face_id) {
+                        continue;
+                    }
+                    let Some(face) = state.faces.iter().find(|face| {
+                        face.id == face_id && face.user_id == user_id && face.hidden == hidden
+                    }) else {
+                        continue;
+                    };
+                    let featured = face.featured_photo_id.as_ref().and_then(|photo_id| {
+                        state
+                            .media_faces
+                            .iter()
+                            .find(|media_face| {
+                                &media_face.media_id == photo_id
+                                    && state.face_of_cluster(media_face.cluster_id) == Some(face_id)
+                            })
+                            .map(|media_face| {
+                                (
+                                    media_face.media_id.clone(),
+                                    media_face.face_bounding_box.clone(),
+                                    media_face.crop_id.clone(),
+                                )
+                            })
+                    });
+                    let (photo_id, bbox, crop_id) =
+                        match featured.or_else(|| state.face_photo(face_id)) {
+                            Some((photo_id, bbox, crop_id)) => (Some(photo_id), bbox, crop_id),
+                            None => (None, vec![], None),
+                        };
+                    faces.push(Face {
+                        face_id,
+                        name: face.name.clone(),
+                        photo_id,
+                        bbox,
+                        crop_id,
+                    });
+                }
+                None if cluster.hidden == hidden => {
+                    if let Some((photo_id, bbox, crop_id)) = state.cluster_photo(cluster.id) {
+                        clusters.push(Cluster {
+                            cluster_id: cluster.id,
+                            photo_id,
+                            bbox,
+                            crop_id,
+                        });
+                    }
+                }
+                None => {}
+            }
+        }
+        Ok((faces, clusters))
+    }
+
+    async fn get_cluster_previews(
+        &self,
+        user_id: String,
+        cluster_id: i32,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<DetectionPreview>, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        let media_faces = state
+            .media_faces
+            .iter()
+            .filter(|media_face| media_face.cluster_id == Some(cluster_id));
+        Ok(state.detection_previews(&user_id, media_faces, page, page_size))
+    }
+
+    async fn get_face_previews(
+        &self,
+        user_id: String,
+        face_id: i32,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<DetectionPreview>, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        if !state.has_face(&user_id, face_id) {
+            return Err(GetPreviewError::NotFound);
+        }
+        let cluster_ids: HashSet<i32> = state
+            .clusters
+            .iter()
+            .filter(|cluster| cluster.face_id == Some(face_id) && cluster.user_id == user_id)
+            .map(|cluster| cluster.id)
+            .collect();
+        let media_faces = state.media_faces.iter().filter(|media_face| {
+            media_face
+                .cluster_id
+                .is_some_and(|cluster_id| cluster_ids.contains(&cluster_id))
+        });
+        Ok(state.detection_previews(&user_id, media_faces, page, page_size))
+    }
+
+    async fn get_people_previews(
+        &self,
+        user_id: String,
+        people: PeopleQuery,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError> {
+        let state = self.state.lock().unwrap();
+        let owns_faces = people
+            .all
+            .iter()
+            .chain(&people.any)
+            .chain(&people.none)
+            .all(|face_id| state.has_face(&user_id, *face_id));
+        if !owns_faces {
+            return Err(GetPreviewError::NotFound);
+        }
+
+        let media = state.media.iter().filter(|media| {
+            if media.user_id != user_id || media.deleted {
+                return false;
+            }
+            let face_ids: HashSet<i32> = state
+                .media_faces
+                .iter()
+                .filter(|media_face| media_face.media_id == media.id)
+                .filter_map(|media_face| state.face_of_cluster(media_face.cluster_id))
+                .collect();
+            people.all.iter().all(|face_id| face_ids.contains(face_id))
+                && (people.any.is_empty()
+                    || people.any.iter().any(|face_id| face_ids.contains(face_id)))
+                && !people.none.iter().any(|face_id| face_ids.contains(face_id))
+        });
+        Ok(state.previews(media, page, page_size))
+    }
+
+    async fn insert_face(
+        &self,
+        user_id: String,
+        cluster_ids: Vec<i32>,
+        name: String,
+    ) -> Result<(), DbErr> {
+        let mut state = self.state.lock().unwrap();
+        let face_id = state.faces.iter().map(|face| face.id).max().unwrap_or(0) + 1;
+        state.faces.push(face::Model {
+            id: face_id,
+            name,
+            featured_photo_id: None,
+            user_id: user_id.clone(),
+            hidden: false,
+        });
+        for cluster in state
+            .clusters
+            .iter_mut()
+            .filter(|cluster| cluster_ids.contains(&cluster.id) && cluster.user_id == user_id)
+        {
+            cluster.face_id = Some(face_id);
+        }
+        Ok(())
+    }
+
+    async fn get_media_with_missing_face_crops(&self, limit: u64) -> Result<Vec<String>, DbErr> {
+        let state = self.state.lock().unwrap();
+        let mut media_ids: Vec<String> = state
+            .media_faces
+            .iter()
+            .filter(|media_face| {
+                media_face.crop_id.is_none() && media_face.crop_attempts < FACE_CROP_MAX_ATTEMPTS
+            })
+            .filter(|media_face| {
+                state
+                    .media
+                    .iter()
+                    .any(|media| media.id == media_face.media_id && !media.deleted)
+            })
+            .map(|media_face| media_face.media_id.clone())
+            .collect();
+        media_ids.sort();
+        media_ids.dedup();
+        media_ids.truncate(limit as usize);
+        Ok(media_ids)
+    }
+
+    async fn get_missing_face_crops(
+        &self,
+        media_id: String,
+    ) -> Result<Vec<(i32, Vec<i32>)>, DbErr> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .media_faces
+            .iter()
+            .filter(|media_face| {
+                media_face.media_id == media_id
+                    && media_face.crop_id.is_none()
+                    && media_face.crop_attempts < FACE_CROP_MAX_ATTEMPTS
+            })
+            .map(|media_face| (media_face.id, media_face.face_bounding_box.clone()))
+            .collect())
+    }
+
+    async fn set_face_crop(&self, media_face_id: i32, crop_id: String) -> Result<(), DbErr> {
+        let mut state = self.state.lock().unwrap();
+        for media_face in state
+            .media_faces
+            .iter_mut()
+            .filter(|media_face| media_face.id == media_face_id)
+        {
+            media_face.crop_id = Some(crop_id.clone());
+        }
+        Ok(())
+    }
+
+    async fn add_face_crop_failures(&self, media_face_ids: Vec<i32>) -> Result<(), DbErr> {
+        let mut state = self.state.lock().unwrap();
+        for media_face in state
+            .media_faces
+            .iter_mut()
+            .filter(|media_face| media_face_ids.contains(&media_face.id))
+        {
+            media_face.crop_attempts += 1;
+        }
+        Ok(())
+    }
+
+    async fn get_face_boxes(&self, media_id: String) -> Result<Vec<Vec<i32>>, DbErr> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .media_faces
+            .iter()
+            .filter(|media_face| media_face.media_id == media_id)
+            .map(|media_face| media_face.face_bounding_box.clone())
+            .collect())
+    }
+
+    async fn get_face_embeddings(&self, user_id: String) -> Result<Vec<FaceEmbedding>, DbErr> {
+        let state = self.state.lock().unwrap();
+        let mut detections: Vec<FaceEmbedding> = state
+            .media_faces
+            .iter()
+            .filter(|media_face| {
+                state
+                    .visible_media(&user_id, &media_face.media_id)
+                    .is_some()
+            })
+            .filter_map(|media_face| {
+                Some(FaceEmbedding {
+                    media_face_id: media_face.id,
+                    embedding: vector::parse(media_face.embedding.as_deref()?)?,
+                    cluster_id: media_face.cluster_id,
+                    face_id: state.face_of_cluster(media_face.cluster_id),
+                    hidden: state.is_hidden(media_face.cluster_id),
+                })
+            })
+            .collect();
+        detections.sort_by_key(|detection| detection.media_face_id);
+        Ok(detections)
+    }
+
+    async fn get_users_with_unclustered_faces(&self) -> Result<Vec<String>, DbErr> {
+        let state = self.state.lock().unwrap();
+        let mut user_ids: Vec<String> = state
+            .media_faces
+            .iter()
+            .filter(|media_face| {
+                media_face.embedding.is_some() && media_face.clustered_at.is_none()
+            })
+            .filter_map(|media_face| {
+                state
+                    .media
+                    .iter()
+                    .find(|media| media.id == media_face.media_id && !media.deleted)
+                    .map(|media| media.user_id.clone())
+            })
+            .collect();
+        user_ids.sort();
+        user_ids.dedup();
+        Ok(user_ids)
+    }
+
+    async fn save_clusters(
+        &self,
+        user_id: String,
+        assignments: Vec<(i32, ClusterAssignment)>,
+        clustered: Vec<i32>,
+    ) -> Result<(), DbErr> {
+        let mut new_clusters: HashMap<usize, i32> = HashMap::new();
+        for (media_face_id, assignment) in assignments {
+            let cluster_id = match assignment {
+                ClusterAssignment::Cluster(cluster_id) => Some(cluster_id),
+                ClusterAssignment::Unclustered => None,
+                ClusterAssignment::NewCluster(number) => Some(
+                    *new_clusters
+                        .entry(number)
+                        .or_insert_with(|| self.add_cluster(&user_id, None)),
+                ),
+            };
+            let mut state = self.state.lock().unwrap();
+            for media_face in state
+                .media_faces
+                .iter_mut()
+                .filter(|media_face| media_face.id == media_face_id)
+            {
+                media_face.cluster_id = cluster_id;
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let now = Utc::now().timestamp_millis();
+        let clustered: HashSet<i32> = clustered.into_iter().collect();
+        for media_face in state.media_faces.iter_mut().filter(|media_face| {
+            media_face.clustered_at.is_none() && clustered.contains(&media_face.id)
+        }) {
+            media_face.clustered_at = Some(now);
+        }
+        let used_clusters: HashSet<i32> = state
+            .media_faces
+            .iter()
+            .filter_map(|media_face| media_face.cluster_id)
+            .collect();
+        state.clusters.retain(|cluster| {
+            cluster.user_id != user_id
+                || cluster.face_id.is_some()
+                || used_clusters.contains(&cluster.id)
+        });
+        Ok(())
+    }
+
+    async fn rename_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        name: String,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.has_face(&user_id, face_id) {
+            return Err(UpdateFaceError::NotFound);
+        }
+        for face in state.faces.iter_mut().filter(|face| face.id == face_id) {
+            face.name = name.clone();
+        }
+        Ok(())
+    }
+
+    async fn merge_faces(
+        &self,
+        user_id: String,
+        face_id: i32,
+        merged_face_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        if face_id == merged_face_id {
+            return Err(UpdateFaceError::InvalidRequest);
+        }
+        if !state.has_face(&user_id, face_id) || !state.has_face(&user_id, merged_face_id) {
+            return Err(UpdateFaceError::NotFound);
+        }
+        for cluster in state
+            .clusters
+            .iter_mut()
+            .filter(|cluster| cluster.face_id == Some(merged_face_id) && cluster.user_id == user_id)
+        {
+            cluster.face_id = Some(face_id);
+        }
+        state.faces.retain(|face| face.id != merged_face_id);
+        state
+            .face_suggestions
+            .retain(|suggestion| suggestion.face_id != merged_face_id);
+        Ok(())
+    }
+
+    async fn delete_face(&self, user_id: String, face_id: i32) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.has_face(&user_id, face_id) {
+            return Err(UpdateFaceError::NotFound);
+        }
+        let mut cluster_ids = Vec::new();
+        for cluster in state
+            .clusters
+            .iter_mut()
+            .filter(|cluster| cluster.face_id == Some(face_id) && cluster.user_id == user_id)
+        {
+            cluster.face_id = None;
+            cluster_ids.push(cluster.id);
+        }
+        state.delete_empty_clusters(&cluster_ids);
+        state.faces.retain(|face| face.id != face_id);
+        state
+            .face_suggestions
+            .retain(|suggestion| suggestion.face_id != face_id);
+        Ok(())
+    }
+
+    async fn tag_face(
+        &self,
+        user_id: String,
+        media_id: String,
+        bbox: Vec<i32>,
+        target: TagTarget,
+    ) -> Result<(i32, i32), UpdateFaceError> {
+        let (face_id, cluster_id) = {
+            let mut state = self.state.lock().unwrap();
+            if state.visible_media(&user_id, &media_id).is_none() {
+                return Err(UpdateFaceError::NotFound);
+            }
+            match target {
+                TagTarget::Face(face_id) => {
+                    if !state.has_face(&user_id, face_id) {
+                        return Err(UpdateFaceError::NotFound);
+                    }
+                    let cluster_id = state
+                        .clusters
+                        .iter()
+                        .filter(|cluster| {
+                            cluster.face_id == Some(face_id) && cluster.user_id == user_id
+                        })
+                        .map(|cluster| cluster.id)
+                        .min();
+                    (face_id, cluster_id)
+                }
+                TagTarget::NewFace(name) => {
+                    let face_id = state.faces.iter().map(|face| face.id).max().unwrap_or(0) + 1;
+                    state.faces.push(face::Model {
+                        id: face_id,
+                        name,
+                        featured_photo_id: None,
+                        user_id: user_id.clone(),
+                        hidden: false,
+                    });
+                    (face_id, None)
+                }
+            }
+        };
+        let cluster_id = cluster_id.unwrap_or_else(|| self.add_cluster(&user_id, Some(face_id)));
+        let media_face_id = self.add_media_face(&media_id, bbox, Some(cluster_id));
+        let mut state = self.state.lock().unwrap();
+        for media_face in state
+            .media_faces
+            .iter_mut()
+            .filter(|media_face| media_face.id == media_face_id)
+        {
+            media_face.manual = true;
+        }
+        Ok((media_face_id, face_id))
+    }
+
+    async fn set_face_hidden(
+        &self,
+        user_id: String,
+        face_id: i32,
+        hidden: bool,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .faces
+            .iter_mut()
+            .find(|face| face.id == face_id && face.user_id == user_id)
+        {
+            Some(face) => {
+                face.hidden = hidden;
+                Ok(())
+            }
+            None => Err(UpdateFaceError::NotFound),
+        }
+    }
+
+    async fn set_cluster_hidden(
+        &self,
+        user_id: String,
+        cluster_id: i32,
+        hidden: bool,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .clusters
+            .iter_mut()
+            .find(|cluster| cluster.id == cluster_id && cluster.user_id == user_id)
+        {
+            Some(cluster) => {
+                cluster.hidden = hidden;
+                Ok(())
+            }
+            None => Err(UpdateFaceError::NotFound),
+        }
+    }
+
+    async fn add_cluster_to_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        cluster_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.has_face(&user_id, face_id) {
+            return Err(UpdateFaceError::NotFound);
+        }
+        match state
+            .clusters
+            .iter_mut()
+            .find(|cluster| cluster.id == cluster_id && cluster.user_id == user_id)
+        {
+            Some(cluster) => {
+                cluster.face_id = Some(face_id);
+                Ok(())
+            }
+            None => Err(UpdateFaceError::NotFound),
+        }
+    }
+
+    async fn remove_cluster_from_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        cluster_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        match state.clusters.iter_mut().find(|cluster| {
+            cluster.id == cluster_id
+                && cluster.face_id == Some(face_id)
+                && cluster.user_id == user_id
+        }) {
+            Some(cluster) => {
+                cluster.face_id = None;
+                state.delete_empty_clusters(&[cluster_id]);
+                Ok(())
+            }
+            None => Err(UpdateFaceError::NotFound),
+        }
+    }
+
+    async fn move_media_face(
+        &self,
+        user_id: String,
+        media_face_id: i32,
+        target: MediaFaceTarget,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        let Some(previous_cluster_id) = state
+            .media_faces
+            .iter()
+            .find(|media_face| {
+                media_face.id == media_face_id
+                    && state
+                        .media
+                        .iter()
+                        .any(|media| media.id == media_face.media_id && media.user_id == user_id)
+            })
+            .map(|media_face| media_face.cluster_id)
+        else {
+            return Err(UpdateFaceError::NotFound);
+        };
+
+        let mut user_clusters = state
+            .clusters
+            .iter()
+            .filter(|cluster| cluster.user_id == user_id);
+        let target_cluster_id = match target {
+            MediaFaceTarget::Cluster(cluster_id) => {
+                match user_clusters.find(|cluster| cluster.id == cluster_id) {
+                    Some(cluster) => cluster.id,
+                    None => return Err(UpdateFaceError::NotFound),
+                }
+            }
+            MediaFaceTarget::Face(face_id) => {
+                if !state.has_face(&user_id, face_id) {
+                    return Err(UpdateFaceError::NotFound);
+                }
+                match user_clusters
+                    .filter(|cluster| cluster.face_id == Some(face_id))
+                    .map(|cluster| cluster.id)
+                    .min()
+                {
+                    Some(cluster_id) => cluster_id,
+                    // Every cluster of the face was removed from it, the detection starts a new one
+                    None => {
+                        let cluster_id = state
+                            .clusters
+                            .iter()
+                            .map(|cluster| cluster.id)
+                            .max()
+                            .unwrap_or(0)
+                            + 1;
+                        state.clusters.push(cluster::Model {
+                            id: cluster_id,
+                            user_id: user_id.clone(),
+                            face_id: Some(face_id),
+                            hidden: false,
+                        });
+                        cluster_id
+                    }
+                }
+            }
+        };
+
+        for media_face in state
+            .media_faces
+            .iter_mut()
+            .filter(|media_face| media_face.id == media_face_id)
+        {
+            media_face.cluster_id = Some(target_cluster_id);
+        }
+        if let Some(previous_cluster_id) = previous_cluster_id {
+            state.delete_empty_clusters(&[previous_cluster_id]);
+        }
+        Ok(())
+    }
+
+    async fn get_rejected_suggestions(&self, user_id: String) -> Result<Vec<(i32, i32)>, DbErr> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .face_suggestions
+            .iter()
+            .filter(|suggestion| {
+                suggestion.rejected && state.has_face(&user_id, suggestion.face_id)
+            })
+            .map(|suggestion| (suggestion.media_face_id, suggestion.face_id))
+            .collect())
+    }
+
+    async fn save_face_suggestions(
+        &self,
+        user_id: String,
+        suggestions: Vec<NewFaceSuggestion>,
+    ) -> Result<(), DbErr> {
+        let mut state = self.state.lock().unwrap();
+        let user_face_ids: HashSet<i32> = state
+            .faces
+            .iter()
+            .filter(|face| face.user_id == user_id)
+            .map(|face| face.id)
+            .collect();
+        state.face_suggestions.retain(|suggestion| {
+            suggestion.rejected || !user_face_ids.contains(&suggestion.face_id)
+        });
+        for suggestion in suggestions {
+            let exists = state.face_suggestions.iter().any(|existing| {
+                existing.media_face_id == suggestion.media_face_id
+                    && existing.face_id == suggestion.face_id
+            });
+            if exists {
+                continue;
+            }
+            let id = state
+                .face_suggestions
+                .iter()
+                .map(|suggestion| suggestion.id)
+                .max()
+                .unwrap_or(0)
+                + 1;
+            state.face_suggestions.push(face_suggestion::Model {
+                id,
+                media_face_id: suggestion.media_face_id,
+                face_id: suggestion.face_id,
+                distance: suggestion.distance,
+                rejected: false,
+            });
+        }
+        Ok(())
+    }
+
+    async fn get_face_suggestions(&self, user_id: String) -> Result<Vec<FaceSuggestion>, DbErr> {
+        let state = self.state.lock().unwrap();
+        let mut suggestions: Vec<FaceSuggestion> = state
+            .face_suggestions
+            .iter()
+            .filter(|suggestion| !suggestion.rejected)
+            .filter_map(|suggestion| {
+                let face = state
+                    .faces
+                    .iter()
+                    .find(|face| face.id == suggestion.face_id && face.user_id == user_id)?;
+                let media_face = state
+                    .media_faces
+                    .iter()
+                    .find(|media_face| media_face.id == suggestion.media_face_id)?;
+                state.visible_media(&user_id, &media_face.media_id)?;
+                if state.face_of_cluster(media_face.cluster_id).is_some() {
+                    return None;
+                }
+                Some(FaceSuggestion {
+                    suggestion_id: suggestion.id,
+                    media_face_id: media_face.id,
+                    media_id: media_face.media_id.clone(),
+                    bbox: media_face.face_bounding_box.clone(),
+                    face_id: face.id,
+                    name: face.name.clone(),
+                    distance: suggestion.distance,
+                })
+            })
+            .collect();
+        suggestions.sort_by(|a, b| {
+            a.distance
+                .total_cmp(&b.distance)
+                .then(a.suggestion_id.cmp(&b.suggestion_id))
+        });
+        Ok(suggestions)
+    }
+
+    async fn accept_face_suggestion(
+        &self,
+        user_id: String,
+        suggestion_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        let suggestion = {
+            let state = self.state.lock().unwrap();
+            state
+                .face_suggestions
+                .iter()
+                .find(|suggestion| {
+                    suggestion.id == suggestion_id
+                        && !suggestion.rejected
+                        && state.has_face(&user_id, suggestion.face_id)
+                })
+                .cloned()
+        };
+        let Some(suggestion) = suggestion else {
+            return Err(UpdateFaceError::NotFound);
+        };
+        self.move_media_face(
+            user_id,
+            suggestion.media_face_id,
+            MediaFaceTarget::Face(suggestion.face_id),
+        )
+        .await?;
+        let mut state = self.state.lock().unwrap();
+        state
+            .face_suggestions
+            .retain(|other| other.rejected || other.media_face_id != suggestion.media_face_id);
+        Ok(())
+    }
+
+    async fn reject_face_suggestion(
+        &self,
+        user_id: String,
+        suggestion_id: i32,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        let user_face_ids: HashSet<i32> = state
+            .faces
+            .iter()
+            .filter(|face| face.user_id == user_id)
+            .map(|face| face.id)
+            .collect();
+        match state.face_suggestions.iter_mut().find(|suggestion| {
+            suggestion.id == suggestion_id && user_face_ids.contains(&suggestion.face_id)
+        }) {
+            Some(suggestion) => {
+                suggestion.rejected = true;
+                Ok(())
+            }
+            None => Err(UpdateFaceError::NotFound),
+        }
+    }
+
+    async fn set_featured_photo(
+        &self,
+        user_id: String,
+        face_id: i32,
+        media_id: String,
+    ) -> Result<(), UpdateFaceError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.has_face(&user_id, face_id) {
+            return Err(UpdateFaceError::NotFound);
+        }
+        let has_detection = state.visible_media(&user_id, &media_id).is_some()
+            && state.media_faces.iter().any(|media_face| {
+                media_face.media_id == media_id
+                    && state.face_of_cluster(media_face.cluster_id) == Some(face_id)
+            });
+        if !has_detection {
+            return Err(UpdateFaceError::InvalidRequest);
+        }
+        for face in state.faces.iter_mut().filter(|face| face.id == face_id) {
+            face.featured_photo_id = Some(media_id.clone());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+use async_trait::async_trait;
+use sea_orm::DbErr;
+
+use crate::{
+    schema::{media, rendition, user},
+    AddLogError, AddUserError, Cluster, ClusterAssignment, DecodedImage, DetectionPreview,
+    EditMediaError, Face, FaceEmbedding, FaceSuggestion, GetLogError, GetPreviewError,
+    GetUserError, LogEntry, LogLevel, MediaEdit, MediaFaceTarget, MediaFilter, MediaMetadata,
+    MediaPreview, NewFaceSuggestion, PeopleQuery, RemoteMediaAdded, RemoteMediaDeleted, Rendition,
+    TagTarget, UpdateFaceError,
+};
+
+#[async_trait]
+pub trait MediaRepository: Send + Sync {
+    async fn query_media(&self, user_id: String, checksum: String) -> Result<bool, &'static str>;
+
+    async fn add_media(
+        &self,
+        user_id: String,
+        media_id: String,
+        checksum: String,
+        // Used as the capture time until the metadata worker finds one in the file
+        timestamp: i64,
+        file_size: i64,
+        file_name: String,
+    ) -> Result<(), DbErr>;
+
+    async fn get_media(&self, media_id: String) -> Result<Option<media::Model>, DbErr>;
+
+    async fn update_media_preview(
+        &self,
+        media_id: String,
+        preview_id: String,
+        image: DecodedImage,
+    ) -> Result<(), String>;
+
+    // Replaces every rendition of the media with the given ones
+    async fn set_renditions(
+        &self,
+        media_id: String,
+        renditions: Vec<Rendition>,
+    ) -> Result<(), DbErr>;
+
+    // Renditions of the given media, whoever they belong to
+    async fn get_renditions(&self, media_ids: Vec<String>) -> Result<Vec<rendition::Model>, DbErr>;
+
+    async fn sync_full(&self, user_id: String) -> Result<Vec<RemoteMediaAdded>, &'static str>;
+
+    async fn sync_partial(
+        &self,
+        user_id: String,
+        since: i64,
+    ) -> Result<(Vec<RemoteMediaAdded>, Vec<RemoteMediaDeleted>), &'static str>;
+
+    async fn user_has_media(&self, user_id: String, media_id: &str) -> Result<bool, &'static str>;
+
+    async fn get_previews(
+        &self,
+        user_id: String,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError>;
+
+    async fn get_preview_from_user(
+        &self,
+        user_id: String,
+        media_id: &str,
+    ) -> Result<String, GetPreviewError>;
+
+    // Media of the user ordered by how close their clip embeddings are to the given media
+    async fn get_similar_media(
+        &self,
+        user_id: String,
+        media_id: &str,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError>;
+
+    // The given media that belong to the user and match the filter, in no particular order
+    async fn filter_media(
+        &self,
+        user_id: String,
+        media_ids: Vec<String>,
+        filter: MediaFilter,
+    ) -> Result<Vec<String>, GetPreviewError>;
+
+    // Previews of the given media that belong to the user, in no particular order
+    async fn get_media_previews(
+        &self,
+        user_id: String,
+        media_ids: Vec<String>,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError>;
+
+    // Media of the user matching the filter, newest first
+    async fn search_media(
+        &self,
+        user_id: String,
+        filter: MediaFilter,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError>;
+
+    // Locations and capture times the user edited are kept
+    async fn insert_metadata(
+        &self,
+        media_id: String,
+        metadata: MediaMetadata,
+    ) -> Result<(), String>;
+
+    // Applies the edit to every given media, or to none when one of them is not the user's
+    async fn edit_media(
+        &self,
+        user_id: String,
+        media_ids: Vec<String>,
+        edit: MediaEdit,
+    ) -> Result<(), EditMediaError>;
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn get_user(&self, username: String) -> Result<user::Model, GetUserError>;
+
+    async fn add_user(
+        &self,
+        id: String,
+        username: String,
+        password: String,
+    ) -> Result<(), AddUserError>;
+}
+
+#[async_trait]
+pub trait LogRepository: Send + Sync {
+    async fn get_logs(
+        &self,
+        user_id: String,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<LogEntry>, GetLogError>;
+
+    async fn add_log(
+        &self,
+        user_id: String,
+        level: LogLevel,
+        date: i64,
+        message: String,
+    ) -> Result<(), AddLogError>;
+}
+
+#[async_trait]
+pub trait FaceRepository: Send + Sync {
+    // Either the visible or the hidden faces and unnamed clusters
+    async fn get_faces(
+        &self,
+        user_id: String,
+        hidden: bool,
+    ) -> Result<(Vec<Face>, Vec<Cluster>), DbErr>;
+
+    // Every detection with the crop of its face, newest media first
+    async fn get_cluster_previews(
+        &self,
+        user_id: String,
+        cluster_id: i32,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<DetectionPreview>, GetPreviewError>;
+
+    async fn get_face_previews(
+        &self,
+        user_id: String,
+        face_id: i32,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<DetectionPreview>, GetPreviewError>;
+
+    // Every face of the query has to belong to the user
+    async fn get_people_previews(
+        &self,
+        user_id: String,
+        people: PeopleQuery,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<MediaPreview>, GetPreviewError>;
+
+    async fn insert_face(
+        &self,
+        user_id: String,
+        cluster_ids: Vec<i32>,
+        name: String,
+    ) -> Result<(), DbErr>;
+
+    // Media with detections that have no crop yet, leaving out those that failed FACE_CROP_MAX_ATTEMPTS times
+    async fn get_media_with_missing_face_crops(&self, limit: u64) -> Result<Vec<String>, DbErr>;
+
+    // (media_face_id, bbox) of the detections of the media that have no crop yet and are still tried
+    async fn get_missing_face_crops(&self, media_id: String)
+        -> Result<Vec<(i32, Vec<i32>)>, DbErr>;
+
+    async fn set_face_crop(&self, media_face_id: i32, crop_id: String) -> Result<(), DbErr>;
+
+    // Counts a failed crop of every detection
+    async fn add_face_crop_failures(&self, media_face_ids: Vec<i32>) -> Result<(), DbErr>;
+
+    // Bounding boxes of every detection of the media, in detection order and in the
+    // coordinates of the upright image
+    async fn get_face_boxes(&self, media_id: String) -> Result<Vec<Vec<i32>>, DbErr>;
+
+    // Every detection in the media of the user that has an embedding, ordered by id
+    async fn get_face_embeddings(&self, user_id: String) -> Result<Vec<FaceEmbedding>, DbErr>;
+
+    // Users with detections that have an embedding the clustering has not seen yet
+    async fn get_users_with_unclustered_faces(&self) -> Result<Vec<String>, DbErr>;
+
+    // Moves the detections and removes the unnamed clusters that are left empty. The `clustered`
+    // detections are recorded as seen by the clustering, noise included
+    async fn save_clusters(
+        &self,
+        user_id: String,
+        assignments: Vec<(i32, ClusterAssignment)>,
+        clustered: Vec<i32>,
+    ) -> Result<(), DbErr>;
+
+    async fn rename_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        name: String,
+    ) -> Result<(), UpdateFaceError>;
+
+    // Moves the clusters of the merged face into the face and deletes the merged face
+    async fn merge_faces(
+        &self,
+        user_id: String,
+        face_id: i32,
+        merged_face_id: i32,
+    ) -> Result<(), UpdateFaceError>;
+
+    // Its clusters are kept as unnamed clusters
+    async fn delete_face(&self, user_id: String, face_id: i32) -> Result<(), UpdateFaceError>;
+
+    // Adds a detection drawn by the user, it has no embedding until the face service provides one.
+    // Returns the ids of the detection and of its face
+    async fn tag_face(
+        &self,
+        user_id: String,
+        media_id: String,
+        bbox: Vec<i32>,
+        target: TagTarget,
+    ) -> Result<(i32, i32), UpdateFaceError>;
+
+    async fn set_face_hidden(
+        &self,
+        user_id: String,
+        face_id: i32,
+        hidden: bool,
+    ) -> Result<(), UpdateFaceError>;
+
+    // Only unnamed clusters are listed by their own, so this has no effect while the cluster is part of a face
+    async fn set_cluster_hidden(
+        &self,
+        user_id: String,
+        cluster_id: i32,
+        hidden: bool,
+    ) -> Result<(), UpdateFaceError>;
+
+    async fn add_cluster_to_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        cluster_id: i32,
+    ) -> Result<(), UpdateFaceError>;
+
+    async fn remove_cluster_from_face(
+        &self,
+        user_id: String,
+        face_id: i32,
+        cluster_id: i32,
+    ) -> Result<(), UpdateFaceError>;
+
+    async fn move_media_face(
+        &self,
+        user_id: String,
+        media_face_id: i32,
+        target: MediaFaceTarget,
+    ) -> Result<(), UpdateFaceError>;
+
+    // (media_face_id, face_id) pairs the user said are not the same person
+    async fn get_rejected_suggestions(&self, user_id: String) -> Result<Vec<(i32, i32)>, DbErr>;
+
+    // Replaces the pending suggestions of the user, rejected ones are never suggested again
+    async fn save_face_suggestions(
+        &self,
+        user_id: String,
+        suggestions: Vec<NewFaceSuggestion>,
+    ) -> Result<(), DbErr>;
+
+    // Pending suggestions for detections that are not part of a face yet, closest first
+    async fn get_face_suggestions(&self, user_id: String) -> Result<Vec<FaceSuggestion>, DbErr>;
+
+    // Moves the detection into the suggested face
+    async fn accept_face_suggestion(
+        &self,
+        user_id: String,
+        suggestion_id: i32,
+    ) -> Result<(), UpdateFaceError>;
+
+    async fn reject_face_suggestion(
+        &self,
+        user_id: String,
+        suggestion_id: i32,
+    ) -> Result<(), UpdateFaceError>;
+
+    // The media has to contain a detection of the face
+    async fn set_featured_photo(
+        &self,
+        user_id: String,
+        face_id: i32,
+        media_id: String,
+    ) -> Result<(), UpdateFaceError>;
+}
+
+// Everything the api and the workers need from the storage layer
+pub trait Repository: MediaRepository + UserRepository + LogRepository + FaceRepository {}
+
+impl<T> Repository for T where T: MediaRepository + UserRepository + LogRepository + FaceRepository {}
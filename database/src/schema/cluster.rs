@@ -9,6 +9,7 @@ pub struct Model {
     pub id: i32,
     pub user_id: String,
     pub face_id: Option<i32>,
+    pub hidden: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
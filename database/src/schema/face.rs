@@ -9,12 +9,16 @@ pub struct Model {
     pub id: i32,
     pub name: String,
     pub featured_photo_id: Option<String>,
+    pub user_id: String,
+    pub hidden: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::cluster::Entity")]
     Cluster,
+    #[sea_orm(has_many = "super::face_suggestion::Entity")]
+    FaceSuggestion,
     #[sea_orm(
         belongs_to = "super::media::Entity",
         from = "Column::FeaturedPhotoId",
@@ -23,6 +27,14 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Media,
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    User,
 }
 
 impl Related<super::cluster::Entity> for Entity {
@@ -31,10 +43,22 @@ impl Related<super::cluster::Entity> for Entity {
     }
 }
 
+impl Related<super::face_suggestion::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FaceSuggestion.def()
+    }
+}
+
 impl Related<super::media::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Media.def()
     }
 }
 
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
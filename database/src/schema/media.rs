@@ -11,6 +11,8 @@ pub struct Model {
     pub preview_id: Option<String>,
     pub hash: String,
     pub created_at: i64,
+    pub uploaded_at: i64,
+    pub utc_offset: Option<i32>,
     pub last_modified_at: i64,
     pub deleted: bool,
     pub file_size: i64,
@@ -21,12 +23,45 @@ pub struct Model {
     pub latitude: Option<f64>,
     pub image_width: Option<i32>,
     pub image_length: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub media_type: Option<String>,
+    pub blurhash: Option<String>,
+    pub dominant_colors: Vec<String>,
     pub make: Option<String>,
     pub model: Option<String>,
     pub fnumber: Option<String>,
     pub exposure_time: Option<String>,
     pub photographic_sensitivity: Option<String>,
     pub orientation: Option<i32>,
+    pub lens_make: Option<String>,
+    pub lens_model: Option<String>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub focal_length: Option<f64>,
+    pub focal_length_35mm: Option<i32>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub altitude: Option<f64>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub image_direction: Option<f64>,
+    pub flash: Option<String>,
+    pub white_balance: Option<String>,
+    pub metering_mode: Option<String>,
+    pub exposure_program: Option<String>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub exposure_bias: Option<f64>,
+    pub software: Option<String>,
+    pub artist: Option<String>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub exif: Option<Json>,
+    pub rating: Option<i32>,
+    pub title: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub people: Vec<String>,
+    pub location_edited: bool,
+    pub capture_time_edited: bool,
+    pub annotations_edited: bool,
     #[sea_orm(column_type = "custom(\"vector\")", nullable)]
     pub clip_embeddings: Option<String>,
 }
@@ -37,6 +72,8 @@ pub enum Relation {
     Face,
     #[sea_orm(has_many = "super::media_face::Entity")]
     MediaFace,
+    #[sea_orm(has_many = "super::rendition::Entity")]
+    Rendition,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
@@ -59,6 +96,12 @@ impl Related<super::media_face::Entity> for Entity {
     }
 }
 
+impl Related<super::rendition::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Rendition.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
@@ -8,10 +8,14 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub media_id: String,
-    #[sea_orm(column_type = "custom(\"vector\")")]
-    pub embedding: String,
+    #[sea_orm(column_type = "custom(\"vector\")", nullable)]
+    pub embedding: Option<String>,
     pub face_bounding_box: Vec<i32>,
     pub cluster_id: Option<i32>,
+    pub manual: bool,
+    pub crop_id: Option<String>,
+    pub clustered_at: Option<i64>,
+    pub crop_attempts: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -24,6 +28,8 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Cluster,
+    #[sea_orm(has_many = "super::face_suggestion::Entity")]
+    FaceSuggestion,
     #[sea_orm(
         belongs_to = "super::media::Entity",
         from = "Column::MediaId",
@@ -40,6 +46,12 @@ impl Related<super::cluster::Entity> for Entity {
     }
 }
 
+impl Related<super::face_suggestion::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FaceSuggestion.def()
+    }
+}
+
 impl Related<super::media::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Media.def()
@@ -4,7 +4,9 @@ pub mod prelude;
 
 pub mod cluster;
 pub mod face;
+pub mod face_suggestion;
 pub mod log;
 pub mod media;
 pub mod media_face;
+pub mod rendition;
 pub mod user;
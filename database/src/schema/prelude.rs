@@ -2,7 +2,9 @@
 
 pub use super::cluster::Entity as Cluster;
 pub use super::face::Entity as Face;
+pub use super::face_suggestion::Entity as FaceSuggestion;
 pub use super::log::Entity as Log;
 pub use super::media::Entity as Media;
 pub use super::media_face::Entity as MediaFace;
+pub use super::rendition::Entity as Rendition;
 pub use super::user::Entity as User;
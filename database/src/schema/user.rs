@@ -16,6 +16,8 @@ pub struct Model {
 pub enum Relation {
     #[sea_orm(has_many = "super::cluster::Entity")]
     Cluster,
+    #[sea_orm(has_many = "super::face::Entity")]
+    Face,
     #[sea_orm(has_many = "super::log::Entity")]
     Log,
     #[sea_orm(has_many = "super::media::Entity")]
@@ -28,6 +30,12 @@ impl Related<super::cluster::Entity> for Entity {
     }
 }
 
+impl Related<super::face::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Face.def()
+    }
+}
+
 impl Related<super::log::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Log.def()
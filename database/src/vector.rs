@@ -0,0 +1,28 @@
+// Helpers for pgvector values, which are read and written in their text form: [1,2,3]
+
+pub fn parse(vector: &str) -> Option<Vec<f32>> {
+    let values = vector.trim().strip_prefix('[')?.strip_suffix(']')?;
+    if values.trim().is_empty() {
+        return Some(vec![]);
+    }
+    values
+        .split(',')
+        .map(|value| value.trim().parse::<f32>().ok())
+        .collect()
+}
+
+pub fn format(vector: &[f32]) -> String {
+    let values: Vec<String> = vector.iter().map(ToString::to_string).collect();
+    format!("[{}]", values.join(","))
+}
+
+// Same metric as the pgvector <=> operator
+pub fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot / (norm_a * norm_b)
+}
@@ -0,0 +1,149 @@
+mod memory;
+mod nats;
+
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::Stream;
+use tokio::sync::oneshot;
+
+pub use memory::InMemoryBus;
+pub use nats::NatsBus;
+
+pub type Subscription = Pin<Box<dyn Stream<Item = Result<Message, BusError>> + Send>>;
+
+#[async_trait]
+pub trait MessageBus: Send + Sync {
+    // Queues a payload on a durable subject, the way the api hands work to the workers
+    async fn publish(&self, subject: &str, payload: Bytes) -> Result<(), BusError>;
+
+    // Consumes a durable subject, consumers sharing a name split its messages between them
+    async fn subscribe(&self, subject: &str, consumer: &str) -> Result<Subscription, BusError>;
+
+    // Sends a payload and waits for a single reply
+    async fn request(&self, subject: &str, payload: Bytes) -> Result<Bytes, BusError>;
+}
+
+pub struct Message {
+    pub subject: String,
+    pub payload: Bytes,
+    handle: Handle,
+}
+
+enum Handle {
+    Nats(Box<async_nats::jetstream::Message>),
+    Memory {
+        reply: Mutex<Option<oneshot::Sender<Bytes>>>,
+        queue: Weak<memory::Queue>,
+        // Set once the message is acknowledged, nak'd, terminated or redelivered
+        settled: Arc<AtomicBool>,
+    },
+}
+
+impl Message {
+    // Marks the message as processed
+    pub async fn ack(&self) -> Result<(), BusError> {
+        match &self.handle {
+            Handle::Nats(msg) => msg
+                .ack()
+                .await
+                .map_err(|err| BusError::Acknowledge(err.to_string())),
+            Handle::Memory { settled, .. } => {
+                settled.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    // Asks for the message to be delivered again, e.g. after a transient error
+    pub async fn nak(&self) -> Result<(), BusError> {
+        match &self.handle {
+            Handle::Nats(msg) => msg
+                .ack_with(async_nats::jetstream::AckKind::Nak(None))
+                .await
+                .map_err(|err| BusError::Acknowledge(err.to_string())),
+            Handle::Memory {
+                reply,
+                queue,
+                settled,
+            } => {
+                if !settled.swap(true, Ordering::Relaxed) {
+                    memory::redeliver(self, reply.lock().unwrap().take(), queue);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Marks the message as impossible to process so it is never redelivered
+    pub async fn term(&self) -> Result<(), BusError> {
+        match &self.handle {
+            Handle::Nats(msg) => msg
+                .ack_with(async_nats::jetstream::AckKind::Term)
+                .await
+                .map_err(|err| BusError::Acknowledge(err.to_string())),
+            Handle::Memory { settled, .. } => {
+                settled.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    // Answers a message that was sent through `MessageBus::request`. Only the in-memory bus
+    // delivers requests to subscriptions: with NATS they go to core NATS subscribers, the
+    // machine learning services, and a message consumed from a stream has nobody waiting
+    pub async fn respond(&self, payload: Bytes) -> Result<(), BusError> {
+        match &self.handle {
+            Handle::Nats(..) => Err(BusError::Respond(format!(
+                "Messages consumed from the {} stream cannot be answered",
+                self.subject
+            ))),
+            Handle::Memory { reply, .. } => {
+                let Some(sender) = reply.lock().unwrap().take() else {
+                    return Err(BusError::Respond(format!(
+                        "Message on {} does not expect a reply",
+                        self.subject
+                    )));
+                };
+                sender
+                    .send(payload)
+                    .map_err(|_| BusError::Respond("Requester stopped waiting".to_string()))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BusError {
+    Connect(String),
+    Publish(String),
+    Subscribe(String),
+    Request(String),
+    NoResponders(String),
+    Respond(String),
+    Acknowledge(String),
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusError::Connect(err) => write!(f, "Couldn't connect to the message bus: {err}"),
+            BusError::Publish(err) => write!(f, "Couldn't publish message: {err}"),
+            BusError::Subscribe(err) => write!(f, "Couldn't subscribe: {err}"),
+            BusError::Request(err) => write!(f, "Request failed: {err}"),
+            BusError::NoResponders(subject) => write!(f, "No responders on subject {subject}"),
+            BusError::Respond(err) => write!(f, "Couldn't respond: {err}"),
+            BusError::Acknowledge(err) => write!(f, "Couldn't acknowledge message: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for BusError {}
@@ -0,0 +1,230 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::{oneshot, Notify};
+
+use crate::{BusError, Handle, Message, MessageBus, Subscription};
+
+// Mirrors the max_messages limit of the NATS streams, the oldest messages are dropped first
+const QUEUE_MAX_MESSAGES: usize = 10000;
+// The default request timeout of the NATS client
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// The default ack wait of NATS consumers
+const ACK_WAIT: Duration = Duration::from_secs(30);
+
+// Keeps every subject as a stream inside the process with a queue per consumer.
+// Every consumer gets every message, subscriptions sharing a consumer name split them.
+// Nak'd messages are redelivered at once, those neither acknowledged nor terminated after
+// the ack wait are redelivered as well. Where it differs from NATS:
+// - the stream of a subject is made by its first subscription, like `NatsBus::subscribe`
+//   does, but publishing before that drops the message instead of failing
+// - requests reach the consumers of the subject instead of core NATS subscribers, so they
+//   can be answered with `Message::respond`
+// - nothing survives a restart of the process
+#[derive(Clone, Default)]
+pub struct InMemoryBus {
+    subjects: Arc<Mutex<HashMap<String, Subject>>>,
+}
+
+#[derive(Default)]
+struct Subject {
+    // What a consumer created later starts from, like the stream behind a NATS subject
+    stream: VecDeque<Bytes>,
+    consumers: HashMap<String, Arc<Queue>>,
+}
+
+#[derive(Default)]
+pub(crate) struct Queue {
+    messages: Mutex<VecDeque<Message>>,
+    notify: Notify,
+    subscriptions: AtomicUsize,
+}
+
+impl Queue {
+    fn push(&self, message: Message) {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() >= QUEUE_MAX_MESSAGES {
+            messages.pop_front();
+        }
+        messages.push_back(message);
+        drop(messages);
+        self.notify.notify_one();
+    }
+
+    fn pop(&self) -> Option<Message> {
+        self.messages.lock().unwrap().pop_front()
+    }
+}
+
+// Counts a live subscription on its queue for as long as the subscription stream exists
+struct Subscriber(Arc<Queue>);
+
+impl Subscriber {
+    fn new(queue: Arc<Queue>) -> Self {
+        queue.subscriptions.fetch_add(1, Ordering::Relaxed);
+        Subscriber(queue)
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.0.subscriptions.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl InMemoryBus {
+    pub fn new() -> Self {
+        InMemoryBus::default()
+    }
+
+    fn message(
+        subject: &str,
+        payload: Bytes,
+        reply: Option<oneshot::Sender<Bytes>>,
+        queue: &Arc<Queue>,
+    ) -> Message {
+        Message {
+            subject: subject.to_string(),
+            payload,
+            handle: Handle::Memory {
+                reply: Mutex::new(reply),
+                queue: Arc::downgrade(queue),
+                settled: Arc::new(AtomicBool::new(false)),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl MessageBus for InMemoryBus {
+    async fn publish(&self, subject: &str, payload: Bytes) -> Result<(), BusError> {
+        let mut subjects = self.subjects.lock().unwrap();
+        // Nothing would ever consume it, e.g. the embeddings of a standalone deployment
+        let Some(entry) = subjects.get_mut(subject) else {
+            return Ok(());
+        };
+        if entry.stream.len() >= QUEUE_MAX_MESSAGES {
+            entry.stream.pop_front();
+        }
+        entry.stream.push_back(payload.clone());
+        for queue in entry.consumers.values() {
+            queue.push(InMemoryBus::message(subject, payload.clone(), None, queue));
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, subject: &str, consumer: &str) -> Result<Subscription, BusError> {
+        let queue = {
+            let mut subjects = self.subjects.lock().unwrap();
+            let entry = subjects.entry(subject.to_string()).or_default();
+            let stream = &entry.stream;
+            entry
+                .consumers
+                .entry(consumer.to_string())
+                .or_insert_with(|| {
+                    let queue = Arc::new(Queue::default());
+                    for payload in stream {
+                        queue.push(InMemoryBus::message(subject, payload.clone(), None, &queue));
+                    }
+                    queue
+                })
+                .clone()
+        };
+
+        Ok(Box::pin(futures_util::stream::unfold(
+            Subscriber::new(queue),
+            |subscriber| async move {
+                loop {
+                    if let Some(message) = subscriber.0.pop() {
+                        // Let the next waiting subscription check the queue as well
+                        subscriber.0.notify.notify_one();
+                        redeliver_after_ack_wait(&message);
+                        return Some((Ok(message), subscriber));
+                    }
+                    subscriber.0.notify.notified().await;
+                }
+            },
+        )))
+    }
+
+    async fn request(&self, subject: &str, payload: Bytes) -> Result<Bytes, BusError> {
+        // Like core NATS, a request reaches a single live subscription and is not kept
+        let queue = self
+            .subjects
+            .lock()
+            .unwrap()
+            .get(subject)
+            .and_then(|entry| {
+                entry
+                    .consumers
+                    .values()
+                    .find(|queue| queue.subscriptions.load(Ordering::Relaxed) > 0)
+                    .cloned()
+            });
+        let Some(queue) = queue else {
+            return Err(BusError::NoResponders(subject.to_string()));
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        queue.push(InMemoryBus::message(subject, payload, Some(sender), &queue));
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, receiver).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(BusError::Request(format!(
+                "Request on {subject} was dropped"
+            ))),
+            Err(_) => Err(BusError::Request(format!("Request on {subject} timed out"))),
+        }
+    }
+}
+
+// Puts a nak'd message back at the end of the queue it came from
+pub(crate) fn redeliver(
+    message: &Message,
+    reply: Option<oneshot::Sender<Bytes>>,
+    queue: &Weak<Queue>,
+) {
+    if let Some(queue) = queue.upgrade() {
+        queue.push(InMemoryBus::message(
+            &message.subject,
+            message.payload.clone(),
+            reply,
+            &queue,
+        ));
+    }
+}
+
+// Redelivers a published message the subscriber has not settled within the ack wait.
+// Requests are left to time out, like core NATS requests
+fn redeliver_after_ack_wait(message: &Message) {
+    let Handle::Memory {
+        reply,
+        queue,
+        settled,
+    } = &message.handle
+    else {
+        return;
+    };
+    if reply.lock().unwrap().is_some() {
+        return;
+    }
+    let (subject, payload) = (message.subject.clone(), message.payload.clone());
+    let (queue, settled) = (queue.clone(), settled.clone());
+    tokio::spawn(async move {
+        tokio::time::sleep(ACK_WAIT).await;
+        if settled.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        if let Some(queue) = queue.upgrade() {
+            queue.push(InMemoryBus::message(&subject, payload, None, &queue));
+        }
+    });
+}
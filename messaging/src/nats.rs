@@ -0,0 +1,89 @@
+use async_nats::jetstream::{self, consumer::pull, stream};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::StreamExt;
+
+use crate::{BusError, Handle, Message, MessageBus, Subscription};
+
+const STREAM_MAX_MESSAGES: i64 = 10000;
+
+#[derive(Clone)]
+pub struct NatsBus {
+    client: async_nats::Client,
+    jetstream: jetstream::Context,
+}
+
+impl NatsBus {
+    pub async fn connect(endpoint: String) -> Result<Self, BusError> {
+        match async_nats::connect(endpoint).await {
+            Ok(client) => Ok(NatsBus::new(client)),
+            Err(err) => Err(BusError::Connect(err.to_string())),
+        }
+    }
+
+    pub fn new(client: async_nats::Client) -> Self {
+        let jetstream = jetstream::new(client.clone());
+        NatsBus { client, jetstream }
+    }
+}
+
+#[async_trait]
+impl MessageBus for NatsBus {
+    async fn publish(&self, subject: &str, payload: Bytes) -> Result<(), BusError> {
+        match self.jetstream.publish(subject.to_string(), payload).await {
+            Ok(..) => Ok(()),
+            Err(err) => Err(BusError::Publish(err.to_string())),
+        }
+    }
+
+    async fn subscribe(&self, subject: &str, consumer: &str) -> Result<Subscription, BusError> {
+        // Every subject is backed by a stream with the same name
+        let stream = self
+            .jetstream
+            .get_or_create_stream(stream::Config {
+                name: subject.to_string(),
+                max_messages: STREAM_MAX_MESSAGES,
+                ..Default::default()
+            })
+            .await
+            .map_err(|err| BusError::Subscribe(err.to_string()))?;
+
+        let consumer = stream
+            .get_or_create_consumer(
+                consumer,
+                pull::Config {
+                    durable_name: Some(consumer.to_string()),
+                    filter_subject: subject.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|err| BusError::Subscribe(err.to_string()))?;
+
+        let messages = consumer
+            .messages()
+            .await
+            .map_err(|err| BusError::Subscribe(err.to_string()))?;
+
+        Ok(Box::pin(messages.map(|msg| match msg {
+            Ok(msg) => Ok(Message {
+                subject: msg.subject.to_string(),
+                payload: msg.payload.clone(),
+                handle: Handle::Nats(Box::new(msg)),
+            }),
+            Err(err) => Err(BusError::Subscribe(err.to_string())),
+        })))
+    }
+
+    async fn request(&self, subject: &str, payload: Bytes) -> Result<Bytes, BusError> {
+        match self.client.request(subject.to_string(), payload).await {
+            Ok(response) => Ok(response.payload),
+            Err(err) => match err.kind() {
+                async_nats::RequestErrorKind::NoResponders => {
+                    Err(BusError::NoResponders(subject.to_string()))
+                }
+                _ => Err(BusError::Request(err.to_string())),
+            },
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use messaging::{BusError, InMemoryBus, MessageBus, Subscription};
+
+async fn next_payload(subscription: &mut Subscription) -> String {
+    let message = tokio::time::timeout(Duration::from_secs(1), subscription.next())
+        .await
+        .expect("no message was delivered")
+        .unwrap()
+        .unwrap();
+    message.ack().await.unwrap();
+    String::from_utf8(message.payload.to_vec()).unwrap()
+}
+
+async fn is_empty(subscription: &mut Subscription) -> bool {
+    tokio::time::timeout(Duration::from_millis(50), subscription.next())
+        .await
+        .is_err()
+}
+
+#[tokio::test]
+async fn every_consumer_gets_every_message() {
+    let bus = InMemoryBus::new();
+    let mut previews = bus.subscribe("media", "previews").await.unwrap();
+    let mut metadata = bus.subscribe("media", "metadata").await.unwrap();
+
+    bus.publish("media", "one".into()).await.unwrap();
+    bus.publish("media", "two".into()).await.unwrap();
+
+    for subscription in [&mut previews, &mut metadata] {
+        assert_eq!(next_payload(subscription).await, "one");
+        assert_eq!(next_payload(subscription).await, "two");
+        assert!(is_empty(subscription).await);
+    }
+}
+
+#[tokio::test]
+async fn subscriptions_of_one_consumer_split_the_messages() {
+    let bus = InMemoryBus::new();
+    let mut first = bus.subscribe("media", "previews").await.unwrap();
+    let mut second = bus.subscribe("media", "previews").await.unwrap();
+
+    bus.publish("media", "one".into()).await.unwrap();
+    bus.publish("media", "two".into()).await.unwrap();
+
+    let mut payloads = vec![
+        next_payload(&mut first).await,
+        next_payload(&mut second).await,
+    ];
+    payloads.sort();
+    assert_eq!(payloads, vec!["one", "two"]);
+    assert!(is_empty(&mut first).await);
+    assert!(is_empty(&mut second).await);
+}
+
+#[tokio::test]
+async fn new_consumers_start_from_the_stream() {
+    let bus = InMemoryBus::new();
+    let _previews = bus.subscribe("media", "previews").await.unwrap();
+    bus.publish("media", "before".into()).await.unwrap();
+
+    let mut metadata = bus.subscribe("media", "metadata").await.unwrap();
+    bus.publish("media", "after".into()).await.unwrap();
+
+    assert_eq!(next_payload(&mut metadata).await, "before");
+    assert_eq!(next_payload(&mut metadata).await, "after");
+}
+
+#[tokio::test]
+async fn messages_nobody_subscribed_to_are_dropped() {
+    let bus = InMemoryBus::new();
+    bus.publish("media", "dropped".into()).await.unwrap();
+
+    let mut subscription = bus.subscribe("media", "previews").await.unwrap();
+    assert!(is_empty(&mut subscription).await);
+}
+
+#[tokio::test]
+async fn nak_redelivers_and_ack_does_not() {
+    let bus = InMemoryBus::new();
+    let mut subscription = bus.subscribe("media", "previews").await.unwrap();
+    bus.publish("media", "retried".into()).await.unwrap();
+
+    let message = subscription.next().await.unwrap().unwrap();
+    message.nak().await.unwrap();
+    let redelivered = subscription.next().await.unwrap().unwrap();
+    assert_eq!(redelivered.payload, "retried");
+
+    redelivered.ack().await.unwrap();
+    assert!(is_empty(&mut subscription).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn unacknowledged_messages_are_redelivered_after_the_ack_wait() {
+    let bus = InMemoryBus::new();
+    let mut subscription = bus.subscribe("media", "previews").await.unwrap();
+    bus.publish("media", "forgotten".into()).await.unwrap();
+    bus.publish("media", "done".into()).await.unwrap();
+
+    let forgotten = subscription.next().await.unwrap().unwrap();
+    assert_eq!(next_payload(&mut subscription).await, "done");
+    drop(forgotten);
+    assert!(is_empty(&mut subscription).await);
+
+    let redelivered = tokio::time::timeout(Duration::from_secs(60), subscription.next())
+        .await
+        .expect("the message was not redelivered")
+        .unwrap()
+        .unwrap();
+    assert_eq!(redelivered.payload, "forgotten");
+    redelivered.ack().await.unwrap();
+    assert!(
+        tokio::time::timeout(Duration::from_secs(60), subscription.next())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn term_does_not_redeliver() {
+    let bus = InMemoryBus::new();
+    let mut subscription = bus.subscribe("media", "previews").await.unwrap();
+    bus.publish("media", "broken".into()).await.unwrap();
+
+    let message = subscription.next().await.unwrap().unwrap();
+    message.term().await.unwrap();
+    assert!(is_empty(&mut subscription).await);
+}
+
+#[tokio::test]
+async fn requests_are_answered() {
+    let bus = InMemoryBus::new();
+    let mut subscription = bus.subscribe("search", "search_consumer").await.unwrap();
+    tokio::spawn(async move {
+        let message = subscription.next().await.unwrap().unwrap();
+        let mut reply = message.payload.to_vec();
+        reply.reverse();
+        message.respond(reply.into()).await.unwrap();
+    });
+
+    let response = bus.request("search", "abc".into()).await.unwrap();
+    assert_eq!(response, "cba");
+}
+
+#[tokio::test]
+async fn requests_without_subscriptions_have_no_responders() {
+    let bus = InMemoryBus::new();
+    assert!(matches!(
+        bus.request("search", "abc".into()).await,
+        Err(BusError::NoResponders(..))
+    ));
+
+    let subscription = bus.subscribe("search", "search_consumer").await.unwrap();
+    drop(subscription);
+    assert!(matches!(
+        bus.request("search", "abc".into()).await,
+        Err(BusError::NoResponders(..))
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn unanswered_requests_time_out() {
+    let bus = InMemoryBus::new();
+    let _subscription = bus.subscribe("search", "search_consumer").await.unwrap();
+
+    assert!(matches!(
+        bus.request("search", "abc".into()).await,
+        Err(BusError::Request(..))
+    ));
+}
@@ -0,0 +1,47 @@
+use database::MediaMetadata;
+
+// What the photographer wrote about a photo in XMP or IPTC
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotations {
+    // From -1 for rejected photos to 5 stars
+    pub rating: Option<i32>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub people: Vec<String>,
+}
+
+impl Annotations {
+    // The fields set in the newer annotations win, lists included
+    pub fn merge(mut self, newer: Annotations) -> Annotations {
+        self.rating = newer.rating.or(self.rating);
+        self.title = newer.title.or(self.title);
+        self.description = newer.description.or(self.description);
+        if !newer.keywords.is_empty() {
+            self.keywords = newer.keywords;
+        }
+        if !newer.people.is_empty() {
+            self.people = newer.people;
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Annotations::default()
+    }
+
+    pub fn apply(self, metadata: &mut MediaMetadata) {
+        metadata.rating = self.rating;
+        metadata.title = self.title;
+        metadata.description = self.description;
+        metadata.keywords = self.keywords;
+        metadata.people = self.people;
+    }
+}
+
+pub(crate) fn add_unique(values: &mut Vec<String>, value: String) {
+    let value = value.trim();
+    if !value.is_empty() && !values.iter().any(|existing| existing == value) {
+        values.push(value.to_string());
+    }
+}
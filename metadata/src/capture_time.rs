@@ -0,0 +1,52 @@
+use chrono::NaiveDate;
+use database::CaptureTime;
+use exif::DateTime;
+
+const MILLIS_PER_MINUTE: i64 = 60 * 1000;
+// Time zones are whole quarter hours and at most 14 hours away from UTC
+const OFFSET_STEP_MINUTES: i64 = 15;
+const MAX_OFFSET_MINUTES: i64 = 14 * 60;
+
+// Combines the local capture time of the camera with the UTC time of the GPS fix.
+// Without a recorded offset the GPS time gives it away, and without either the local
+// time is read as UTC so the photo at least lands on the day it shows
+pub fn capture_time(date_time: Option<&DateTime>, gps_time: Option<i64>) -> Option<CaptureTime> {
+    let Some(date_time) = date_time else {
+        return gps_time.map(|created_at| CaptureTime {
+            created_at,
+            utc_offset: None,
+        });
+    };
+
+    let local = local_millis(date_time)?;
+    let utc_offset = date_time
+        .offset
+        .map(i32::from)
+        .or_else(|| gps_time.and_then(|gps_time| offset_from_gps(local, gps_time)));
+    Some(CaptureTime {
+        created_at: local - utc_offset.unwrap_or(0) as i64 * MILLIS_PER_MINUTE,
+        utc_offset,
+    })
+}
+
+// Milliseconds since the epoch of the wall clock time, as if it were UTC
+fn local_millis(date_time: &DateTime) -> Option<i64> {
+    NaiveDate::from_ymd_opt(
+        date_time.year.into(),
+        date_time.month.into(),
+        date_time.day.into(),
+    )?
+    .and_hms_nano_opt(
+        date_time.hour.into(),
+        date_time.minute.into(),
+        date_time.second.into(),
+        date_time.nanosecond.unwrap_or(0),
+    )
+    .map(|date_time| date_time.and_utc().timestamp_millis())
+}
+
+fn offset_from_gps(local: i64, gps_time: i64) -> Option<i32> {
+    let minutes = (local - gps_time) as f64 / MILLIS_PER_MINUTE as f64;
+    let offset = (minutes / OFFSET_STEP_MINUTES as f64).round() as i64 * OFFSET_STEP_MINUTES;
+    (offset.abs() <= MAX_OFFSET_MINUTES).then_some(offset as i32)
+}
@@ -0,0 +1,443 @@
+use chrono::DateTime;
+use database::schema::media;
+use exif::{experimental::Writer, Field, In, Rational, Reader, Tag, Value};
+use quick_xml::{
+    escape::escape,
+    events::{BytesStart, BytesText, Event},
+    name::{Namespace, ResolveResult},
+    NsReader,
+};
+use std::io::Cursor;
+
+use crate::{heif, jpeg, xmp::find_xmp};
+
+const RDF: &[u8] = b"http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const DC: &[u8] = b"http://purl.org/dc/elements/1.1/";
+const XMP: &[u8] = b"http://ns.adobe.com/xap/1.0/";
+const EXIF: &[u8] = b"http://ns.adobe.com/exif/1.0/";
+const PHOTOSHOP: &[u8] = b"http://ns.adobe.com/photoshop/1.0/";
+
+const MILLIS_PER_MINUTE: i64 = 60 * 1000;
+// Replaced by the exported values, the maker note is dropped as its offsets break once moved
+const REPLACED_TAGS: [Tag; 9] = [
+    Tag::DateTimeOriginal,
+    Tag::SubSecTimeOriginal,
+    Tag::OffsetTimeOriginal,
+    Tag::GPSLatitude,
+    Tag::GPSLatitudeRef,
+    Tag::GPSLongitude,
+    Tag::GPSLongitudeRef,
+    Tag::ImageDescription,
+    Tag::MakerNote,
+];
+
+// The metadata of a media as Chronolens knows it, edits included
+#[derive(Debug, Clone, Default)]
+pub struct ExportMetadata {
+    pub created_at: i64,
+    pub utc_offset: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub rating: Option<i32>,
+    pub keywords: Vec<String>,
+}
+
+impl From<&media::Model> for ExportMetadata {
+    fn from(media: &media::Model) -> Self {
+        ExportMetadata {
+            created_at: media.created_at,
+            utc_offset: media.utc_offset,
+            latitude: media.latitude,
+            longitude: media.longitude,
+            title: media.title.clone(),
+            description: media.description.clone(),
+            rating: media.rating,
+            keywords: media.keywords.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaFormat {
+    Jpeg,
+    Heif,
+    Other,
+}
+
+impl MediaFormat {
+    pub fn of(bytes: &[u8]) -> MediaFormat {
+        if bytes.starts_with(&[0xff, 0xd8]) {
+            MediaFormat::Jpeg
+        } else if bytes.get(4..8) == Some(b"ftyp") {
+            MediaFormat::Heif
+        } else {
+            MediaFormat::Other
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            MediaFormat::Jpeg => "image/jpeg",
+            MediaFormat::Heif => "image/heic",
+            MediaFormat::Other => "application/octet-stream",
+        }
+    }
+}
+
+// A copy of the media carrying the metadata in its EXIF and XMP, the other EXIF tags of the
+// original are kept. Formats that can't be written are returned as they are
+pub fn export_media(bytes: &[u8], metadata: &ExportMetadata) -> Result<Vec<u8>, String> {
+    let format = MediaFormat::of(bytes);
+    if format == MediaFormat::Other {
+        return Ok(bytes.to_vec());
+    }
+
+    let exif = exif_block(bytes, metadata)?;
+    let xmp = xmp_packet(find_xmp(bytes), metadata);
+    match format {
+        MediaFormat::Jpeg => jpeg::replace_metadata(bytes, &exif, &xmp),
+        MediaFormat::Heif => heif::replace_metadata(bytes, &exif, &xmp),
+        MediaFormat::Other => Ok(bytes.to_vec()),
+    }
+}
+
+// A TIFF structure with the tags of the original image, or only the exported ones without it
+fn exif_block(bytes: &[u8], metadata: &ExportMetadata) -> Result<Vec<u8>, String> {
+    let original = Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .ok();
+    let mut fields: Vec<Field> = original
+        .iter()
+        .flat_map(|exif| exif.fields())
+        .filter(|field| field.ifd_num == In::PRIMARY)
+        .filter(|field| {
+            !REPLACED_TAGS.contains(&field.tag)
+                || (field.tag == Tag::ImageDescription && metadata.description.is_none())
+                || (is_gps_position(field.tag) && metadata.latitude.is_none())
+        })
+        .cloned()
+        .collect();
+
+    let local = local_time(metadata)?;
+    fields.push(ascii(
+        Tag::DateTimeOriginal,
+        &local.format("%Y:%m:%d %H:%M:%S").to_string(),
+    ));
+    fields.push(ascii(
+        Tag::SubSecTimeOriginal,
+        &local.format("%3f").to_string(),
+    ));
+    if let Some(utc_offset) = metadata.utc_offset {
+        fields.push(ascii(Tag::OffsetTimeOriginal, &format_offset(utc_offset)));
+    }
+    if let (Some(latitude), Some(longitude)) = (metadata.latitude, metadata.longitude) {
+        fields.push(ascii(
+            Tag::GPSLatitudeRef,
+            if latitude < 0.0 { "S" } else { "N" },
+        ));
+        fields.push(rationals(Tag::GPSLatitude, degrees(latitude)));
+        fields.push(ascii(
+            Tag::GPSLongitudeRef,
+            if longitude < 0.0 { "W" } else { "E" },
+        ));
+        fields.push(rationals(Tag::GPSLongitude, degrees(longitude)));
+    }
+    if let Some(description) = &metadata.description {
+        fields.push(ascii(Tag::ImageDescription, description));
+    }
+
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut block = Cursor::new(Vec::new());
+    let little_endian = original.is_some_and(|exif| exif.little_endian());
+    writer
+        .write(&mut block, little_endian)
+        .map_err(|err| format!("Couldn't write the EXIF: {err}"))?;
+    Ok(block.into_inner())
+}
+
+// The XMP of the original with the exported values in place of its own, or only the exported
+// values when the original has none or it can't be parsed
+fn xmp_packet(original: Option<&str>, metadata: &ExportMetadata) -> String {
+    let description = xmp_description(metadata);
+    let merged =
+        original.and_then(|xmp| merge_xmp(xmp, &replaced_properties(metadata), &description).ok());
+    let xmpmeta = match merged {
+        Some(merged) if merged.starts_with("<x:xmpmeta") => merged,
+        // A bare rdf:RDF packet
+        Some(merged) => format!("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n{merged}\n</x:xmpmeta>"),
+        None => format!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">
+ <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+{description}
+ </rdf:RDF>
+</x:xmpmeta>"
+        ),
+    };
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n{xmpmeta}\n<?xpacket end=\"w\"?>"
+    )
+}
+
+// The namespace and name of the properties written by the export, which replace those of the original.
+// Mirrors xmp_description
+fn replaced_properties(metadata: &ExportMetadata) -> Vec<(&'static [u8], &'static [u8])> {
+    let mut replaced = Vec::new();
+    if local_time(metadata).is_ok() {
+        replaced.push((EXIF, b"DateTimeOriginal".as_slice()));
+        replaced.push((PHOTOSHOP, b"DateCreated".as_slice()));
+    }
+    if metadata.latitude.is_some() && metadata.longitude.is_some() {
+        replaced.push((EXIF, b"GPSLatitude".as_slice()));
+        replaced.push((EXIF, b"GPSLongitude".as_slice()));
+    }
+    if metadata.rating.is_some() {
+        replaced.push((XMP, b"Rating".as_slice()));
+    }
+    if metadata.title.is_some() {
+        replaced.push((DC, b"title".as_slice()));
+    }
+    if metadata.description.is_some() {
+        replaced.push((DC, b"description".as_slice()));
+    }
+    if !metadata.keywords.is_empty() {
+        replaced.push((DC, b"subject".as_slice()));
+    }
+    replaced
+}
+
+// Copies the original packet without the replaced properties, written either as attributes or as
+// elements of an rdf:Description, and adds the exported ones as a description of their own.
+// Everything else, e.g. the xmpNote:HasExtendedXMP reference to the rest of a JPEG packet, is kept
+fn merge_xmp(
+    original: &str,
+    replaced: &[(&[u8], &[u8])],
+    description: &str,
+) -> Result<String, String> {
+    let mut reader = NsReader::from_str(original);
+    let mut writer = quick_xml::Writer::new(Vec::new());
+    // Whether every open element is an rdf:Description
+    let mut open: Vec<bool> = Vec::new();
+    // How deep the reader is inside a replaced property
+    let mut skipped = 0;
+    let mut merged = false;
+
+    loop {
+        let (namespace, event) = reader
+            .read_resolved_event()
+            .map_err(|err| err.to_string())?;
+        if skipped > 0 {
+            match event {
+                Event::Start(_) => skipped += 1,
+                Event::End(_) => skipped -= 1,
+                Event::Eof => return Err("Unclosed XMP property".to_string()),
+                _ => {}
+            }
+            continue;
+        }
+        let in_description = open.last() == Some(&true);
+        let event = match event {
+            Event::Start(ref element) | Event::Empty(ref element)
+                if in_description
+                    && is_replaced(&namespace, element.local_name().as_ref(), replaced) =>
+            {
+                if matches!(event, Event::Start(_)) {
+                    skipped = 1;
+                }
+                continue;
+            }
+            Event::Start(element) => {
+                let is_description =
+                    is_rdf(&namespace, element.local_name().as_ref(), b"Description");
+                open.push(is_description);
+                if is_description {
+                    Event::Start(without_replaced(&reader, &element, replaced))
+                } else {
+                    Event::Start(element)
+                }
+            }
+            Event::Empty(element) => {
+                if is_rdf(&namespace, element.local_name().as_ref(), b"Description") {
+                    Event::Empty(without_replaced(&reader, &element, replaced))
+                } else {
+                    Event::Empty(element)
+                }
+            }
+            Event::End(element) => {
+                open.pop();
+                if is_rdf(&namespace, element.local_name().as_ref(), b"RDF") {
+                    writer
+                        .write_event(Event::Text(BytesText::from_escaped(description)))
+                        .map_err(|err| err.to_string())?;
+                    merged = true;
+                }
+                Event::End(element)
+            }
+            Event::Eof => break,
+            event => event,
+        };
+        writer.write_event(event).map_err(|err| err.to_string())?;
+    }
+
+    if !merged {
+        return Err("XMP without rdf:RDF".to_string());
+    }
+    String::from_utf8(writer.into_inner()).map_err(|err| err.to_string())
+}
+
+fn is_rdf(namespace: &ResolveResult, local_name: &[u8], name: &[u8]) -> bool {
+    matches!(namespace, ResolveResult::Bound(Namespace(RDF))) && local_name == name
+}
+
+fn is_replaced(namespace: &ResolveResult, local_name: &[u8], replaced: &[(&[u8], &[u8])]) -> bool {
+    let ResolveResult::Bound(Namespace(namespace)) = namespace else {
+        return false;
+    };
+    replaced.contains(&(namespace, local_name))
+}
+
+fn without_replaced(
+    reader: &NsReader<&[u8]>,
+    element: &BytesStart,
+    replaced: &[(&[u8], &[u8])],
+) -> BytesStart<'static> {
+    let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+    let mut kept = BytesStart::new(name);
+    for attribute in element.attributes().flatten() {
+        let (namespace, local_name) = reader.resolve_attribute(attribute.key);
+        if !is_replaced(&namespace, local_name.as_ref(), replaced) {
+            kept.push_attribute(attribute);
+        }
+    }
+    kept.into_owned()
+}
+
+// An rdf:Description holding the exported values
+fn xmp_description(metadata: &ExportMetadata) -> String {
+    let mut attributes = Vec::new();
+    let mut properties = Vec::new();
+
+    if let Ok(local) = local_time(metadata) {
+        let mut date = local.format("%Y-%m-%dT%H:%M:%S%.3f").to_string();
+        if let Some(utc_offset) = metadata.utc_offset {
+            date.push_str(&format_offset(utc_offset));
+        }
+        attributes.push(format!("exif:DateTimeOriginal=\"{date}\""));
+        attributes.push(format!("photoshop:DateCreated=\"{date}\""));
+    }
+    if let (Some(latitude), Some(longitude)) = (metadata.latitude, metadata.longitude) {
+        attributes.push(format!(
+            "exif:GPSLatitude=\"{}\"",
+            xmp_coordinate(latitude, 'N', 'S')
+        ));
+        attributes.push(format!(
+            "exif:GPSLongitude=\"{}\"",
+            xmp_coordinate(longitude, 'E', 'W')
+        ));
+    }
+    if let Some(rating) = metadata.rating {
+        attributes.push(format!("xmp:Rating=\"{rating}\""));
+    }
+    if let Some(title) = &metadata.title {
+        properties.push(format!(
+            "   <dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>",
+            escape(title)
+        ));
+    }
+    if let Some(description) = &metadata.description {
+        properties.push(format!(
+            "   <dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>",
+            escape(description)
+        ));
+    }
+    if !metadata.keywords.is_empty() {
+        let keywords: String = metadata
+            .keywords
+            .iter()
+            .map(|keyword| format!("<rdf:li>{}</rdf:li>", escape(keyword)))
+            .collect();
+        properties.push(format!(
+            "   <dc:subject><rdf:Bag>{keywords}</rdf:Bag></dc:subject>"
+        ));
+    }
+
+    format!(
+        "  <rdf:Description rdf:about=\"\"
+    xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\"
+    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"
+    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"
+    xmlns:exif=\"http://ns.adobe.com/exif/1.0/\"
+    xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\"
+    {}>
+{}
+  </rdf:Description>
+",
+        attributes.join("\n    "),
+        properties.join("\n")
+    )
+}
+
+fn local_time(metadata: &ExportMetadata) -> Result<chrono::NaiveDateTime, String> {
+    let local = metadata.created_at + metadata.utc_offset.unwrap_or(0) as i64 * MILLIS_PER_MINUTE;
+    DateTime::from_timestamp_millis(local)
+        .map(|date_time| date_time.naive_utc())
+        .ok_or_else(|| format!("Capture time {} is out of range", metadata.created_at))
+}
+
+fn format_offset(utc_offset: i32) -> String {
+    let sign = if utc_offset < 0 { '-' } else { '+' };
+    let minutes = utc_offset.abs();
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn is_gps_position(tag: Tag) -> bool {
+    matches!(
+        tag,
+        Tag::GPSLatitude | Tag::GPSLatitudeRef | Tag::GPSLongitude | Tag::GPSLongitudeRef
+    )
+}
+
+// Degrees, minutes and seconds to the thousandth
+fn degrees(coordinate: f64) -> [Rational; 3] {
+    let coordinate = coordinate.abs();
+    let degrees = coordinate.trunc();
+    let minutes = ((coordinate - degrees) * 60.0).trunc();
+    let seconds = (coordinate - degrees - minutes / 60.0) * 3600.0;
+    [
+        Rational::from((degrees as u32, 1)),
+        Rational::from((minutes as u32, 1)),
+        Rational::from(((seconds * 1000.0).round() as u32, 1000)),
+    ]
+}
+
+// XMP writes coordinates as degrees and decimal minutes followed by the hemisphere
+fn xmp_coordinate(coordinate: f64, positive: char, negative: char) -> String {
+    let hemisphere = if coordinate < 0.0 { negative } else { positive };
+    let coordinate = coordinate.abs();
+    let degrees = coordinate.trunc();
+    format!(
+        "{},{:.6}{hemisphere}",
+        degrees as u32,
+        (coordinate - degrees) * 60.0
+    )
+}
+
+fn ascii(tag: Tag, value: &str) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+    }
+}
+
+fn rationals(tag: Tag, values: [Rational; 3]) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Rational(values.to_vec()),
+    }
+}
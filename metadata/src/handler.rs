@@ -1,12 +1,23 @@
-use async_nats::jetstream::Message;
-use database::DbManager;
-use exif::{Exif, In, Reader, Tag, Value};
+use chrono::{NaiveDate, NaiveTime};
+use database::{CaptureTime, MediaMetadata, Repository};
+use exif::{DateTime, Exif, In, Reader, Tag, Value};
 use log::error;
+use messaging::Message;
 use s3::Bucket;
+use serde_json::{Map, Value as JsonValue};
 use std::io::Cursor;
 use std::str;
+use std::sync::Arc;
 
-pub async fn handle_request(msg: Message, bucket: Box<Bucket>, db: DbManager) {
+use crate::{
+    annotations::Annotations, capture_time::capture_time, iptc::parse_iptc, xmp::find_xmp,
+    xmp::parse_xmp,
+};
+
+// Where the api stores the XMP sidecar uploaded for a media
+const SIDECAR_PREFIX: &str = "xmp/";
+
+pub async fn handle_request(msg: Message, bucket: Box<Bucket>, db: Arc<dyn Repository>) {
     let payload_bytes: &[u8] = &msg.payload;
     let source_media_id = match str::from_utf8(payload_bytes) {
         Ok(path) => path.to_owned(),
@@ -24,44 +35,83 @@ pub async fn handle_request(msg: Message, bucket: Box<Bucket>, db: DbManager) {
         }
     };
 
+    let sidecar = bucket
+        .get_object(format!("{SIDECAR_PREFIX}{source_media_id}"))
+        .await
+        .ok()
+        .filter(|response| response.status_code() == 200);
+
     let source_media_bytes = source_media_response.bytes();
-    let mut bufreader = Cursor::new(source_media_bytes);
-    let exifreader = Reader::new();
-
-    match exifreader.read_from_container(&mut bufreader) {
-        Ok(exifdata) => {
-            let longitude = extract_longitude(&exifdata);
-            let latitude = extract_latitude(&exifdata);
-            let image_width = extract_image_width(&exifdata);
-            let image_length = extract_image_length(&exifdata);
-            let make = extract_make(&exifdata);
-            let model = extract_model(&exifdata);
-            let fnumber = extract_fnumber(&exifdata);
-            let exposure_time = extract_exposure_time(&exifdata);
-            let photographic_sensitivity = extract_photographic_sensitivity(&exifdata);
-            let orientation = extract_orientation(&exifdata);
-
-            let _ = db
-                .insert_metadata(
-                    source_media_id,
-                    longitude,
-                    latitude,
-                    image_width,
-                    image_length,
-                    make,
-                    model,
-                    fnumber,
-                    exposure_time,
-                    photographic_sensitivity,
-                    orientation,
-                )
-                .await;
-            let _ = msg.ack_with(async_nats::jetstream::AckKind::Ack).await;
-        }
-        Err(_) => {
-            let _ = msg.ack_with(async_nats::jetstream::AckKind::Term).await;
+    let exifdata = Reader::new()
+        .read_from_container(&mut Cursor::new(source_media_bytes))
+        .ok();
+    let annotations = read_annotations(
+        source_media_bytes,
+        sidecar.as_ref().map(|sidecar| sidecar.as_slice()),
+    );
+    if exifdata.is_none() && annotations.is_empty() {
+        let _ = msg.term().await;
+        return;
+    }
+
+    let mut metadata = exifdata
+        .as_ref()
+        .map(extract_exif_metadata)
+        .unwrap_or_default();
+    annotations.apply(&mut metadata);
+
+    if let Err(err) = db.insert_metadata(source_media_id, metadata).await {
+        error!("Couldn't store the metadata: {err}");
+    }
+    let _ = msg.ack().await;
+}
+
+// The embedded IPTC is the oldest, then comes the embedded XMP and last the sidecar
+// the photo manager exported next to the file
+fn read_annotations(media: &[u8], sidecar: Option<&[u8]>) -> Annotations {
+    let mut annotations = parse_iptc(media).unwrap_or_default();
+    for xmp in [Some(media), sidecar]
+        .into_iter()
+        .flatten()
+        .filter_map(find_xmp)
+    {
+        match parse_xmp(xmp) {
+            Ok(xmp) => annotations = annotations.merge(xmp),
+            Err(err) => error!("Couldn't parse the XMP packet: {err}"),
         }
-    };
+    }
+    annotations
+}
+
+fn extract_exif_metadata(exifdata: &Exif) -> MediaMetadata {
+    MediaMetadata {
+        longitude: extract_longitude(exifdata),
+        latitude: extract_latitude(exifdata),
+        image_width: extract_uint(exifdata, Tag::ImageWidth),
+        image_length: extract_uint(exifdata, Tag::ImageLength),
+        make: extract_text(exifdata, Tag::Make),
+        model: extract_text(exifdata, Tag::Model),
+        fnumber: extract_display(exifdata, Tag::FNumber),
+        exposure_time: extract_display(exifdata, Tag::ExposureTime),
+        photographic_sensitivity: extract_display(exifdata, Tag::PhotographicSensitivity),
+        orientation: extract_uint(exifdata, Tag::Orientation),
+        lens_make: extract_text(exifdata, Tag::LensMake),
+        lens_model: extract_text(exifdata, Tag::LensModel),
+        focal_length: extract_rational(exifdata, Tag::FocalLength),
+        focal_length_35mm: extract_uint(exifdata, Tag::FocalLengthIn35mmFilm),
+        altitude: extract_altitude(exifdata),
+        image_direction: extract_rational(exifdata, Tag::GPSImgDirection),
+        flash: extract_display(exifdata, Tag::Flash),
+        white_balance: extract_display(exifdata, Tag::WhiteBalance),
+        metering_mode: extract_display(exifdata, Tag::MeteringMode),
+        exposure_program: extract_display(exifdata, Tag::ExposureProgram),
+        exposure_bias: extract_rational(exifdata, Tag::ExposureBiasValue),
+        software: extract_text(exifdata, Tag::Software),
+        artist: extract_text(exifdata, Tag::Artist),
+        exif: Some(extract_tags(exifdata)),
+        capture_time: extract_capture_time(exifdata),
+        ..Default::default()
+    }
 }
 
 fn extract_longitude(exifdata: &Exif) -> Option<f64> {
@@ -114,61 +164,112 @@ fn extract_latitude(exifdata: &Exif) -> Option<f64> {
     None
 }
 
-fn extract_image_width(exifdata: &Exif) -> Option<i32> {
-    exifdata
-        .get_field(Tag::ImageWidth, In::PRIMARY)
+fn extract_altitude(exifdata: &Exif) -> Option<f64> {
+    let altitude = extract_rational(exifdata, Tag::GPSAltitude)?;
+    // A reference of 1 means below sea level
+    match exifdata
+        .get_field(Tag::GPSAltitudeRef, In::PRIMARY)
         .and_then(|field| field.value.get_uint(0))
-        .map(|value| value as i32)
+    {
+        Some(1) => Some(-altitude),
+        _ => Some(altitude),
+    }
 }
 
-fn extract_image_length(exifdata: &Exif) -> Option<i32> {
+fn extract_uint(exifdata: &Exif, tag: Tag) -> Option<i32> {
     exifdata
-        .get_field(Tag::ImageLength, In::PRIMARY)
+        .get_field(tag, In::PRIMARY)
         .and_then(|field| field.value.get_uint(0))
         .map(|value| value as i32)
 }
 
-fn extract_make(exifdata: &Exif) -> Option<String> {
-    exifdata.get_field(Tag::Make, In::PRIMARY).map(|field| {
-        field
-            .value
-            .display_as(field.tag)
-            .to_string()
-            .replace("\"", "")
-    })
+fn extract_rational(exifdata: &Exif, tag: Tag) -> Option<f64> {
+    match exifdata.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(ref values) => values.first().map(|value| value.to_f64()),
+        Value::SRational(ref values) => values.first().map(|value| value.to_f64()),
+        _ => None,
+    }
 }
 
-fn extract_model(exifdata: &Exif) -> Option<String> {
-    exifdata.get_field(Tag::Model, In::PRIMARY).map(|field| {
-        field
-            .value
-            .display_as(field.tag)
-            .to_string()
-            .replace("\"", "")
-    })
+fn extract_text(exifdata: &Exif, tag: Tag) -> Option<String> {
+    extract_ascii(exifdata, tag).map(ascii_to_string)
 }
 
-fn extract_fnumber(exifdata: &Exif) -> Option<String> {
+// The value as exif describes it, e.g. "f/2.8" or "fired, return light detected"
+fn extract_display(exifdata: &Exif, tag: Tag) -> Option<String> {
     exifdata
-        .get_field(Tag::FNumber, In::PRIMARY)
+        .get_field(tag, In::PRIMARY)
         .map(|field| field.value.display_as(field.tag).to_string())
 }
 
-fn extract_exposure_time(exifdata: &Exif) -> Option<String> {
-    exifdata
-        .get_field(Tag::ExposureTime, In::PRIMARY)
-        .map(|field| field.value.display_as(field.tag).to_string())
+// Every tag of the primary image by name, the maker note is skipped as it is
+// an opaque blob only the camera vendor can read
+fn extract_tags(exifdata: &Exif) -> JsonValue {
+    let tags: Map<String, JsonValue> = exifdata
+        .fields()
+        .filter(|field| field.ifd_num == In::PRIMARY && field.tag != Tag::MakerNote)
+        .map(|field| {
+            let value = match field.value {
+                Value::Ascii(ref data) => data
+                    .iter()
+                    .map(|data| ascii_to_string(data))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                _ => field.display_value().with_unit(exifdata).to_string(),
+            };
+            (field.tag.to_string(), JsonValue::String(value))
+        })
+        .collect();
+    JsonValue::Object(tags)
 }
 
-fn extract_photographic_sensitivity(exifdata: &Exif) -> Option<String> {
-    exifdata
-        .get_field(Tag::PhotographicSensitivity, In::PRIMARY)
-        .map(|field| field.value.display_as(field.tag).to_string())
+fn ascii_to_string(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches(['\0', ' '])
+        .to_string()
 }
 
-fn extract_orientation(exifdata: &Exif) -> Option<i32> {
-    exifdata
-        .get_field(Tag::Orientation, In::PRIMARY)
-        .and_then(|field| field.value.get_uint(0))
-        .map(|value| value as i32)
+fn extract_capture_time(exifdata: &Exif) -> Option<CaptureTime> {
+    capture_time(
+        extract_date_time_original(exifdata).as_ref(),
+        extract_gps_time(exifdata),
+    )
+}
+
+fn extract_date_time_original(exifdata: &Exif) -> Option<DateTime> {
+    let mut date_time = extract_ascii(exifdata, Tag::DateTimeOriginal)
+        .and_then(|data| DateTime::from_ascii(data).ok())?;
+    if let Some(data) = extract_ascii(exifdata, Tag::SubSecTimeOriginal) {
+        let _ = date_time.parse_subsec(data);
+    }
+    if let Some(data) = extract_ascii(exifdata, Tag::OffsetTimeOriginal) {
+        let _ = date_time.parse_offset(data);
+    }
+    Some(date_time)
+}
+
+// The GPS date and time are always in UTC
+fn extract_gps_time(exifdata: &Exif) -> Option<i64> {
+    let date = extract_ascii(exifdata, Tag::GPSDateStamp)
+        .and_then(|data| str::from_utf8(data).ok())
+        .and_then(|date| NaiveDate::parse_from_str(date.trim(), "%Y:%m:%d").ok())?;
+    let field = exifdata.get_field(Tag::GPSTimeStamp, In::PRIMARY)?;
+    let Value::Rational(ref values) = field.value else {
+        return None;
+    };
+    if values.len() != 3 {
+        return None;
+    }
+    let seconds = values[0].to_f64() * 3600.0 + values[1].to_f64() * 60.0 + values[2].to_f64();
+    Some(
+        date.and_time(NaiveTime::MIN).and_utc().timestamp_millis()
+            + (seconds * 1000.0).round() as i64,
+    )
+}
+
+fn extract_ascii(exifdata: &Exif, tag: Tag) -> Option<&[u8]> {
+    match exifdata.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(ref data) => data.first().map(Vec::as_slice),
+        _ => None,
+    }
 }
@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+
+const EXIF_PREFIX: &[u8] = b"\0\0\0\x06Exif\0\0";
+const XMP_CONTENT_TYPE: &[u8] = b"application/rdf+xml";
+
+struct IsoBox {
+    kind: [u8; 4],
+    start: usize,
+    content: usize,
+    end: usize,
+    // A size of 0 stands for a box running to the end of the file
+    open_ended: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ItemKind {
+    Exif,
+    Xmp,
+    Other,
+}
+
+// Where the extents of an item are, as positions of their offset and length fields
+struct ItemLocation {
+    construction_method: u8,
+    base_offset: u64,
+    base_offset_at: usize,
+    extents: Vec<(usize, usize)>,
+}
+
+struct Iloc {
+    version: u8,
+    offset_size: usize,
+    length_size: usize,
+    base_offset_size: usize,
+    index_size: usize,
+    id_size: usize,
+    item_count: u64,
+    // Where new entries can be written, after the last one
+    entries_end: usize,
+    items: HashMap<u32, ItemLocation>,
+}
+
+// Points the Exif and XMP items of a HEIF file to new data appended in a trailing mdat box.
+// The item locations are patched in place so no other offset in the file moves, which
+// leaves the old Exif unreferenced and the old XMP blanked out. Missing items are added first
+pub fn replace_metadata(heif: &[u8], exif: &[u8], xmp: &str) -> Result<Vec<u8>, String> {
+    let top_level = boxes(heif, 0, heif.len())?;
+    let meta = top_level
+        .iter()
+        .find(|iso_box| &iso_box.kind == b"meta")
+        .ok_or("HEIF file without a meta box")?;
+    // meta is a full box, its children come after the version and flags
+    let children = boxes(heif, meta.content + 4, meta.end)?;
+    let items = item_infos(heif, child(&children, b"iinf")?)?;
+    let item_of = |kind: ItemKind| {
+        items
+            .iter()
+            .find(|(_, item_kind)| *item_kind == kind)
+            .map(|(item_id, _)| *item_id)
+    };
+    let (Some(exif_item), Some(xmp_item)) = (item_of(ItemKind::Exif), item_of(ItemKind::Xmp))
+    else {
+        let missing = [ItemKind::Exif, ItemKind::Xmp]
+            .into_iter()
+            .filter(|kind| item_of(*kind).is_none())
+            .collect();
+        let with_items = add_items(heif, &top_level, meta, &children, &items, missing)?;
+        return replace_metadata(&with_items, exif, xmp);
+    };
+    let iloc = item_locations(heif, child(&children, b"iloc")?)?;
+
+    let mut output = heif.to_vec();
+    if let Some(last) = top_level.last().filter(|iso_box| iso_box.open_ended) {
+        let size = u32::try_from(last.end - last.start).map_err(|_| "HEIF box too large")?;
+        output[last.start..last.start + 4].copy_from_slice(&size.to_be_bytes());
+    }
+
+    let mut mdat = Vec::new();
+    let data_start = heif.len() + 8;
+    mdat.extend_from_slice(EXIF_PREFIX);
+    mdat.extend_from_slice(exif);
+    relocate(&mut output, &iloc, exif_item, data_start, mdat.len())?;
+
+    let (old_offset, old_length) = item_extent(heif, &iloc, xmp_item)?;
+    output[old_offset..old_offset + old_length].fill(b' ');
+    relocate(
+        &mut output,
+        &iloc,
+        xmp_item,
+        data_start + mdat.len(),
+        xmp.len(),
+    )?;
+    mdat.extend_from_slice(xmp.as_bytes());
+
+    let size = u32::try_from(8 + mdat.len()).map_err(|_| "Metadata too large")?;
+    output.extend_from_slice(&size.to_be_bytes());
+    output.extend_from_slice(b"mdat");
+    output.extend_from_slice(&mdat);
+    Ok(output)
+}
+
+fn boxes(data: &[u8], from: usize, to: usize) -> Result<Vec<IsoBox>, String> {
+    let mut boxes = Vec::new();
+    let mut position = from;
+    while position < to {
+        let size = read_uint(data, position, 4)? as usize;
+        let kind: [u8; 4] = data
+            .get(position + 4..position + 8)
+            .and_then(|kind| kind.try_into().ok())
+            .ok_or("Truncated HEIF box")?;
+        let open_ended = size == 0;
+        let (header, size) = match size {
+            0 => (8, to - position),
+            1 => (16, read_uint(data, position + 8, 8)? as usize),
+            size => (8, size),
+        };
+        if size < header || position + size > to {
+            return Err(format!("Invalid HEIF box size at {position}"));
+        }
+        boxes.push(IsoBox {
+            kind,
+            start: position,
+            content: position + header,
+            end: position + size,
+            open_ended,
+        });
+        position += size;
+    }
+    Ok(boxes)
+}
+
+fn child<'a>(children: &'a [IsoBox], kind: &[u8; 4]) -> Result<&'a IsoBox, String> {
+    children
+        .iter()
+        .find(|iso_box| &iso_box.kind == kind)
+        .ok_or_else(|| format!("HEIF meta box without {}", String::from_utf8_lossy(kind)))
+}
+
+// The id and kind of every item, the XMP being stored as a mime item
+fn item_infos(data: &[u8], iinf: &IsoBox) -> Result<Vec<(u32, ItemKind)>, String> {
+    let version = *data.get(iinf.content).ok_or("Truncated iinf box")?;
+    let entries_at = iinf.content + 4 + if version == 0 { 2 } else { 4 };
+
+    let mut items = Vec::new();
+    for infe in boxes(data, entries_at, iinf.end)? {
+        let version = *data.get(infe.content).ok_or("Truncated infe box")?;
+        let id_size = if version < 3 { 2 } else { 4 };
+        let item_id = read_uint(data, infe.content + 4, id_size)? as u32;
+        if version < 2 {
+            items.push((item_id, ItemKind::Other));
+            continue;
+        }
+        let type_at = infe.content + 4 + id_size + 2;
+        let item_type = data.get(type_at..type_at + 4).ok_or("Truncated infe box")?;
+        let kind = match item_type {
+            b"Exif" => ItemKind::Exif,
+            b"mime" => {
+                // The name and the content type follow the type as null terminated strings
+                let mut strings = data[type_at + 4..infe.end].split(|byte| *byte == 0);
+                let content_type = strings.nth(1).unwrap_or_default();
+                if content_type == XMP_CONTENT_TYPE {
+                    ItemKind::Xmp
+                } else {
+                    ItemKind::Other
+                }
+            }
+            _ => ItemKind::Other,
+        };
+        items.push((item_id, kind));
+    }
+    Ok(items)
+}
+
+// Adds empty items of the missing kinds, described as metadata of the primary image.
+// The meta box grows, so the items stored after it are moved by as much in the item locations
+fn add_items(
+    heif: &[u8],
+    top_level: &[IsoBox],
+    meta: &IsoBox,
+    children: &[IsoBox],
+    items: &[(u32, ItemKind)],
+    missing: Vec<ItemKind>,
+) -> Result<Vec<u8>, String> {
+    // The sample offsets of image sequences would have to move as well
+    if top_level.iter().any(|iso_box| &iso_box.kind == b"moov") {
+        return Err("Metadata items can't be added to HEIF sequences".to_string());
+    }
+    let iinf = child(children, b"iinf")?;
+    let iloc_box = child(children, b"iloc")?;
+    let iloc = item_locations(heif, iloc_box)?;
+    let primary_item = primary_item(heif, child(children, b"pitm")?)?;
+    let last_id = items
+        .iter()
+        .map(|(item_id, _)| *item_id)
+        .chain(iloc.items.keys().copied())
+        .max()
+        .unwrap_or(0);
+    let new_items: Vec<(u32, ItemKind)> = missing
+        .into_iter()
+        .zip(last_id + 1..)
+        .map(|(kind, item_id)| (item_id, kind))
+        .collect();
+    let iref = children.iter().find(|iso_box| &iso_box.kind == b"iref");
+
+    let build = |shift: u64| -> Result<Vec<u8>, String> {
+        let mut content = heif[meta.content..meta.content + 4].to_vec();
+        for iso_box in children {
+            match &iso_box.kind {
+                b"iinf" => content.extend(grown_iinf(heif, iinf, &new_items)?),
+                b"iloc" => {
+                    content.extend(grown_iloc(heif, iloc_box, &iloc, &new_items, meta, shift)?);
+                    if iref.is_none() {
+                        content.extend(new_iref(&new_items, primary_item)?);
+                    }
+                }
+                b"iref" => content.extend(grown_iref(heif, iso_box, &new_items, primary_item)?),
+                _ => content.extend_from_slice(&heif[iso_box.start..iso_box.end]),
+            }
+        }
+        iso_box(b"meta", &content)
+    };
+    let shift = (build(0)?.len() - (meta.end - meta.start)) as u64;
+    let meta_box = build(shift)?;
+    Ok([&heif[..meta.start], &meta_box, &heif[meta.end..]].concat())
+}
+
+fn primary_item(data: &[u8], pitm: &IsoBox) -> Result<u32, String> {
+    let version = *data.get(pitm.content).ok_or("Truncated pitm box")?;
+    let id_size = if version == 0 { 2 } else { 4 };
+    Ok(read_uint(data, pitm.content + 4, id_size)? as u32)
+}
+
+fn grown_iinf(
+    data: &[u8],
+    iinf: &IsoBox,
+    new_items: &[(u32, ItemKind)],
+) -> Result<Vec<u8>, String> {
+    let mut content = data[iinf.content..iinf.end].to_vec();
+    let count_size = if content[0] == 0 { 2 } else { 4 };
+    let count = read_uint(&content, 4, count_size)?;
+    write_uint(&mut content, 4, count_size, count + new_items.len() as u64)?;
+    for (item_id, kind) in new_items {
+        let mut infe = if *item_id > u16::MAX as u32 {
+            [vec![3, 0, 0, 0], uint(*item_id as u64, 4)?].concat()
+        } else {
+            [vec![2, 0, 0, 0], uint(*item_id as u64, 2)?].concat()
+        };
+        // No protection, then the type, an empty name and the content type of a mime item
+        infe.extend_from_slice(&[0, 0]);
+        match kind {
+            ItemKind::Xmp => {
+                infe.extend_from_slice(b"mime ");
+                infe.extend_from_slice(XMP_CONTENT_TYPE);
+                infe.push(0);
+            }
+            _ => infe.extend_from_slice(b"Exif "),
+        }
+        content.extend(iso_box(b"infe", &infe)?);
+    }
+    iso_box(b"iinf", &content)
+}
+
+// The item locations with those stored after the meta box shifted, and an empty extent for every new item
+fn grown_iloc(
+    data: &[u8],
+    iloc_box: &IsoBox,
+    iloc: &Iloc,
+    new_items: &[(u32, ItemKind)],
+    meta: &IsoBox,
+    shift: u64,
+) -> Result<Vec<u8>, String> {
+    let mut content = data[iloc_box.content..iloc_box.end].to_vec();
+    let at = |position: usize| position - iloc_box.content;
+    write_uint(
+        &mut content,
+        6,
+        iloc.id_size,
+        iloc.item_count + new_items.len() as u64,
+    )?;
+    let meta_end = meta.end as u64;
+    for item in iloc.items.values() {
+        if item.construction_method != 0 {
+            continue;
+        }
+        if iloc.base_offset_size > 0 && item.base_offset >= meta_end {
+            let base_offset = item.base_offset + shift;
+            write_uint(
+                &mut content,
+                at(item.base_offset_at),
+                iloc.base_offset_size,
+                base_offset,
+            )?;
+            continue;
+        }
+        for (offset_at, _) in &item.extents {
+            let offset = read_uint(data, *offset_at, iloc.offset_size)?;
+            if item.base_offset + offset >= meta_end {
+                write_uint(
+                    &mut content,
+                    at(*offset_at),
+                    iloc.offset_size,
+                    offset + shift,
+                )?;
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (item_id, _) in new_items {
+        entries.extend(uint(*item_id as u64, iloc.id_size)?);
+        if iloc.version > 0 {
+            // Stored in the file itself
+            entries.extend_from_slice(&[0, 0]);
+        }
+        // Data reference, base offset and a single extent
+        entries.extend_from_slice(&[0, 0]);
+        entries.resize(entries.len() + iloc.base_offset_size, 0);
+        entries.extend_from_slice(&1u16.to_be_bytes());
+        entries.resize(
+            entries.len() + iloc.index_size + iloc.offset_size + iloc.length_size,
+            0,
+        );
+    }
+    let entries_end = at(iloc.entries_end);
+    content.splice(entries_end..entries_end, entries);
+    iso_box(b"iloc", &content)
+}
+
+// Content description references from every new item to the primary image
+fn references(
+    new_items: &[(u32, ItemKind)],
+    primary_item: u32,
+    id_size: usize,
+) -> Result<Vec<u8>, String> {
+    let mut references = Vec::new();
+    for (item_id, _) in new_items {
+        let reference = [
+            uint(*item_id as u64, id_size)?,
+            1u16.to_be_bytes().to_vec(),
+            uint(primary_item as u64, id_size)?,
+        ]
+        .concat();
+        references.extend(iso_box(b"cdsc", &reference)?);
+    }
+    Ok(references)
+}
+
+fn grown_iref(
+    data: &[u8],
+    iref: &IsoBox,
+    new_items: &[(u32, ItemKind)],
+    primary_item: u32,
+) -> Result<Vec<u8>, String> {
+    let mut content = data[iref.content..iref.end].to_vec();
+    let id_size = if content.first() == Some(&0) { 2 } else { 4 };
+    content.extend(references(new_items, primary_item, id_size)?);
+    iso_box(b"iref", &content)
+}
+
+fn new_iref(new_items: &[(u32, ItemKind)], primary_item: u32) -> Result<Vec<u8>, String> {
+    let wide = new_items
+        .iter()
+        .any(|(item_id, _)| *item_id > u16::MAX as u32)
+        || primary_item > u16::MAX as u32;
+    let (version, id_size) = if wide { (1, 4) } else { (0, 2) };
+    let content = [
+        vec![version, 0, 0, 0],
+        references(new_items, primary_item, id_size)?,
+    ]
+    .concat();
+    iso_box(b"iref", &content)
+}
+
+fn iso_box(kind: &[u8; 4], content: &[u8]) -> Result<Vec<u8>, String> {
+    let size = u32::try_from(content.len() + 8).map_err(|_| "HEIF box too large")?;
+    Ok([&size.to_be_bytes(), kind.as_slice(), content].concat())
+}
+
+fn item_locations(data: &[u8], iloc: &IsoBox) -> Result<Iloc, String> {
+    let version = *data.get(iloc.content).ok_or("Truncated iloc box")?;
+    let mut position = iloc.content + 4;
+    let sizes = read_uint(data, position, 2)?;
+    let offset_size = (sizes >> 12) as usize;
+    let length_size = ((sizes >> 8) & 0xf) as usize;
+    let base_offset_size = ((sizes >> 4) & 0xf) as usize;
+    let index_size = if version == 0 {
+        0
+    } else {
+        (sizes & 0xf) as usize
+    };
+    position += 2;
+    let id_size = if version < 2 { 2 } else { 4 };
+    let item_count = read_uint(data, position, id_size)?;
+    position += id_size;
+
+    let mut items = HashMap::new();
+    for _ in 0..item_count {
+        let item_id = read_uint(data, position, id_size)? as u32;
+        position += id_size;
+        let mut construction_method = 0;
+        if version > 0 {
+            construction_method = (read_uint(data, position, 2)? & 0xf) as u8;
+            position += 2;
+        }
+        // Data reference index
+        position += 2;
+        let base_offset_at = position;
+        let base_offset = read_uint(data, position, base_offset_size)?;
+        position += base_offset_size;
+        let extent_count = read_uint(data, position, 2)?;
+        position += 2;
+
+        let mut extents = Vec::new();
+        for _ in 0..extent_count {
+            position += index_size;
+            extents.push((position, position + offset_size));
+            position += offset_size + length_size;
+        }
+        items.insert(
+            item_id,
+            ItemLocation {
+                construction_method,
+                base_offset,
+                base_offset_at,
+                extents,
+            },
+        );
+    }
+    if position > iloc.end {
+        return Err("Truncated iloc box".to_string());
+    }
+    Ok(Iloc {
+        version,
+        offset_size,
+        length_size,
+        base_offset_size,
+        index_size,
+        id_size,
+        item_count,
+        entries_end: position,
+        items,
+    })
+}
+
+// Only items stored in a single extent of the file itself can be moved
+fn movable_item(iloc: &Iloc, item_id: u32) -> Result<&ItemLocation, String> {
+    let item = iloc
+        .items
+        .get(&item_id)
+        .ok_or_else(|| format!("HEIF item {item_id} has no location"))?;
+    if item.construction_method != 0
+        || item.extents.len() != 1
+        || ![4, 8].contains(&iloc.offset_size)
+        || ![4, 8].contains(&iloc.length_size)
+    {
+        return Err(format!("HEIF item {item_id} can't be moved"));
+    }
+    Ok(item)
+}
+
+fn item_extent(data: &[u8], iloc: &Iloc, item_id: u32) -> Result<(usize, usize), String> {
+    let item = movable_item(iloc, item_id)?;
+    let (offset_at, length_at) = item.extents[0];
+    let offset = item.base_offset + read_uint(data, offset_at, iloc.offset_size)?;
+    let length = read_uint(data, length_at, iloc.length_size)?;
+    let (offset, length) = (offset as usize, length as usize);
+    if offset + length > data.len() {
+        return Err(format!("HEIF item {item_id} is out of the file"));
+    }
+    Ok((offset, length))
+}
+
+fn relocate(
+    output: &mut [u8],
+    iloc: &Iloc,
+    item_id: u32,
+    offset: usize,
+    length: usize,
+) -> Result<(), String> {
+    let item = movable_item(iloc, item_id)?;
+    let (offset_at, length_at) = item.extents[0];
+    let offset = (offset as u64)
+        .checked_sub(item.base_offset)
+        .ok_or("HEIF base offset past the end of the file")?;
+    write_uint(output, offset_at, iloc.offset_size, offset)?;
+    write_uint(output, length_at, iloc.length_size, length as u64)
+}
+
+fn read_uint(data: &[u8], position: usize, size: usize) -> Result<u64, String> {
+    let bytes = data
+        .get(position..position + size)
+        .ok_or("Truncated HEIF file")?;
+    Ok(bytes
+        .iter()
+        .fold(0, |value, byte| (value << 8) | *byte as u64))
+}
+
+fn uint(value: u64, size: usize) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![0; size];
+    write_uint(&mut bytes, 0, size, value)?;
+    Ok(bytes)
+}
+
+fn write_uint(data: &mut [u8], position: usize, size: usize, value: u64) -> Result<(), String> {
+    if size < 8 && value >> (size * 8) != 0 {
+        return Err(format!("{value} doesn't fit in {size} bytes"));
+    }
+    let bytes = data
+        .get_mut(position..position + size)
+        .ok_or("Truncated HEIF file")?;
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value >> ((size - 1 - index) * 8)) as u8;
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use crate::annotations::{add_unique, Annotations};
+
+const PHOTOSHOP_SEGMENT: &[u8] = b"Photoshop 3.0\0";
+const RESOURCE_SIGNATURE: &[u8] = b"8BIM";
+const IPTC_RESOURCE: u16 = 0x0404;
+const TAG_MARKER: u8 = 0x1c;
+const APPLICATION_RECORD: u8 = 2;
+const OBJECT_NAME: u8 = 5;
+const KEYWORDS: u8 = 25;
+const CAPTION: u8 = 120;
+
+// Reads the title, caption and keywords of the IPTC-IIM block Photoshop and most
+// photo managers store in the APP13 segment of JPEG files
+pub fn parse_iptc(bytes: &[u8]) -> Option<Annotations> {
+    let start = bytes
+        .windows(PHOTOSHOP_SEGMENT.len())
+        .position(|window| window == PHOTOSHOP_SEGMENT)?
+        + PHOTOSHOP_SEGMENT.len();
+    let iim = find_iptc_resource(&bytes[start..])?;
+
+    let mut annotations = Annotations::default();
+    let mut position = 0;
+    while position + 5 <= iim.len() && iim[position] == TAG_MARKER {
+        let record = iim[position + 1];
+        let dataset = iim[position + 2];
+        let size = u16::from_be_bytes([iim[position + 3], iim[position + 4]]) as usize;
+        // Extended datasets over 32kB are not used by any of the fields read here
+        if size & 0x8000 != 0 {
+            break;
+        }
+        let value = iim.get(position + 5..position + 5 + size)?;
+        position += 5 + size;
+
+        if record != APPLICATION_RECORD {
+            continue;
+        }
+        let value = decode(value);
+        match dataset {
+            OBJECT_NAME => annotations.title = Some(value).filter(|value| !value.is_empty()),
+            CAPTION => annotations.description = Some(value).filter(|value| !value.is_empty()),
+            KEYWORDS => add_unique(&mut annotations.keywords, value),
+            _ => {}
+        }
+    }
+    Some(annotations)
+}
+
+// Walks the Photoshop image resources, each made of the signature, an id, a padded
+// pascal string name and a padded block of data
+fn find_iptc_resource(mut resources: &[u8]) -> Option<&[u8]> {
+    while resources.starts_with(RESOURCE_SIGNATURE) {
+        let id = u16::from_be_bytes([*resources.get(4)?, *resources.get(5)?]);
+        let name_length = *resources.get(6)? as usize;
+        let name_size = (1 + name_length + 1) & !1;
+        let size_at = 6 + name_size;
+        let size =
+            u32::from_be_bytes(resources.get(size_at..size_at + 4)?.try_into().ok()?) as usize;
+        let data_at = size_at + 4;
+        let data = resources.get(data_at..data_at + size)?;
+        if id == IPTC_RESOURCE {
+            return Some(data);
+        }
+        resources = resources.get(data_at + ((size + 1) & !1)..)?;
+    }
+    None
+}
+
+// Modern writers use UTF-8, older ones Latin-1
+fn decode(value: &[u8]) -> String {
+    let text = match std::str::from_utf8(value) {
+        Ok(text) => text.to_string(),
+        Err(_) => value.iter().map(|&byte| byte as char).collect(),
+    };
+    text.trim_end_matches('\0').trim().to_string()
+}
@@ -0,0 +1,75 @@
+const SOI: [u8; 2] = [0xff, 0xd8];
+const APP0: u8 = 0xe0;
+const APP1: u8 = 0xe1;
+const SOS: u8 = 0xda;
+const EOI: u8 = 0xd9;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+// The length of a segment is 16 bits and counts itself
+const MAX_SEGMENT_DATA: usize = u16::MAX as usize - 2;
+
+// Swaps the EXIF and XMP segments of a JPEG for new ones, placed right after the JFIF header
+// when there is one. ExtendedXMP segments are kept, the new XMP carries over the reference to
+// them. Only the segments before the image data are parsed, the rest is copied
+pub fn replace_metadata(jpeg: &[u8], exif: &[u8], xmp: &str) -> Result<Vec<u8>, String> {
+    if !jpeg.starts_with(&SOI) {
+        return Err("Not a JPEG file".to_string());
+    }
+    let exif_segment = segment(APP1, &[EXIF_HEADER, exif])?;
+    let xmp_segment = segment(APP1, &[XMP_HEADER, xmp.as_bytes()])?;
+
+    let mut output = Vec::with_capacity(jpeg.len() + exif_segment.len() + xmp_segment.len());
+    output.extend_from_slice(&SOI);
+    let mut inserted = false;
+    let mut position = SOI.len();
+    loop {
+        let marker = match jpeg.get(position..position + 2) {
+            Some([0xff, 0xff]) => {
+                // Fill byte before a marker
+                position += 1;
+                continue;
+            }
+            Some([0xff, marker]) => *marker,
+            _ => return Err(format!("Invalid JPEG marker at {position}")),
+        };
+        if !inserted && marker != APP0 {
+            output.extend_from_slice(&exif_segment);
+            output.extend_from_slice(&xmp_segment);
+            inserted = true;
+        }
+        if marker == SOS || marker == EOI {
+            output.extend_from_slice(&jpeg[position..]);
+            return Ok(output);
+        }
+
+        let length = jpeg
+            .get(position + 2..position + 4)
+            .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+            .ok_or("Truncated JPEG segment")?;
+        let end = position + 2 + length;
+        let data = jpeg
+            .get(position + 4..end)
+            .ok_or("Truncated JPEG segment")?;
+        let replaced = marker == APP1
+            && [EXIF_HEADER, XMP_HEADER]
+                .iter()
+                .any(|header| data.starts_with(header));
+        if !replaced {
+            output.extend_from_slice(&jpeg[position..end]);
+        }
+        position = end;
+    }
+}
+
+fn segment(marker: u8, parts: &[&[u8]]) -> Result<Vec<u8>, String> {
+    let size: usize = parts.iter().map(|part| part.len()).sum();
+    if size > MAX_SEGMENT_DATA {
+        return Err(format!("{size} bytes don't fit in a JPEG segment"));
+    }
+    let mut segment = vec![0xff, marker];
+    segment.extend_from_slice(&((size + 2) as u16).to_be_bytes());
+    for part in parts {
+        segment.extend_from_slice(part);
+    }
+    Ok(segment)
+}
@@ -0,0 +1,54 @@
+mod annotations;
+mod capture_time;
+mod export;
+mod handler;
+mod heif;
+mod iptc;
+mod jpeg;
+mod xmp;
+use database::Repository;
+use futures_util::StreamExt;
+use handler::handle_request;
+use log::{error, info};
+use messaging::{BusError, MessageBus};
+use s3::Bucket;
+use std::sync::Arc;
+
+pub use annotations::Annotations;
+pub use capture_time::capture_time;
+pub use export::{export_media, ExportMetadata, MediaFormat};
+pub use iptc::parse_iptc;
+pub use xmp::{find_xmp, parse_xmp};
+
+// Consumes the metadata subject until the bus closes the subscription
+pub async fn run(
+    bus: Arc<dyn MessageBus>,
+    bucket: Box<Bucket>,
+    db: Arc<dyn Repository>,
+    concurrency: usize,
+) -> Result<(), BusError> {
+    // FIXME: crate a const or a env var for the metadata consumer
+    let messages = bus.subscribe("metadata", "metadata_consumer").await?;
+    messages
+        .for_each_concurrent(concurrency, |msg| {
+            let thread_bucket = bucket.clone();
+            let thread_db = db.clone();
+            async move {
+                match msg {
+                    Ok(msg) => {
+                        info!(
+                            "Message received: {:?}",
+                            String::from_utf8(msg.payload.to_vec())
+                        );
+
+                        handle_request(msg, thread_bucket, thread_db).await;
+                    }
+                    Err(err) => {
+                        error!("Error receiving message: {err}");
+                    }
+                }
+            }
+        })
+        .await;
+    Ok(())
+}
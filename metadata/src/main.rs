@@ -1,11 +1,11 @@
-mod handler;
 use database::DbManager;
-use futures_util::StreamExt;
-use handler::handle_request;
-use log::{error, info};
+use messaging::NatsBus;
+use metadata::run;
 use s3::{creds::Credentials, error::S3Error, Bucket, BucketConfiguration, Region};
 use serde::Deserialize;
-use std::error::Error;
+use std::{error::Error, sync::Arc};
+
+const WORKER_CONCURRENCY: usize = 5;
 
 #[derive(Deserialize, Debug)]
 pub struct EnvVars {
@@ -47,58 +47,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let bucket = setup_bucket(&envs).await?;
 
-    let client = match async_nats::connect(envs.nats_endpoint).await {
-        Ok(c) => c,
+    let bus = match NatsBus::connect(envs.nats_endpoint).await {
+        Ok(bus) => bus,
         Err(err) => {
             panic!("Couldn't connect nats client: {err}");
         }
     };
 
-    let jetstream = async_nats::jetstream::new(client);
-    let stream_name = String::from("metadata");
-    let stream = jetstream
-        .get_or_create_stream(async_nats::jetstream::stream::Config {
-            name: stream_name.clone(),
-            max_messages: 10000,
-            ..Default::default()
-        })
-        .await?;
-
-    // FIXME: crate a const or a env var for the preview consumer
-    let consumer = stream
-        .get_or_create_consumer(
-            "metadata_consumer",
-            async_nats::jetstream::consumer::pull::Config {
-                durable_name: Some("metadata_consumer".to_string()),
-                filter_subject: "metadata".to_string(),
-                ..Default::default()
-            },
-        )
-        .await?;
-
-    let messages = consumer.messages().await?;
-    let thread_limit = 5;
-    let _ = messages
-        .for_each_concurrent(thread_limit, |msg| {
-            let thread_bucket = bucket.clone();
-            let thread_db = db.clone();
-            async move {
-                match msg {
-                    Ok(msg) => {
-                        info!(
-                            "Message received: {:?}",
-                            String::from_utf8(msg.payload.to_vec())
-                        );
-
-                        handle_request(msg, thread_bucket, thread_db).await;
-                    }
-                    Err(err) => {
-                        error!("Error receiving message: {err}");
-                    }
-                }
-            }
-        })
-        .await;
+    run(Arc::new(bus), bucket, Arc::new(db), WORKER_CONCURRENCY).await?;
     Ok(())
 }
 
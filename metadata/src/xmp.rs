@@ -0,0 +1,134 @@
+use quick_xml::{
+    events::{BytesStart, Event},
+    name::ResolveResult,
+    NsReader,
+};
+
+use crate::annotations::{add_unique, Annotations};
+
+const DC: &[u8] = b"http://purl.org/dc/elements/1.1/";
+const XMP: &[u8] = b"http://ns.adobe.com/xap/1.0/";
+const MWG_RS: &[u8] = b"http://www.metadataworkinggroup.com/schemas/regions/";
+const IPTC_EXT: &[u8] = b"http://iptc.org/std/Iptc4xmpExt/2008-02-29/";
+
+const PACKETS: [(&[u8], &[u8]); 2] = [
+    (b"<x:xmpmeta", b"</x:xmpmeta>"),
+    (b"<rdf:RDF", b"</rdf:RDF>"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Property {
+    Rating,
+    Title,
+    Description,
+    Keywords,
+    Person,
+    Other,
+}
+
+// The first XMP packet embedded in a file, which is stored as plain text by JPEG, PNG and HEIF alike
+pub fn find_xmp(bytes: &[u8]) -> Option<&str> {
+    PACKETS.iter().find_map(|(start, end)| {
+        let from = find(bytes, start, 0)?;
+        let to = find(bytes, end, from)? + end.len();
+        std::str::from_utf8(&bytes[from..to]).ok()
+    })
+}
+
+// Reads the rating, the Dublin Core title, description and subject, and the people from
+// IPTC extension and MWG face regions. Properties can be written both as attributes and
+// as elements, with lists nested in rdf containers, so every text is attributed to the
+// closest known property around it
+pub fn parse_xmp(xml: &str) -> Result<Annotations, String> {
+    let mut reader = NsReader::from_str(xml);
+    let mut annotations = Annotations::default();
+    let mut properties: Vec<Property> = Vec::new();
+
+    loop {
+        match reader.read_resolved_event() {
+            Ok((namespace, Event::Start(element))) => {
+                let property = property(namespace, element.local_name().as_ref());
+                read_attributes(&reader, &element, &mut annotations);
+                properties.push(property);
+            }
+            Ok((_, Event::Empty(element))) => read_attributes(&reader, &element, &mut annotations),
+            Ok((_, Event::End(_))) => {
+                properties.pop();
+            }
+            Ok((_, Event::Text(text))) => {
+                let Some(property) = properties
+                    .iter()
+                    .rev()
+                    .find(|property| **property != Property::Other)
+                else {
+                    continue;
+                };
+                let text = text.unescape().map_err(|err| err.to_string())?;
+                apply(&mut annotations, *property, &text);
+            }
+            Ok((_, Event::Eof)) => return Ok(annotations),
+            Ok(_) => {}
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+fn read_attributes(reader: &NsReader<&[u8]>, element: &BytesStart, annotations: &mut Annotations) {
+    for attribute in element.attributes().flatten() {
+        let (namespace, local_name) = reader.resolve_attribute(attribute.key);
+        let property = property(namespace, local_name.as_ref());
+        if property == Property::Other {
+            continue;
+        }
+        if let Ok(value) = attribute.unescape_value() {
+            apply(annotations, property, &value);
+        }
+    }
+}
+
+fn property(namespace: ResolveResult, local_name: &[u8]) -> Property {
+    let ResolveResult::Bound(namespace) = namespace else {
+        return Property::Other;
+    };
+    match (namespace.as_ref(), local_name) {
+        (XMP, b"Rating") => Property::Rating,
+        (DC, b"title") => Property::Title,
+        (DC, b"description") => Property::Description,
+        (DC, b"subject") => Property::Keywords,
+        (IPTC_EXT, b"PersonInImage") | (MWG_RS, b"Name") => Property::Person,
+        _ => Property::Other,
+    }
+}
+
+// Titles and descriptions can be given in several languages, the first one is kept
+fn apply(annotations: &mut Annotations, property: Property, text: &str) {
+    let text = text.trim();
+    if text.is_empty() {
+        return;
+    }
+    match property {
+        Property::Rating => {
+            if let Ok(rating) = text.parse::<f32>() {
+                annotations.rating = Some((rating.round() as i32).clamp(-1, 5));
+            }
+        }
+        Property::Title => {
+            annotations.title.get_or_insert_with(|| text.to_string());
+        }
+        Property::Description => {
+            annotations
+                .description
+                .get_or_insert_with(|| text.to_string());
+        }
+        Property::Keywords => add_unique(&mut annotations.keywords, text.to_string()),
+        Property::Person => add_unique(&mut annotations.people, text.to_string()),
+        Property::Other => {}
+    }
+}
+
+fn find(bytes: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    bytes[from..]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| from + position)
+}
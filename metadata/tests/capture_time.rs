@@ -0,0 +1,60 @@
+use database::CaptureTime;
+use exif::DateTime;
+use metadata::capture_time;
+
+const HOUR: i64 = 3600 * 1000;
+// 2024-06-01 12:00:00 UTC
+const NOON: i64 = 1_717_243_200_000;
+
+fn noon(offset: Option<i16>) -> DateTime {
+    let mut date_time = DateTime::from_ascii(b"2024:06:01 12:00:00").unwrap();
+    date_time.offset = offset;
+    date_time
+}
+
+#[test]
+fn recorded_offset_gives_the_utc_time() {
+    assert_eq!(
+        capture_time(Some(&noon(Some(120))), None),
+        Some(CaptureTime {
+            created_at: NOON - 2 * HOUR,
+            utc_offset: Some(120),
+        })
+    );
+}
+
+#[test]
+fn gps_time_gives_the_missing_offset() {
+    // The GPS fix is a few seconds off the camera clock
+    let gps_time = NOON + 5 * HOUR + 7_000;
+    assert_eq!(
+        capture_time(Some(&noon(None)), Some(gps_time)),
+        Some(CaptureTime {
+            created_at: NOON + 5 * HOUR,
+            utc_offset: Some(-300),
+        })
+    );
+}
+
+#[test]
+fn local_time_is_read_as_utc_without_an_offset() {
+    assert_eq!(
+        capture_time(Some(&noon(None)), Some(NOON + 30 * HOUR)),
+        Some(CaptureTime {
+            created_at: NOON,
+            utc_offset: None,
+        })
+    );
+}
+
+#[test]
+fn gps_time_is_the_fallback() {
+    assert_eq!(
+        capture_time(None, Some(NOON)),
+        Some(CaptureTime {
+            created_at: NOON,
+            utc_offset: None,
+        })
+    );
+    assert_eq!(capture_time(None, None), None);
+}
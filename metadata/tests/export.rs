@@ -0,0 +1,260 @@
+use exif::{experimental::Writer, Exif, Field, In, Reader, Tag, Value};
+use metadata::{export_media, find_xmp, parse_xmp, Annotations, ExportMetadata};
+use std::io::Cursor;
+
+const OLD_XMP: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmlns:xmpNote="http://ns.adobe.com/xmp/note/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmp:Rating="1" xmp:CreatorTool="darktable" xmpNote:HasExtendedXMP="0123456789ABCDEF0123456789ABCDEF"><dc:title><rdf:Alt><rdf:li xml:lang="x-default">IMG_0001</rdf:li></rdf:Alt></dc:title><dc:creator><rdf:Seq><rdf:li>Ana</rdf:li></rdf:Seq></dc:creator></rdf:Description></rdf:RDF></x:xmpmeta>"#;
+const IMAGE_DATA: &[u8] = b"\xff\xda\x00\x02compressed image\xff\xd9";
+
+// 2024-06-01 12:00:00.250 UTC, taken two hours east of it
+fn edited() -> ExportMetadata {
+    ExportMetadata {
+        created_at: 1_717_243_200_250,
+        utc_offset: Some(120),
+        latitude: Some(-33.5),
+        longitude: Some(151.25),
+        title: Some("Harbour <bridge>".to_string()),
+        description: Some("Opera house".to_string()),
+        rating: Some(5),
+        keywords: vec!["sydney".to_string()],
+    }
+}
+
+fn original_exif() -> Vec<u8> {
+    let fields = [
+        ascii(Tag::Make, "Canon"),
+        ascii(Tag::DateTimeOriginal, "2001:01:01 00:00:00"),
+    ];
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut block = Cursor::new(Vec::new());
+    writer.write(&mut block, false).unwrap();
+    block.into_inner()
+}
+
+fn ascii(tag: Tag, value: &str) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+    }
+}
+
+fn jpeg_segment(marker: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let data = parts.concat();
+    let mut segment = vec![0xff, marker];
+    segment.extend_from_slice(&((data.len() + 2) as u16).to_be_bytes());
+    segment.extend_from_slice(&data);
+    segment
+}
+
+fn iso_box(kind: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    let content = parts.concat();
+    let mut iso_box = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+    iso_box.extend_from_slice(kind);
+    iso_box.extend_from_slice(&content);
+    iso_box
+}
+
+fn infe(item_id: u16, item_type: &[u8], content_type: &[u8]) -> Vec<u8> {
+    let id = item_id.to_be_bytes();
+    iso_box(
+        b"infe",
+        &[b"\x02\0\0\0", &id, b"\0\0", item_type, b"\0", content_type],
+    )
+}
+
+// A HEIF file whose items are an image, then an Exif block and an XMP packet when asked for,
+// in that order in mdat. The Exif describes the image
+fn heif(with_exif: bool, with_xmp: bool) -> Vec<u8> {
+    let exif = [b"\0\0\0\x06Exif\0\0".as_slice(), &original_exif()].concat();
+    let mut items: Vec<(u16, Vec<u8>, &[u8])> = vec![(1, infe(1, b"hvc1", b""), b"image")];
+    if with_exif {
+        items.push((2, infe(2, b"Exif", b""), &exif));
+    }
+    if with_xmp {
+        items.push((
+            3,
+            infe(3, b"mime", b"application/rdf+xml\0"),
+            OLD_XMP.as_bytes(),
+        ));
+    }
+
+    let ftyp = iso_box(b"ftyp", &[b"heic\0\0\0\0mif1heic"]);
+    let pitm = iso_box(b"pitm", &[b"\0\0\0\0\0\x01"]);
+    let mut iinf_content = b"\0\0\0\0".to_vec();
+    iinf_content.extend_from_slice(&(items.len() as u16).to_be_bytes());
+    for (_, infe, _) in &items {
+        iinf_content.extend_from_slice(infe);
+    }
+    let iinf = iso_box(b"iinf", &[&iinf_content]);
+    let iref = if with_exif {
+        iso_box(
+            b"iref",
+            &[b"\0\0\0\0", &iso_box(b"cdsc", &[b"\0\x02\0\x01\0\x01"])],
+        )
+    } else {
+        Vec::new()
+    };
+    let iloc_size = 8 + 4 + 2 + 2 + items.len() * (2 + 2 + 2 + 8);
+    let meta_size = 8 + 4 + pitm.len() + iinf.len() + iref.len() + iloc_size;
+    let mut offset = ftyp.len() + meta_size + 8;
+    let mut iloc_content = b"\0\0\0\0\x44\x00".to_vec();
+    iloc_content.extend_from_slice(&(items.len() as u16).to_be_bytes());
+    for (item_id, _, data) in &items {
+        iloc_content.extend_from_slice(&item_id.to_be_bytes());
+        iloc_content.extend_from_slice(b"\0\0\0\x01");
+        iloc_content.extend_from_slice(&(offset as u32).to_be_bytes());
+        iloc_content.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len();
+    }
+    let iloc = iso_box(b"iloc", &[&iloc_content]);
+    let meta = iso_box(b"meta", &[b"\0\0\0\0", &pitm, &iinf, &iref, &iloc]);
+    assert_eq!(meta.len(), meta_size);
+    let data: Vec<&[u8]> = items.iter().map(|(_, _, data)| *data).collect();
+    [ftyp, meta, iso_box(b"mdat", &data)].concat()
+}
+
+// The data of the image item, found through the first entry of the item locations
+fn image_item(heif: &[u8]) -> &[u8] {
+    let iloc = heif.windows(4).position(|w| w == b"iloc").unwrap();
+    let extent = iloc + 4 + 4 + 2 + 2 + 2 + 2 + 2;
+    let read = |at: usize| u32::from_be_bytes(heif[at..at + 4].try_into().unwrap()) as usize;
+    let (offset, length) = (read(extent), read(extent + 4));
+    &heif[offset..offset + length]
+}
+
+fn read_exif(bytes: &[u8]) -> Exif {
+    Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .unwrap()
+}
+
+fn text(exif: &Exif, tag: Tag) -> String {
+    let field = exif.get_field(tag, In::PRIMARY).unwrap();
+    match field.value {
+        Value::Ascii(ref values) => String::from_utf8(values[0].clone()).unwrap(),
+        _ => panic!("{tag} is not text"),
+    }
+}
+
+fn assert_exported(bytes: &[u8]) {
+    let exif = read_exif(bytes);
+    assert_eq!(text(&exif, Tag::Make), "Canon");
+    assert_exported_values(bytes);
+}
+
+fn assert_exported_values(bytes: &[u8]) {
+    let exif = read_exif(bytes);
+    assert_eq!(text(&exif, Tag::DateTimeOriginal), "2024:06:01 14:00:00");
+    assert_eq!(text(&exif, Tag::SubSecTimeOriginal), "250");
+    assert_eq!(text(&exif, Tag::OffsetTimeOriginal), "+02:00");
+    assert_eq!(text(&exif, Tag::GPSLatitudeRef), "S");
+    assert_eq!(text(&exif, Tag::GPSLongitudeRef), "E");
+    assert_eq!(text(&exif, Tag::ImageDescription), "Opera house");
+
+    let xmp = find_xmp(bytes).unwrap();
+    assert_eq!(
+        parse_xmp(xmp).unwrap(),
+        Annotations {
+            rating: Some(5),
+            title: Some("Harbour <bridge>".to_string()),
+            description: Some("Opera house".to_string()),
+            keywords: vec!["sydney".to_string()],
+            people: Vec::new(),
+        }
+    );
+    assert!(xmp.contains(r#"exif:DateTimeOriginal="2024-06-01T14:00:00.250+02:00""#));
+    assert!(xmp.contains(r#"exif:GPSLatitude="33,30.000000S""#));
+}
+
+// The properties of the original XMP that aren't exported are still there, the others are gone
+fn assert_original_xmp_kept(bytes: &[u8]) {
+    let xmp = find_xmp(bytes).unwrap();
+    assert!(xmp.contains(r#"xmp:CreatorTool="darktable""#));
+    assert!(xmp.contains(r#"xmpNote:HasExtendedXMP="0123456789ABCDEF0123456789ABCDEF""#));
+    assert!(xmp.contains("<rdf:li>Ana</rdf:li>"));
+    assert!(!xmp.contains(r#"xmp:Rating="1""#));
+    assert!(!xmp.contains("IMG_0001"));
+}
+
+#[test]
+fn jpeg_metadata_is_replaced() {
+    let app0 = jpeg_segment(0xe0, &[b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"]);
+    let extended_xmp = jpeg_segment(
+        0xe1,
+        &[
+            b"http://ns.adobe.com/xmp/extension/\0",
+            b"0123456789ABCDEF0123456789ABCDEF",
+            b"\0\0\0\x10\0\0\0\0rest of the xmp",
+        ],
+    );
+    let original = [
+        b"\xff\xd8".to_vec(),
+        app0.clone(),
+        jpeg_segment(0xe1, &[b"Exif\0\0", &original_exif()]),
+        jpeg_segment(
+            0xe1,
+            &[b"http://ns.adobe.com/xap/1.0/\0", OLD_XMP.as_bytes()],
+        ),
+        extended_xmp.clone(),
+        jpeg_segment(0xdb, &[b"quantization tables"]),
+        IMAGE_DATA.to_vec(),
+    ]
+    .concat();
+
+    let exported = export_media(&original, &edited()).unwrap();
+    assert_exported(&exported);
+    assert_original_xmp_kept(&exported);
+    assert!(exported
+        .windows(extended_xmp.len())
+        .any(|w| w == extended_xmp.as_slice()));
+    assert!(exported[2..].starts_with(&app0));
+    assert!(exported.ends_with(IMAGE_DATA));
+    assert_eq!(
+        exported
+            .windows(OLD_XMP.len())
+            .filter(|w| *w == OLD_XMP.as_bytes())
+            .count(),
+        0
+    );
+    // The stored original is not touched
+    assert_eq!(
+        text(&read_exif(&original), Tag::DateTimeOriginal),
+        "2001:01:01 00:00:00"
+    );
+}
+
+#[test]
+fn heif_items_are_moved_to_the_new_metadata() {
+    let original = heif(true, true);
+    let exported = export_media(&original, &edited()).unwrap();
+    assert_exported(&exported);
+    assert_original_xmp_kept(&exported);
+    // The image item and everything before the appended metadata stay where they were
+    let image_at = original.windows(5).position(|w| w == b"image").unwrap();
+    assert_eq!(&exported[image_at..image_at + 5], b"image");
+    assert_eq!(image_item(&exported), b"image");
+}
+
+#[test]
+fn missing_heif_items_are_added() {
+    let exported = export_media(&heif(true, false), &edited()).unwrap();
+    assert_exported(&exported);
+    assert_eq!(image_item(&exported), b"image");
+
+    let exported = export_media(&heif(false, false), &edited()).unwrap();
+    assert_exported_values(&exported);
+    assert_eq!(image_item(&exported), b"image");
+    // Exporting again finds the added items
+    let exported = export_media(&exported, &edited()).unwrap();
+    assert_exported_values(&exported);
+    assert_eq!(image_item(&exported), b"image");
+}
+
+#[test]
+fn other_formats_are_returned_as_they_are() {
+    let png = b"\x89PNG\r\n\x1a\nrest of the file";
+    assert_eq!(export_media(png, &edited()).unwrap(), png);
+}
@@ -0,0 +1,60 @@
+use metadata::{parse_iptc, Annotations};
+
+fn dataset(number: u8, value: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x1c, 2, number];
+    bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(value);
+    bytes
+}
+
+fn resource(id: u16, data: &[u8]) -> Vec<u8> {
+    let mut bytes = b"8BIM".to_vec();
+    bytes.extend_from_slice(&id.to_be_bytes());
+    // Empty name, padded to an even size
+    bytes.extend_from_slice(&[0, 0]);
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        bytes.push(0);
+    }
+    bytes
+}
+
+fn jpeg(resources: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = b"\xff\xd8\xff\xedxxPhotoshop 3.0\0".to_vec();
+    for resource in resources {
+        bytes.extend_from_slice(resource);
+    }
+    bytes.extend_from_slice(b"\xff\xd9");
+    bytes
+}
+
+#[test]
+fn title_caption_and_keywords_are_read() {
+    let iim = [
+        dataset(5, b"Harbour"),
+        dataset(25, b"boats"),
+        dataset(25, b"sea"),
+        // Latin-1 caption
+        dataset(120, b"Fishermen in Porto, S\xe3o Jo\xe3o"),
+    ]
+    .concat();
+    // Another resource comes first and has an odd size
+    let file = jpeg(&[resource(0x0425, b"abc"), resource(0x0404, &iim)]);
+
+    assert_eq!(
+        parse_iptc(&file),
+        Some(Annotations {
+            title: Some("Harbour".to_string()),
+            description: Some("Fishermen in Porto, São João".to_string()),
+            keywords: vec!["boats".to_string(), "sea".to_string()],
+            ..Default::default()
+        })
+    );
+}
+
+#[test]
+fn files_without_iptc_have_none() {
+    assert_eq!(parse_iptc(b"\xff\xd8\xff\xd9"), None);
+    assert_eq!(parse_iptc(&jpeg(&[resource(0x0425, b"abcd")])), None);
+}
@@ -0,0 +1,115 @@
+use metadata::{find_xmp, parse_xmp, Annotations};
+
+const LIGHTROOM: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:mwg-rs="http://www.metadataworkinggroup.com/schemas/regions/"
+    xmlns:stArea="http://ns.adobe.com/xmp/sType/Area#"
+    xmp:Rating="4">
+   <dc:title>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">Sunset &amp; friends</rdf:li>
+     <rdf:li xml:lang="pt">Pôr do sol</rdf:li>
+    </rdf:Alt>
+   </dc:title>
+   <dc:description>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">At the beach</rdf:li>
+    </rdf:Alt>
+   </dc:description>
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>beach</rdf:li>
+     <rdf:li>sunset</rdf:li>
+     <rdf:li>beach</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+   <mwg-rs:Regions rdf:parseType="Resource">
+    <mwg-rs:RegionList>
+     <rdf:Bag>
+      <rdf:li>
+       <rdf:Description mwg-rs:Name="Alice" mwg-rs:Type="Face">
+        <mwg-rs:Area stArea:x="0.5" stArea:y="0.5" stArea:w="0.1" stArea:h="0.1"/>
+       </rdf:Description>
+      </rdf:li>
+      <rdf:li>
+       <rdf:Description mwg-rs:Type="Face">
+        <mwg-rs:Name>Bob</mwg-rs:Name>
+       </rdf:Description>
+      </rdf:li>
+     </rdf:Bag>
+    </mwg-rs:RegionList>
+   </mwg-rs:Regions>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+#[test]
+fn lightroom_packets_are_read() {
+    assert_eq!(
+        parse_xmp(LIGHTROOM).unwrap(),
+        Annotations {
+            rating: Some(4),
+            title: Some("Sunset & friends".to_string()),
+            description: Some("At the beach".to_string()),
+            keywords: vec!["beach".to_string(), "sunset".to_string()],
+            people: vec!["Alice".to_string(), "Bob".to_string()],
+        }
+    );
+}
+
+#[test]
+fn prefixes_are_resolved_by_namespace() {
+    let xml = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description xmlns:a="http://ns.adobe.com/xap/1.0/" xmlns:other="urn:other"
+    a:Rating="-1" other:Rating="5">
+   <other:subject><rdf:Bag><rdf:li>ignored</rdf:li></rdf:Bag></other:subject>
+   <Iptc4xmpExt:PersonInImage xmlns:Iptc4xmpExt="http://iptc.org/std/Iptc4xmpExt/2008-02-29/">
+    <rdf:Bag><rdf:li>Carol</rdf:li></rdf:Bag>
+   </Iptc4xmpExt:PersonInImage>
+  </rdf:Description>
+</rdf:RDF>"#;
+    assert_eq!(
+        parse_xmp(xml).unwrap(),
+        Annotations {
+            rating: Some(-1),
+            people: vec!["Carol".to_string()],
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn packets_are_found_inside_files() {
+    let mut jpeg = b"\xff\xd8\xff\xe1\x00\x10http://ns.adobe.com/xap/1.0/\0".to_vec();
+    jpeg.extend_from_slice(LIGHTROOM.as_bytes());
+    jpeg.extend_from_slice(b"\xff\xd9");
+    assert_eq!(find_xmp(&jpeg), Some(LIGHTROOM));
+    assert_eq!(find_xmp(b"\xff\xd8\xff\xd9"), None);
+}
+
+#[test]
+fn newer_annotations_win() {
+    let embedded = Annotations {
+        rating: Some(2),
+        title: Some("Embedded".to_string()),
+        keywords: vec!["beach".to_string()],
+        ..Default::default()
+    };
+    let sidecar = Annotations {
+        rating: Some(5),
+        keywords: vec!["sunset".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        embedded.merge(sidecar),
+        Annotations {
+            rating: Some(5),
+            title: Some("Embedded".to_string()),
+            keywords: vec!["sunset".to_string()],
+            ..Default::default()
+        }
+    );
+}
@@ -0,0 +1,83 @@
+use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+use log::warn;
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageDecoder, ImageReader, RgbImage};
+use s3::Bucket;
+
+// FIX: change this to the http crate
+const CONTENT_TYPE_HEADER: &str = "content-type";
+const IOS_MEDIA_TYPES: [&str; 2] = ["image/heif", "image/heic"];
+
+// Downloads an original and decodes it, upright, along with the content type it was stored with
+pub async fn load_image(bucket: &Bucket, key: &str) -> Result<(DynamicImage, String), String> {
+    let (bytes, content_type) = fetch_image(bucket, key).await?;
+    let image = decode(&bytes, &content_type)?;
+    Ok((image, content_type))
+}
+
+// Downloads an original, returning its bytes and the content type it was stored with
+pub async fn fetch_image(bucket: &Bucket, key: &str) -> Result<(Vec<u8>, String), String> {
+    let source_image_response = bucket
+        .get_object(key)
+        .await
+        .map_err(|err| format!("Get object failed: {err}"))?;
+
+    let content_type = match source_image_response.headers().get(CONTENT_TYPE_HEADER) {
+        Some(ct) => ct.clone(),
+        None => {
+            warn!("No content type provided in {key} object.");
+            String::new()
+        }
+    };
+    Ok((source_image_response.to_vec(), content_type))
+}
+
+// Decodes downloaded bytes upright. CPU bound, callers on the runtime should spawn it blocking
+pub fn decode(bytes: &[u8], content_type: &str) -> Result<DynamicImage, String> {
+    // FIX: create and add the other ios types
+    if IOS_MEDIA_TYPES.contains(&content_type) {
+        decode_heif(bytes)
+    } else {
+        decode_image(bytes)
+    }
+}
+
+fn decode_heif(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let lib_heif = LibHeif::new();
+    let heif_context = HeifContext::read_from_bytes(bytes)
+        .map_err(|err| format!("Error reading heif image content: {err}"))?;
+    let handle = heif_context
+        .primary_image_handle()
+        .map_err(|err| format!("Error getting heif primary handle: {err}"))?;
+    let decoded_image = lib_heif
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(|err| format!("Couldn't decode heif image: {err}"))?;
+
+    let width = decoded_image.width();
+    let height = decoded_image.height();
+    let pixels = decoded_image
+        .planes()
+        .interleaved
+        .ok_or("Couldn't get pixels from decoded image.")?;
+    let img_buffer = RgbImage::from_raw(width, height, pixels.data.to_vec())
+        .ok_or("Couldn't create image buffer from decoded image.")?;
+
+    Ok(DynamicImage::ImageRgb8(img_buffer))
+}
+
+fn decode_image(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let source_reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|err| format!("Couldn't convert image: {err}"))?;
+    let mut decoder = source_reader
+        .into_decoder()
+        .map_err(|err| format!("Could not decode image: {err}"))?;
+    let orientation = decoder
+        .orientation()
+        .map_err(|err| format!("Could not get image orientation: {err}"))?;
+    let mut dynamic_image = DynamicImage::from_decoder(decoder)
+        .map_err(|err| format!("Couldn't convert image: {err}"))?;
+    dynamic_image.apply_orientation(orientation);
+    Ok(dynamic_image)
+}
@@ -0,0 +1,183 @@
+use log::warn;
+use std::io::Cursor;
+
+use database::Repository;
+use image::{imageops::FilterType::Triangle, DynamicImage, GenericImageView};
+use s3::Bucket;
+
+use crate::decode::{decode, fetch_image};
+
+const FACE_CROP_PREFIX: &str = "face/";
+const FACE_CROP_SIZE: u32 = 256;
+// Share of the box added around it on every side, so the crop shows the whole head
+const FACE_CROP_PADDING: f32 = 0.25;
+
+// Crops every detection of the media that has no crop yet, returning how many were made.
+// A detection whose box or original can't be cropped is counted as a failed attempt, so the
+// catch-up gives up on it instead of retrying it forever
+pub async fn crop_faces(
+    media_id: &str,
+    bucket: &Bucket,
+    db: &dyn Repository,
+) -> Result<usize, String> {
+    let detections = db
+        .get_missing_face_crops(media_id.to_string())
+        .await
+        .map_err(|err| format!("Couldn't get the faces of {media_id}: {err}"))?;
+    if detections.is_empty() {
+        return Ok(0);
+    }
+
+    let media_face_ids: Vec<i32> = detections.iter().map(|(id, _)| *id).collect();
+    let (bytes, content_type) = match fetch_image(bucket, media_id).await {
+        Ok(fetched) => fetched,
+        Err(err) => {
+            add_failures(db, media_face_ids).await;
+            return Err(err);
+        }
+    };
+    // Decoding and encoding are CPU bound, keep them off the runtime threads
+    let crops = match tokio::task::spawn_blocking(move || {
+        decode(&bytes, &content_type).map(|image| encode_crops(&image, detections))
+    })
+    .await
+    {
+        Ok(Ok(crops)) => crops,
+        Ok(Err(err)) => {
+            add_failures(db, media_face_ids).await;
+            return Err(err);
+        }
+        Err(err) => return Err(format!("Face crop task failed: {err}")),
+    };
+
+    let mut cropped = 0;
+    for (media_face_id, crop) in crops {
+        let crop_bytes = match crop {
+            Ok(crop_bytes) => crop_bytes,
+            Err(err) => {
+                warn!("{err}");
+                add_failures(db, vec![media_face_id]).await;
+                continue;
+            }
+        };
+
+        let crop_id = format!("{FACE_CROP_PREFIX}{media_face_id}");
+        let response = bucket
+            .put_object_with_content_type(&crop_id, &crop_bytes, "image/jpeg")
+            .await
+            .map_err(|err| format!("Put face crop object failed with: {err}"))?;
+        if response.status_code() != 200 {
+            return Err(format!(
+                "Put face crop object failed with status code: {}",
+                response.status_code()
+            ));
+        }
+
+        db.set_face_crop(media_face_id, crop_id)
+            .await
+            .map_err(|err| format!("Couldn't save the crop of face {media_face_id}: {err}"))?;
+        cropped += 1;
+    }
+    Ok(cropped)
+}
+
+// The JPEG crop of every detection, or why it couldn't be made
+fn encode_crops(
+    image: &DynamicImage,
+    detections: Vec<(i32, Vec<i32>)>,
+) -> Vec<(i32, Result<Vec<u8>, String>)> {
+    let (width, height) = image.dimensions();
+    detections
+        .into_iter()
+        .map(|(media_face_id, bbox)| {
+            let Some((x, y, side)) = crop_square(&bbox, width, height) else {
+                return (
+                    media_face_id,
+                    Err(format!(
+                        "Face {media_face_id} has an invalid bounding box {bbox:?}"
+                    )),
+                );
+            };
+            let crop = image
+                .crop_imm(x, y, side, side)
+                .resize_exact(FACE_CROP_SIZE, FACE_CROP_SIZE, Triangle)
+                .to_rgb8();
+
+            let mut crop_bytes: Vec<u8> = Vec::new();
+            let encoded = crop
+                .write_to(&mut Cursor::new(&mut crop_bytes), image::ImageFormat::Jpeg)
+                .map(|_| crop_bytes)
+                .map_err(|err| format!("Couldn't encode the crop of face {media_face_id}: {err}"));
+            (media_face_id, encoded)
+        })
+        .collect()
+}
+
+async fn add_failures(db: &dyn Repository, media_face_ids: Vec<i32>) {
+    if let Err(err) = db.add_face_crop_failures(media_face_ids.clone()).await {
+        warn!("Couldn't count the failed crops of faces {media_face_ids:?}: {err}");
+    }
+}
+
+// The padded square around a [left, top, right, bottom] box, moved or shrunk to stay inside the image.
+// Returns its top left corner and side
+pub fn crop_square(bbox: &[i32], width: u32, height: u32) -> Option<(u32, u32, u32)> {
+    let &[left, top, right, bottom] = bbox else {
+        return None;
+    };
+    if right <= left || bottom <= top || width == 0 || height == 0 {
+        return None;
+    }
+    let (left, top, right, bottom) = (left as f32, top as f32, right as f32, bottom as f32);
+
+    let side = ((right - left).max(bottom - top) * (1.0 + 2.0 * FACE_CROP_PADDING))
+        .min(width.min(height) as f32)
+        .floor()
+        .max(1.0);
+    let x = ((left + right - side) / 2.0).clamp(0.0, width as f32 - side);
+    let y = ((top + bottom - side) / 2.0).clamp(0.0, height as f32 - side);
+    Some((x as u32, y as u32, side as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_padded_square_is_centred_on_the_box() {
+        assert_eq!(crop_square(&[40, 40, 60, 60], 100, 100), Some((35, 35, 30)));
+        // The longer side of the box sets the side of the square
+        assert_eq!(crop_square(&[40, 30, 60, 70], 100, 100), Some((20, 20, 60)));
+    }
+
+    #[test]
+    fn squares_at_the_edges_are_moved_inside_the_image() {
+        assert_eq!(crop_square(&[0, 0, 20, 20], 100, 100), Some((0, 0, 30)));
+        assert_eq!(
+            crop_square(&[80, 80, 100, 100], 100, 100),
+            Some((70, 70, 30))
+        );
+        assert_eq!(crop_square(&[0, 80, 20, 100], 100, 100), Some((0, 70, 30)));
+    }
+
+    #[test]
+    fn boxes_larger_than_the_image_are_shrunk_to_its_short_side() {
+        assert_eq!(
+            crop_square(&[-50, -10, 250, 90], 200, 100),
+            Some((50, 0, 100))
+        );
+        assert_eq!(crop_square(&[0, 0, 100, 100], 100, 100), Some((0, 0, 100)));
+    }
+
+    #[test]
+    fn degenerate_boxes_are_rejected() {
+        assert_eq!(crop_square(&[10, 10, 10, 20], 100, 100), None);
+        assert_eq!(crop_square(&[20, 10, 10, 20], 100, 100), None);
+        assert_eq!(crop_square(&[10, 20, 20, 10], 100, 100), None);
+        assert_eq!(crop_square(&[10, 10, 20], 100, 100), None);
+        assert_eq!(crop_square(&[], 100, 100), None);
+        assert_eq!(crop_square(&[10, 10, 20, 20], 0, 100), None);
+        // A box of one pixel still gives a square of one
+        assert_eq!(crop_square(&[10, 10, 11, 11], 100, 100), Some((10, 10, 1)));
+    }
+}
@@ -0,0 +1,102 @@
+mod decode;
+mod face_crops;
+mod handler;
+mod placeholder;
+mod renditions;
+use database::Repository;
+use face_crops::crop_faces;
+use futures_util::StreamExt;
+use handler::handle_request;
+use log::{error, info};
+use messaging::{BusError, MessageBus};
+use s3::Bucket;
+use std::{sync::Arc, time::Duration};
+
+pub use renditions::PreviewConfig;
+
+// How many media the face crop worker catches up on at every interval
+const FACE_CROP_BATCH: u64 = 100;
+
+// Consumes the previews subject until the bus closes the subscription
+pub async fn run(
+    bus: Arc<dyn MessageBus>,
+    bucket: Box<Bucket>,
+    db: Arc<dyn Repository>,
+    concurrency: usize,
+    config: PreviewConfig,
+) -> Result<(), BusError> {
+    let config = Arc::new(config);
+    // FIX: crate a const or a env var for the preview consumer
+    let messages = bus.subscribe("previews", "preview_consumer").await?;
+    messages
+        .for_each_concurrent(concurrency, |msg| {
+            let thread_bucket = bucket.clone();
+            let thread_db = db.clone();
+            let thread_config = config.clone();
+            async move {
+                match msg {
+                    Ok(msg) => {
+                        info!(
+                            "Message received: {:?}",
+                            String::from_utf8(msg.payload.to_vec())
+                        );
+                        handle_request(msg, thread_bucket, thread_db, thread_config).await
+                    }
+                    Err(err) => {
+                        error!("Error receiving message: {err}");
+                    }
+                }
+            }
+        })
+        .await;
+    Ok(())
+}
+
+// Crops the faces of the media whose id is published on the face-crops subject, e.g. by the face
+// detection once it stored its detections. Faces that are still missing a crop are caught up on an interval,
+// until they failed FACE_CROP_MAX_ATTEMPTS times
+pub async fn run_face_crops(
+    bus: Arc<dyn MessageBus>,
+    bucket: Box<Bucket>,
+    db: Arc<dyn Repository>,
+    interval: Duration,
+) -> Result<(), BusError> {
+    let mut requests = bus.subscribe("face-crops", "face_crop_consumer").await?;
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let media_ids = match db.get_media_with_missing_face_crops(FACE_CROP_BATCH).await {
+                    Ok(media_ids) => media_ids,
+                    Err(err) => {
+                        error!("Error looking for missing face crops: {err}");
+                        continue;
+                    }
+                };
+                for media_id in media_ids {
+                    if let Err(err) = crop_faces(&media_id, &bucket, &*db).await {
+                        error!("Error cropping the faces of {media_id}: {err}");
+                    }
+                }
+            }
+            request = requests.next() => match request {
+                Some(Ok(msg)) => {
+                    let media_id = String::from_utf8_lossy(&msg.payload).to_string();
+                    match crop_faces(&media_id, &bucket, &*db).await {
+                        Ok(cropped) => {
+                            info!("Cropped {cropped} faces of {media_id}");
+                            let _ = msg.ack().await;
+                        }
+                        Err(err) => {
+                            error!("Error cropping the faces of {media_id}: {err}");
+                            let _ = msg.term().await;
+                        }
+                    }
+                }
+                Some(Err(err)) => error!("Error receiving message: {err}"),
+                None => return Ok(()),
+            }
+        }
+    }
+}
@@ -1,11 +1,11 @@
-mod handler;
 use database::DbManager;
-use futures_util::StreamExt;
-use handler::handle_request;
-use log::{error, info};
+use messaging::NatsBus;
+use preview::{run, run_face_crops, PreviewConfig};
 use s3::{creds::Credentials, error::S3Error, Bucket, BucketConfiguration, Region};
 use serde::Deserialize;
-use std::error::Error;
+use std::{error::Error, sync::Arc, time::Duration};
+
+const WORKER_CONCURRENCY: usize = 5;
 
 #[derive(Deserialize, Debug)]
 pub struct EnvVars {
@@ -23,6 +23,15 @@ pub struct EnvVars {
     pub object_storage_access_key: String,
     #[serde(alias = "OBJECT_STORAGE_SECRET_KEY")]
     pub object_storage_secret_key: String,
+    #[serde(alias = "FACE_CROP_INTERVAL_SECONDS")]
+    #[serde(default = "face_crop_interval_default")]
+    pub face_crop_interval_seconds: u64,
+    #[serde(alias = "PREVIEW_SIZES")]
+    #[serde(default = "preview_sizes_default")]
+    pub preview_sizes: String,
+    #[serde(alias = "PREVIEW_FORMATS")]
+    #[serde(default = "preview_formats_default")]
+    pub preview_formats: String,
 }
 
 fn nats_endpoint_default() -> String {
@@ -31,6 +40,15 @@ fn nats_endpoint_default() -> String {
 fn object_storage_endpoint_default() -> String {
     "http://localhost".to_string()
 }
+fn face_crop_interval_default() -> u64 {
+    300
+}
+fn preview_sizes_default() -> String {
+    "grid:256,display:1440".to_string()
+}
+fn preview_formats_default() -> String {
+    "avif".to_string()
+}
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     dotenvy::dotenv().ok();
@@ -39,6 +57,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Err(err) => panic!("{}", err),
     };
 
+    let preview_config = match PreviewConfig::parse(&envs.preview_sizes, &envs.preview_formats) {
+        Ok(config) => config,
+        Err(err) => panic!("{}", err),
+    };
+
     let db = match DbManager::new().await {
         Ok(database) => database,
         Err(err) => panic!("{}", err),
@@ -46,57 +69,30 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let bucket = setup_bucket(&envs).await?;
 
-    let client = match async_nats::connect(envs.nats_endpoint).await {
-        Ok(c) => c,
+    let bus = match NatsBus::connect(envs.nats_endpoint).await {
+        Ok(bus) => bus,
         Err(err) => {
             panic!("Couldn't connect nats client: {err}");
         }
     };
 
-    let jetstream = async_nats::jetstream::new(client);
-    let stream_name = String::from("previews");
-    let stream = jetstream
-        .get_or_create_stream(async_nats::jetstream::stream::Config {
-            name: stream_name.clone(),
-            max_messages: 10000,
-            ..Default::default()
-        })
-        .await?;
-
-    // FIX: crate a const or a env var for the preview consumer
-    let consumer = stream
-        .get_or_create_consumer(
-            "preview_consumer",
-            async_nats::jetstream::consumer::pull::Config {
-                durable_name: Some("preview_consumer".to_string()),
-                filter_subject: "previews".to_string(),
-                ..Default::default()
-            },
-        )
-        .await?;
-
-    let messages = consumer.messages().await?;
-    let thread_limit = 5;
-    let _ = messages
-        .for_each_concurrent(thread_limit, |msg| {
-            let thread_bucket = bucket.clone();
-            let thread_db = db.clone();
-            async move {
-                match msg {
-                    Ok(msg) => {
-                        info!(
-                            "Message received: {:?}",
-                            String::from_utf8(msg.payload.to_vec())
-                        );
-                        handle_request(msg, thread_bucket, thread_db).await
-                    }
-                    Err(err) => {
-                        error!("Error receiving message: {err}");
-                    }
-                }
-            }
-        })
-        .await;
+    let bus: Arc<NatsBus> = Arc::new(bus);
+    let db = Arc::new(db);
+    tokio::try_join!(
+        run(
+            bus.clone(),
+            bucket.clone(),
+            db.clone(),
+            WORKER_CONCURRENCY,
+            preview_config
+        ),
+        run_face_crops(
+            bus,
+            bucket,
+            db,
+            Duration::from_secs(envs.face_crop_interval_seconds)
+        ),
+    )?;
     Ok(())
 }
 
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, GenericImageView};
+
+// Blurhash only needs the overall shapes, encoding a large image is slow for nothing
+const BLURHASH_SIZE: u32 = 64;
+const MAX_COMPONENTS: u32 = 4;
+const DOMINANT_COLORS: usize = 5;
+// Colors are counted in buckets of 4 bits per channel, so close shades add up
+const COLOR_BITS: u8 = 4;
+// Buckets holding less of the image than this are not dominant
+const MIN_COLOR_SHARE: f32 = 0.02;
+
+pub struct Placeholder {
+    pub blurhash: Option<String>,
+    pub dominant_colors: Vec<String>,
+}
+
+// What clients paint while the preview of the image loads
+pub fn placeholder(image: &DynamicImage) -> Placeholder {
+    let small = image.thumbnail(BLURHASH_SIZE, BLURHASH_SIZE);
+    Placeholder {
+        blurhash: blurhash(&small),
+        dominant_colors: dominant_colors(&small),
+    }
+}
+
+// More components along the longer side, so the hash follows the aspect ratio
+fn blurhash(image: &DynamicImage) -> Option<String> {
+    let (width, height) = image.dimensions();
+    let (components_x, components_y) = if width >= height {
+        (MAX_COMPONENTS, (MAX_COMPONENTS * height / width).max(1))
+    } else {
+        ((MAX_COMPONENTS * width / height).max(1), MAX_COMPONENTS)
+    };
+    let rgba = image.to_rgba8();
+    blurhash::encode(components_x, components_y, width, height, rgba.as_raw()).ok()
+}
+
+// The average color of the most common buckets, most common first, as #rrggbb
+fn dominant_colors(image: &DynamicImage) -> Vec<String> {
+    let rgba = image.to_rgba8();
+    let mut buckets: HashMap<[u8; 3], (u32, [u32; 3])> = HashMap::new();
+    let mut total = 0;
+    // Transparent pixels are the background, not a color of the image
+    for pixel in rgba.pixels().filter(|pixel| pixel[3] >= 128) {
+        let key = [0, 1, 2].map(|channel| pixel[channel] >> (8 - COLOR_BITS));
+        let (count, sums) = buckets.entry(key).or_default();
+        *count += 1;
+        for channel in 0..3 {
+            sums[channel] += pixel[channel] as u32;
+        }
+        total += 1;
+    }
+
+    let mut buckets: Vec<(u32, [u32; 3])> = buckets.into_values().collect();
+    buckets.sort_by(|(a, a_sums), (b, b_sums)| b.cmp(a).then(a_sums.cmp(b_sums)));
+    buckets
+        .into_iter()
+        .filter(|(count, _)| *count as f32 >= total as f32 * MIN_COLOR_SHARE)
+        .take(DOMINANT_COLORS)
+        .map(|(count, sums)| {
+            let [red, green, blue] = sums.map(|sum| sum / count);
+            format!("#{red:02x}{green:02x}{blue:02x}")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    #[test]
+    fn a_plain_image_has_one_dominant_color() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(200, 100, Rgb([255, 0, 0])));
+        let placeholder = placeholder(&image);
+        assert_eq!(placeholder.dominant_colors, vec!["#ff0000".to_string()]);
+
+        // A wide image gets 4x2 components, which the first character encodes as 3 + 1 * 9.
+        // The average color and the largest component take 6 characters, every other one 2
+        let blurhash = placeholder.blurhash.unwrap();
+        assert!(blurhash.starts_with('C'), "{blurhash}");
+        assert_eq!(blurhash.len(), 6 + 2 * (4 * 2 - 1));
+    }
+
+    #[test]
+    fn dominant_colors_are_sorted_by_share() {
+        let image = RgbImage::from_fn(256, 128, |x, _| {
+            if x < 192 {
+                Rgb([0, 0, 255])
+            } else {
+                Rgb([0, 255, 0])
+            }
+        });
+        let placeholder = placeholder(&DynamicImage::ImageRgb8(image));
+        assert_eq!(
+            placeholder.dominant_colors,
+            vec!["#0000ff".to_string(), "#00ff00".to_string()]
+        );
+    }
+
+    #[test]
+    fn transparent_pixels_are_not_dominant() {
+        let image = RgbaImage::from_fn(128, 256, |_, y| {
+            if y < 128 {
+                Rgba([255, 0, 0, 0])
+            } else {
+                Rgba([0, 0, 255, 255])
+            }
+        });
+        let placeholder = placeholder(&DynamicImage::ImageRgba8(image));
+        assert_eq!(placeholder.dominant_colors, vec!["#0000ff".to_string()]);
+        // A tall image gets 2x4 components, encoded as 1 + 3 * 9
+        assert!(placeholder.blurhash.unwrap().starts_with('S'));
+    }
+}
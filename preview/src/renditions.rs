@@ -0,0 +1,356 @@
+use database::FULL_RENDITION;
+use image::{
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder, png::PngEncoder},
+    imageops::FilterType::Triangle,
+    DynamicImage, GenericImageView, ImageEncoder,
+};
+
+const RENDITION_ID_PREFIX: &str = "rend/";
+const JPEG_QUALITY: u8 = 85;
+// rav1e speed from 1 to 10, anything slower takes seconds for a display sized image
+const AVIF_SPEED: u8 = 8;
+const AVIF_QUALITY: u8 = 70;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenditionFormat {
+    Avif,
+    Jpeg,
+    Png,
+}
+
+impl RenditionFormat {
+    pub fn parse(format: &str) -> Option<RenditionFormat> {
+        match format.trim().to_lowercase().as_str() {
+            "avif" => Some(RenditionFormat::Avif),
+            "jpeg" | "jpg" => Some(RenditionFormat::Jpeg),
+            "png" => Some(RenditionFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            RenditionFormat::Avif => "image/avif",
+            RenditionFormat::Jpeg => "image/jpeg",
+            RenditionFormat::Png => "image/png",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RenditionFormat::Avif => "avif",
+            RenditionFormat::Jpeg => "jpg",
+            RenditionFormat::Png => "png",
+        }
+    }
+
+    // The format every client can show, PNG when the image has transparency to keep
+    pub fn fallback(image: &DynamicImage) -> RenditionFormat {
+        if image.color().has_alpha() {
+            RenditionFormat::Png
+        } else {
+            RenditionFormat::Jpeg
+        }
+    }
+
+    pub fn encode(&self, image: &DynamicImage) -> Result<Vec<u8>, String> {
+        let mut bytes: Vec<u8> = Vec::new();
+        let image = if image.color().has_alpha() && *self != RenditionFormat::Jpeg {
+            DynamicImage::ImageRgba8(image.to_rgba8())
+        } else {
+            DynamicImage::ImageRgb8(image.to_rgb8())
+        };
+        let (width, height) = image.dimensions();
+        let color = image.color().into();
+        let result = match self {
+            RenditionFormat::Avif => {
+                AvifEncoder::new_with_speed_quality(&mut bytes, AVIF_SPEED, AVIF_QUALITY)
+                    .write_image(image.as_bytes(), width, height, color)
+            }
+            RenditionFormat::Jpeg => JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY)
+                .write_image(image.as_bytes(), width, height, color),
+            RenditionFormat::Png => {
+                PngEncoder::new(&mut bytes).write_image(image.as_bytes(), width, height, color)
+            }
+        };
+        result.map_err(|err| format!("Couldn't encode {} rendition: {err}", self.extension()))?;
+        Ok(bytes)
+    }
+}
+
+// A size renditions are made in, scaled to `height` keeping the aspect ratio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenditionSize {
+    pub name: String,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PreviewConfig {
+    // Smallest first, the fallback of the smallest one is also the preview of the media
+    pub sizes: Vec<RenditionSize>,
+    // Made for every size next to the fallback
+    pub formats: Vec<RenditionFormat>,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        PreviewConfig {
+            sizes: vec![
+                RenditionSize {
+                    name: "grid".to_string(),
+                    height: 256,
+                },
+                RenditionSize {
+                    name: "display".to_string(),
+                    height: 1440,
+                },
+            ],
+            formats: vec![RenditionFormat::Avif],
+        }
+    }
+}
+
+impl PreviewConfig {
+    // Reads sizes as `name:height` pairs and formats as names, both comma separated,
+    // e.g. "grid:256,display:1440" and "avif"
+    pub fn parse(sizes: &str, formats: &str) -> Result<PreviewConfig, String> {
+        let mut parsed_sizes = sizes
+            .split(',')
+            .filter(|size| !size.trim().is_empty())
+            .map(|size| {
+                let (name, height) = size
+                    .split_once(':')
+                    .ok_or_else(|| format!("Preview size {size} is not name:height"))?;
+                let height = height
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|height| *height > 0)
+                    .ok_or_else(|| format!("Preview size {size} has an invalid height"))?;
+                Ok(RenditionSize {
+                    name: name.trim().to_string(),
+                    height,
+                })
+            })
+            .collect::<Result<Vec<RenditionSize>, String>>()?;
+        if parsed_sizes.is_empty() {
+            return Err("At least one preview size is needed".to_string());
+        }
+        parsed_sizes.sort_by_key(|size| size.height);
+
+        let mut parsed_formats = Vec::new();
+        for format in formats
+            .split(',')
+            .filter(|format| !format.trim().is_empty())
+        {
+            let format = RenditionFormat::parse(format)
+                .ok_or_else(|| format!("Unknown preview format {format}"))?;
+            if !parsed_formats.contains(&format) {
+                parsed_formats.push(format);
+            }
+        }
+        Ok(PreviewConfig {
+            sizes: parsed_sizes,
+            formats: parsed_formats,
+        })
+    }
+}
+
+// An encoded rendition waiting to be stored
+pub struct EncodedRendition {
+    pub name: String,
+    pub format: RenditionFormat,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: Vec<u8>,
+}
+
+impl EncodedRendition {
+    pub fn object_id(&self, media_id: &str) -> String {
+        format!(
+            "{RENDITION_ID_PREFIX}{media_id}/{}.{}",
+            self.name,
+            self.format.extension()
+        )
+    }
+}
+
+// Every size in every format of the config and in the fallback format, smaller images
+// are never scaled up. A display copy is the whole image in the fallback format, added last
+pub fn make_renditions(
+    image: &DynamicImage,
+    config: &PreviewConfig,
+    display_copy: bool,
+) -> Result<Vec<EncodedRendition>, String> {
+    let fallback = RenditionFormat::fallback(image);
+    let mut renditions = Vec::new();
+    for size in &config.sizes {
+        let resized = resize(image, size.height);
+        let (width, height) = resized.dimensions();
+        let formats = config
+            .formats
+            .iter()
+            .copied()
+            .filter(|format| *format != fallback)
+            .chain([fallback]);
+        for format in formats {
+            renditions.push(EncodedRendition {
+                name: size.name.clone(),
+                format,
+                width,
+                height,
+                bytes: format.encode(&resized)?,
+            });
+        }
+    }
+    if display_copy {
+        let (width, height) = image.dimensions();
+        renditions.push(EncodedRendition {
+            name: FULL_RENDITION.to_string(),
+            format: fallback,
+            width,
+            height,
+            bytes: fallback.encode(image)?,
+        });
+    }
+    Ok(renditions)
+}
+
+fn resize(image: &DynamicImage, height: u32) -> DynamicImage {
+    let (orig_width, orig_height) = image.dimensions();
+    if height >= orig_height {
+        return image.clone();
+    }
+    let aspect_ratio = orig_width as f32 / orig_height as f32;
+    let width = ((height as f32 * aspect_ratio) as u32).max(1);
+    image.resize(width, height, Triangle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{RgbImage, RgbaImage};
+
+    fn size(name: &str, height: u32) -> RenditionSize {
+        RenditionSize {
+            name: name.to_string(),
+            height,
+        }
+    }
+
+    // Name, format and dimensions of every rendition, in the order they are made
+    fn made(renditions: &[EncodedRendition]) -> Vec<(&str, RenditionFormat, u32, u32)> {
+        renditions
+            .iter()
+            .map(|rendition| {
+                (
+                    rendition.name.as_str(),
+                    rendition.format,
+                    rendition.width,
+                    rendition.height,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn config_sizes_are_sorted_and_formats_deduplicated() {
+        let config = PreviewConfig::parse(" display:1440, grid:256,", "avif, JPG,Avif").unwrap();
+        assert_eq!(config.sizes, vec![size("grid", 256), size("display", 1440)]);
+        assert_eq!(
+            config.formats,
+            vec![RenditionFormat::Avif, RenditionFormat::Jpeg]
+        );
+
+        // Without formats only the fallback is made
+        let config = PreviewConfig::parse("grid:256", "").unwrap();
+        assert!(config.formats.is_empty());
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        for (sizes, formats) in [
+            ("", "avif"),
+            (" , ", "avif"),
+            ("grid", "avif"),
+            ("grid:0", "avif"),
+            ("grid:-1", "avif"),
+            ("grid:large", "avif"),
+            ("grid:256", "webp"),
+        ] {
+            assert!(
+                PreviewConfig::parse(sizes, formats).is_err(),
+                "{sizes} {formats}"
+            );
+        }
+    }
+
+    #[test]
+    fn every_size_is_made_in_every_format_and_the_fallback() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(800, 400));
+        let config = PreviewConfig {
+            sizes: vec![size("grid", 100), size("display", 1000)],
+            formats: vec![RenditionFormat::Png, RenditionFormat::Jpeg],
+        };
+        let renditions = make_renditions(&image, &config, false).unwrap();
+        // The JPEG fallback is made once, smaller images are not scaled up
+        assert_eq!(
+            made(&renditions),
+            vec![
+                ("grid", RenditionFormat::Png, 200, 100),
+                ("grid", RenditionFormat::Jpeg, 200, 100),
+                ("display", RenditionFormat::Png, 800, 400),
+                ("display", RenditionFormat::Jpeg, 800, 400),
+            ]
+        );
+        let encoded = image::load_from_memory(&renditions[1].bytes).unwrap();
+        assert_eq!(encoded.dimensions(), (200, 100));
+        assert_eq!(
+            renditions[0].object_id("photo"),
+            "rend/photo/grid.png".to_string()
+        );
+    }
+
+    #[test]
+    fn transparent_images_fall_back_to_png() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(300, 200));
+        let config = PreviewConfig {
+            sizes: vec![size("grid", 100)],
+            formats: vec![RenditionFormat::Jpeg],
+        };
+        let renditions = make_renditions(&image, &config, false).unwrap();
+        assert_eq!(
+            made(&renditions),
+            vec![
+                ("grid", RenditionFormat::Jpeg, 150, 100),
+                ("grid", RenditionFormat::Png, 150, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_full_copy_is_only_made_for_display_copies() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(400, 300));
+        let config = PreviewConfig {
+            sizes: vec![size("grid", 100), size("display", 1440)],
+            formats: vec![],
+        };
+        let renditions = make_renditions(&image, &config, false).unwrap();
+        assert!(renditions
+            .iter()
+            .all(|rendition| rendition.name != FULL_RENDITION));
+
+        // It comes last at the size of the original, which the largest size can match,
+        // so picking by height has to leave it out
+        let renditions = make_renditions(&image, &config, true).unwrap();
+        assert_eq!(
+            made(&renditions),
+            vec![
+                ("grid", RenditionFormat::Jpeg, 133, 100),
+                ("display", RenditionFormat::Jpeg, 400, 300),
+                (FULL_RENDITION, RenditionFormat::Jpeg, 400, 300),
+            ]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use serde::Deserialize;
+use std::{error::Error, fs};
+
+#[derive(Deserialize, Debug)]
+pub struct Config {
+    #[serde(default = "listen_on_default")]
+    pub listen_on: String,
+    pub jwt_secret: String,
+    pub database: DatabaseConfig,
+    pub object_storage: ObjectStorageConfig,
+    #[serde(default)]
+    pub workers: WorkersConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub clustering: ClusteringConfig,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DatabaseConfig {
+    pub username: String,
+    pub password: String,
+    pub host: String,
+    #[serde(default = "database_port_default")]
+    pub port: u16,
+    pub name: String,
+    #[serde(default = "database_max_connections_default")]
+    pub max_connections: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ObjectStorageConfig {
+    #[serde(default = "object_storage_endpoint_default")]
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WorkersConfig {
+    #[serde(default = "worker_concurrency_default")]
+    pub preview_concurrency: usize,
+    #[serde(default = "worker_concurrency_default")]
+    pub metadata_concurrency: usize,
+    #[serde(default = "face_crop_interval_default")]
+    pub face_crop_interval_seconds: u64,
+    // `name:height` pairs, the smallest is the preview of the media
+    #[serde(default = "preview_sizes_default")]
+    pub preview_sizes: String,
+    // Made next to the JPEG of every size
+    #[serde(default = "preview_formats_default")]
+    pub preview_formats: String,
+}
+
+impl Default for WorkersConfig {
+    fn default() -> Self {
+        WorkersConfig {
+            preview_concurrency: worker_concurrency_default(),
+            metadata_concurrency: worker_concurrency_default(),
+            face_crop_interval_seconds: face_crop_interval_default(),
+            preview_sizes: preview_sizes_default(),
+            preview_formats: preview_formats_default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchConfig {
+    #[serde(default = "search_timeout_seconds_default")]
+    pub timeout_seconds: u64,
+    #[serde(default = "search_cache_ttl_seconds_default")]
+    pub cache_ttl_seconds: u64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            timeout_seconds: search_timeout_seconds_default(),
+            cache_ttl_seconds: search_cache_ttl_seconds_default(),
+        }
+    }
+}
+
+// Unset values fall back to the defaults of the clustering worker
+#[derive(Deserialize, Debug, Default)]
+pub struct ClusteringConfig {
+    pub max_distance: Option<f32>,
+    pub min_faces: Option<usize>,
+    pub auto_assign_distance: Option<f32>,
+    pub suggestion_distance: Option<f32>,
+    pub interval_seconds: Option<u64>,
+}
+
+fn listen_on_default() -> String {
+    "0.0.0.0:8080".to_string()
+}
+
+fn database_port_default() -> u16 {
+    5432
+}
+
+// Small boards run out of memory long before postgres runs out of connections
+fn database_max_connections_default() -> u32 {
+    10
+}
+
+fn object_storage_endpoint_default() -> String {
+    "http://localhost".to_string()
+}
+
+fn face_crop_interval_default() -> u64 {
+    300
+}
+
+fn preview_sizes_default() -> String {
+    "grid:256,display:1440".to_string()
+}
+
+fn preview_formats_default() -> String {
+    "avif".to_string()
+}
+
+fn worker_concurrency_default() -> usize {
+    2
+}
+
+fn search_timeout_seconds_default() -> u64 {
+    10
+}
+
+fn search_cache_ttl_seconds_default() -> u64 {
+    300
+}
+
+impl Config {
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Couldn't read config file {path}: {err}"))?;
+        let config = toml::from_str(&contents)
+            .map_err(|err| format!("Couldn't parse config file {path}: {err}"))?;
+        Ok(config)
+    }
+}
+
+impl DatabaseConfig {
+    pub fn connection_string(&self) -> String {
+        format!(
+            "postgresql://{}:{}@{}:{}/{}",
+            self.username, self.password, self.host, self.port, self.name
+        )
+    }
+}
@@ -0,0 +1,167 @@
+mod config;
+use api::{router, SearchCache, ServerConfig};
+use clustering::ClusteringConfig;
+use config::{Config, ObjectStorageConfig};
+use database::{DbManager, Repository};
+use messaging::{InMemoryBus, MessageBus};
+use preview::PreviewConfig;
+use s3::{creds::Credentials, error::S3Error, Bucket, BucketConfiguration, Region};
+use std::{env, error::Error, sync::Arc, time::Duration};
+
+const DEFAULT_CONFIG_PATH: &str = "chronolens.toml";
+
+// Runs the api and the preview, face crop, metadata and clustering workers in a single process.
+// The services share one database pool and bucket and talk through an in-process queue,
+// so only postgres and the object storage need to run next to it.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let config_path = env::args()
+        .nth(1)
+        .unwrap_or(DEFAULT_CONFIG_PATH.to_string());
+    let config = match Config::from_file(&config_path) {
+        Ok(config) => config,
+        Err(err) => panic!("{}", err),
+    };
+
+    let preview_config = match PreviewConfig::parse(
+        &config.workers.preview_sizes,
+        &config.workers.preview_formats,
+    ) {
+        Ok(preview_config) => preview_config,
+        Err(err) => panic!("{}", err),
+    };
+
+    let database = match DbManager::connect(
+        &config.database.connection_string(),
+        config.database.max_connections,
+    )
+    .await
+    {
+        Ok(database) => database,
+        Err(err) => panic!("{}", err),
+    };
+
+    let bucket = match setup_bucket(&config.object_storage).await {
+        Ok(bucket) => bucket,
+        Err(err) => panic!("{}", err),
+    };
+
+    let database: Arc<dyn Repository> = Arc::new(database);
+    let bus: Arc<dyn MessageBus> = Arc::new(InMemoryBus::new());
+
+    let preview_worker = tokio::spawn(preview::run(
+        bus.clone(),
+        bucket.clone(),
+        database.clone(),
+        config.workers.preview_concurrency,
+        preview_config,
+    ));
+    let face_crop_worker = tokio::spawn(preview::run_face_crops(
+        bus.clone(),
+        bucket.clone(),
+        database.clone(),
+        Duration::from_secs(config.workers.face_crop_interval_seconds),
+    ));
+    let metadata_worker = tokio::spawn(metadata::run(
+        bus.clone(),
+        bucket.clone(),
+        database.clone(),
+        config.workers.metadata_concurrency,
+    ));
+
+    let clustering_defaults = ClusteringConfig::default();
+    let clustering_worker = tokio::spawn(clustering::run(
+        bus.clone(),
+        database.clone(),
+        ClusteringConfig {
+            max_distance: config
+                .clustering
+                .max_distance
+                .unwrap_or(clustering_defaults.max_distance),
+            min_faces: config
+                .clustering
+                .min_faces
+                .unwrap_or(clustering_defaults.min_faces),
+            auto_assign_distance: config
+                .clustering
+                .auto_assign_distance
+                .unwrap_or(clustering_defaults.auto_assign_distance),
+            suggestion_distance: config
+                .clustering
+                .suggestion_distance
+                .unwrap_or(clustering_defaults.suggestion_distance),
+            interval: config
+                .clustering
+                .interval_seconds
+                .map(Duration::from_secs)
+                .unwrap_or(clustering_defaults.interval),
+        },
+    ));
+
+    let server_config = ServerConfig {
+        database,
+        secret: config.jwt_secret,
+        bucket,
+        bus,
+        search_cache: Arc::new(SearchCache::new(Duration::from_secs(
+            config.search.cache_ttl_seconds,
+        ))),
+        search_timeout: Duration::from_secs(config.search.timeout_seconds),
+    };
+
+    let listener = tokio::net::TcpListener::bind(&config.listen_on).await?;
+    let server = axum::serve(listener, router(server_config));
+
+    // None of the services is expected to return, stop the whole process if one of them does
+    tokio::select! {
+        result = server => result?,
+        result = preview_worker => {
+            result??;
+            return Err("The preview worker stopped".into());
+        }
+        result = face_crop_worker => {
+            result??;
+            return Err("The face crop worker stopped".into());
+        }
+        result = metadata_worker => {
+            result??;
+            return Err("The metadata worker stopped".into());
+        }
+        result = clustering_worker => {
+            result??;
+            return Err("The clustering worker stopped".into());
+        }
+    }
+
+    Ok(())
+}
+
+async fn setup_bucket(config: &ObjectStorageConfig) -> Result<Box<Bucket>, S3Error> {
+    // connect to s3 storage
+    let region = Region::Custom {
+        region: config.region.to_string(),
+        endpoint: config.endpoint.to_string(),
+    };
+    let credentials = Credentials::new(
+        Some(&config.access_key),
+        Some(&config.secret_key),
+        None,
+        None,
+        None,
+    )?;
+
+    let mut bucket =
+        Bucket::new(&config.bucket, region.clone(), credentials.clone())?.with_path_style();
+
+    if !bucket.exists().await? {
+        bucket = Bucket::create_with_path_style(
+            &config.bucket,
+            region,
+            credentials,
+            BucketConfiguration::default(),
+        )
+        .await?
+        .bucket;
+    }
+    Ok(bucket)
+}